@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use chrono::*;
+use git2::{BlameOptions, Oid};
+
+use crate::error::{MetalGitError, ResultExt};
+use crate::repository_manager::RepositoryManager;
+
+pub struct BlameChunk {
+    pub commit_oid: Oid,
+    pub author_name: String,
+    pub summary: String,
+    pub time: String,
+    pub time_seconds: i64,
+    pub orig_path: Option<String>,
+    pub start_line: usize,
+    pub lines: Vec<String>,
+}
+
+pub struct BlameResult {
+    pub path: String,
+    pub revision: Oid,
+    pub chunks: Vec<BlameChunk>,
+}
+
+pub fn blame_file(repository_manager: &RepositoryManager,
+                  path: &str,
+                  revision: Oid)
+                  -> Result<BlameResult, MetalGitError> {
+    let repo = repository_manager.open()?;
+
+    let commit = repo.find_commit(revision).oid_context("Failed to find the commit", revision)?;
+    let entry = commit.tree()
+                      .and_then(|x| x.get_path(Path::new(path)))
+                      .path_context("Failed to find the file", Path::new(path))?;
+    let blob = repo.find_blob(entry.id()).path_context("Failed to read", Path::new(path))?;
+    let content = String::from_utf8_lossy(blob.content()).into_owned();
+    let content_lines = content.lines().collect::<Vec<_>>();
+
+    let mut opts = BlameOptions::new();
+    opts.newest_commit(revision);
+    let blame = repo.blame_file(Path::new(path), Some(&mut opts)).path_context("Failed to blame", Path::new(path))?;
+
+    let mut chunks = Vec::new();
+    for hunk in blame.iter() {
+        let hunk_commit = repo.find_commit(hunk.final_commit_id())
+                              .oid_context("Failed to find the commit", hunk.final_commit_id())?;
+        let signature = hunk.final_signature();
+
+        // final_start_line is 1-based
+        let start_line = hunk.final_start_line();
+        let lines = content_lines.iter()
+                                 .skip(start_line - 1)
+                                 .take(hunk.lines_in_hunk())
+                                 .map(|x| x.to_string())
+                                 .collect();
+
+        chunks.push(BlameChunk {
+            commit_oid: hunk.final_commit_id(),
+            author_name: signature.name().unwrap_or("").to_string(),
+            summary: hunk_commit.summary().unwrap_or("").to_string(),
+            time: format_time(&signature.when()),
+            time_seconds: signature.when().seconds(),
+            orig_path: hunk.path().and_then(|p| p.to_str()).map(|s| s.to_string()),
+            start_line,
+            lines,
+        });
+    }
+
+    Ok(BlameResult {
+        path: path.to_string(),
+        revision,
+        chunks,
+    })
+}
+
+// returns the revision and the path to blame the lines of the chunk before it was changed
+pub fn parent_revision(repository_manager: &RepositoryManager,
+                       chunk: &BlameChunk)
+                       -> Result<Option<(Oid, String)>, MetalGitError> {
+    let repo = repository_manager.open()?;
+    let commit = repo.find_commit(chunk.commit_oid).oid_context("Failed to find the commit", chunk.commit_oid)?;
+
+    if commit.parent_count() == 0 {
+        return Ok(None);
+    }
+
+    let parent_oid = commit.parent_id(0).oid_context("Failed to find the parent of", chunk.commit_oid)?;
+    Ok(chunk.orig_path.clone().map(|path| (parent_oid, path)))
+}
+
+impl BlameResult {
+    // 0.0 for the oldest chunk, 1.0 for the newest chunk
+    pub fn age_ratio(&self, chunk: &BlameChunk) -> f64 {
+        let min = self.chunks.iter().map(|x| x.time_seconds).min().unwrap_or(0);
+        let max = self.chunks.iter().map(|x| x.time_seconds).max().unwrap_or(0);
+
+        if max == min {
+            return 1.0;
+        }
+
+        (chunk.time_seconds - min) as f64 / (max - min) as f64
+    }
+}
+
+fn format_time(time: &git2::Time) -> String {
+    let datetime = FixedOffset::east_opt(time.offset_minutes() * 60).unwrap().timestamp_opt(time.seconds(), 0).unwrap();
+    format!("{}", datetime.format("%Y-%m-%d %H:%M:%S %Z"))
+}
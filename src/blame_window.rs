@@ -0,0 +1,278 @@
+use glib::StaticType;
+use gtk::prelude::{GtkListStoreExtManual, TreeViewColumnExt, WidgetExtManual};
+use gtk::traits::{
+    BoxExt, ButtonExt, CellRendererTextExt, ContainerExt, GtkListStoreExt, GtkWindowExt,
+    LabelExt, TreeModelExt, TreeSelectionExt, TreeViewExt, WidgetExt,
+};
+use gtk::Inhibit;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use git2::Oid;
+
+use crate::blame_util;
+use crate::blame_util::BlameResult;
+use crate::error::MetalGitError;
+use crate::repository_manager::RepositoryManager;
+
+pub struct BlameWindow {
+    window: gtk::Window,
+
+    title_label: gtk::Label,
+    back_button: gtk::Button,
+    parent_button: gtk::Button,
+
+    blame_list_store: gtk::ListStore,
+    blame_tree_view: gtk::TreeView,
+
+    repository_manager: Rc<RepositoryManager>,
+    current_result: RefCell<Option<BlameResult>>,
+    back_stack: RefCell<Vec<(String, Oid)>>,
+
+    commit_selected: RefCell<Box<dyn Fn(Oid)>>,
+}
+
+const COLUMN_COMMIT: u32 = 0;
+const COLUMN_AUTHOR_NAME: u32 = 1;
+const COLUMN_TIME: u32 = 2;
+const COLUMN_LINE_NUMBER: u32 = 3;
+const COLUMN_CONTENT: u32 = 4;
+const COLUMN_BACKGROUND: u32 = 5;
+const COLUMN_CHUNK_INDEX: u32 = 6;
+const COLUMN_SUMMARY: u32 = 7;
+
+// colors of the oldest and the newest chunk
+const OLD_COLOR: (f64, f64, f64) = (232.0, 238.0, 247.0);
+const NEW_COLOR: (f64, f64, f64) = (247.0, 217.0, 176.0);
+
+impl BlameWindow {
+    pub fn new(repository_manager: Rc<RepositoryManager>) -> Rc<BlameWindow> {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_default_size(800, 600);
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 0);
+
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let title_label = gtk::Label::new(None);
+        title_label.set_xalign(0.0);
+        hbox.pack_start(&title_label, true, true, 6);
+        let back_button = gtk::Button::with_label("Back");
+        hbox.pack_start(&back_button, false, true, 0);
+        let parent_button = gtk::Button::with_label("Blame parent");
+        hbox.pack_start(&parent_button, false, true, 0);
+        vbox.pack_start(&hbox, false, true, 6);
+
+        let blame_list_store = gtk::ListStore::new(&[
+            String::static_type(), // COLUMN_COMMIT
+            String::static_type(), // COLUMN_AUTHOR_NAME
+            String::static_type(), // COLUMN_TIME
+            u32::static_type(),    // COLUMN_LINE_NUMBER
+            String::static_type(), // COLUMN_CONTENT
+            String::static_type(), // COLUMN_BACKGROUND
+            u32::static_type(),    // COLUMN_CHUNK_INDEX
+            String::static_type(), // COLUMN_SUMMARY
+        ]);
+
+        let blame_tree_view = gtk::TreeView::with_model(&blame_list_store);
+        for &(title, column, monospace) in &[("Commit", COLUMN_COMMIT, true),
+                                             ("Author", COLUMN_AUTHOR_NAME, false),
+                                             ("Time", COLUMN_TIME, false),
+                                             ("Line", COLUMN_LINE_NUMBER, true),
+                                             ("Content", COLUMN_CONTENT, true)] {
+            let renderer = gtk::CellRendererText::new();
+            if monospace {
+                renderer.set_family(Some("Monospace"));
+            }
+            let col = gtk::TreeViewColumn::new();
+            col.set_title(title);
+            col.pack_start(&renderer, false);
+            col.add_attribute(&renderer, "text", column as i32);
+            col.add_attribute(&renderer, "cell-background", COLUMN_BACKGROUND as i32);
+            blame_tree_view.append_column(&col);
+        }
+        blame_tree_view.set_tooltip_column(COLUMN_SUMMARY as i32);
+
+        let scrolled = gtk::ScrolledWindow::builder().build();
+        scrolled.add(&blame_tree_view);
+        vbox.pack_start(&scrolled, true, true, 0);
+
+        window.add(&vbox);
+
+        let blame_window = Rc::new(BlameWindow {
+            window,
+            title_label,
+            back_button,
+            parent_button,
+            blame_list_store,
+            blame_tree_view,
+            repository_manager,
+            current_result: RefCell::new(None),
+            back_stack: RefCell::new(Vec::new()),
+            commit_selected: RefCell::new(Box::new(|_| {})),
+        });
+
+        let w = Rc::downgrade(&blame_window);
+        blame_window.window.connect_delete_event(move |_, _| {
+            w.upgrade().unwrap().hide();
+            Inhibit(true)
+        });
+
+        let w = Rc::downgrade(&blame_window);
+        blame_window.blame_tree_view.selection().connect_changed(move |selection| {
+            if let Some((model, iter)) = selection.selected() {
+                let index = model.value(&iter, COLUMN_CHUNK_INDEX as i32)
+                                 .get::<u32>()
+                                 .unwrap();
+                w.upgrade().unwrap().chunk_selected(index);
+            }
+        });
+
+        let w = Rc::downgrade(&blame_window);
+        blame_window.parent_button.connect_clicked(move |_| {
+            dialog_when_error!("{}", w.upgrade().unwrap().parent_button_clicked());
+        });
+
+        let w = Rc::downgrade(&blame_window);
+        blame_window.back_button.connect_clicked(move |_| {
+            dialog_when_error!("{}", w.upgrade().unwrap().back_button_clicked());
+        });
+
+        blame_window
+    }
+
+    pub fn show(&self) {
+        self.window.show_all();
+        self.window.present();
+    }
+
+    pub fn hide(&self) {
+        self.window.hide();
+    }
+
+    // the window is not shown again
+    pub fn close(&self) {
+        // nothing shows or refreshes the window after this
+        unsafe {
+            self.window.destroy();
+        }
+    }
+
+    pub fn open(&self, path: &str, revision: Oid) -> Result<(), MetalGitError> {
+        self.back_stack.borrow_mut().clear();
+        self.blame(path, revision)?;
+        self.show();
+
+        Ok(())
+    }
+
+    fn blame(&self, path: &str, revision: Oid) -> Result<(), MetalGitError> {
+        let result = blame_util::blame_file(&self.repository_manager, path, revision)?;
+
+        self.blame_list_store.clear();
+
+        for (i, chunk) in result.chunks.iter().enumerate() {
+            let background = Self::age_color(result.age_ratio(chunk));
+            let commit = chunk.commit_oid.to_string()[..7].to_string();
+            let chunk_index = i as u32;
+
+            for (j, line) in chunk.lines.iter().enumerate() {
+                let line_number = (chunk.start_line + j) as u32;
+                // commit information is shown only on the first line of a chunk
+                let (commit, author_name, time) = if j == 0 {
+                    (commit.as_str(), chunk.author_name.as_str(), chunk.time.as_str())
+                } else {
+                    ("", "", "")
+                };
+
+                self.blame_list_store.insert_with_values(None,
+                                                         &[(COLUMN_COMMIT, &commit),
+                                                           (COLUMN_AUTHOR_NAME, &author_name),
+                                                           (COLUMN_TIME, &time),
+                                                           (COLUMN_LINE_NUMBER, &line_number),
+                                                           (COLUMN_CONTENT, line),
+                                                           (COLUMN_BACKGROUND, &background),
+                                                           (COLUMN_CHUNK_INDEX, &chunk_index),
+                                                           (COLUMN_SUMMARY, &chunk.summary)]);
+            }
+        }
+
+        let title = format!("{} @ {}", path, revision);
+        self.title_label.set_text(&title);
+        self.window.set_title(&format!("Blame: {} - Metal Git", title));
+
+        self.current_result.replace(Some(result));
+        self.back_button.set_sensitive(!self.back_stack.borrow().is_empty());
+
+        Ok(())
+    }
+
+    fn age_color(ratio: f64) -> String {
+        let mix = |old: f64, new: f64| (old + (new - old) * ratio) as u8;
+        format!("#{:02x}{:02x}{:02x}",
+                mix(OLD_COLOR.0, NEW_COLOR.0),
+                mix(OLD_COLOR.1, NEW_COLOR.1),
+                mix(OLD_COLOR.2, NEW_COLOR.2))
+    }
+
+    fn selected_chunk_index(&self) -> Option<usize> {
+        self.blame_tree_view.selection().selected().map(|(model, iter)| {
+            model.value(&iter, COLUMN_CHUNK_INDEX as i32).get::<u32>().unwrap() as usize
+        })
+    }
+
+    fn chunk_selected(&self, chunk_index: u32) {
+        let oid = self.current_result
+                      .borrow()
+                      .as_ref()
+                      .map(|result| result.chunks[chunk_index as usize].commit_oid);
+
+        if let Some(oid) = oid {
+            self.commit_selected.borrow()(oid);
+        }
+    }
+
+    fn parent_button_clicked(&self) -> Result<(), MetalGitError> {
+        let chunk_index = match self.selected_chunk_index() {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        let (current, parent) = {
+            let result_ref = self.current_result.borrow();
+            let result = match result_ref.as_ref() {
+                Some(result) => result,
+                None => return Ok(()),
+            };
+
+            let chunk = &result.chunks[chunk_index];
+            let parent = blame_util::parent_revision(&self.repository_manager, chunk)?;
+            ((result.path.clone(), result.revision), parent)
+        };
+
+        match parent {
+            Some((parent_oid, parent_path)) => {
+                self.blame(&parent_path, parent_oid)?;
+                self.back_stack.borrow_mut().push(current);
+                self.back_button.set_sensitive(true);
+                Ok(())
+            }
+            None => Err(MetalGitError::new("The commit has no parent to blame")),
+        }
+    }
+
+    fn back_button_clicked(&self) -> Result<(), MetalGitError> {
+        let previous = self.back_stack.borrow_mut().pop();
+        if let Some((path, revision)) = previous {
+            self.blame(&path, revision)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn connect_commit_selected<F>(&self, callback: F)
+        where F: Fn(Oid) + 'static
+    {
+        *self.commit_selected.borrow_mut() = Box::new(callback);
+    }
+}
@@ -1,107 +1,405 @@
-use git2::{DiffOptions, Error, Oid};
+use git2::Oid;
 use glib::{Cast, StaticType};
+use gtk::prelude::ComboBoxExtManual;
 use gtk::prelude::GtkListStoreExt;
 use gtk::prelude::GtkListStoreExtManual;
 use gtk::prelude::TreeViewColumnExt;
 use gtk::traits::{
-    ContainerExt, PanedExt, TextViewExt, TreeModelExt, TreeSelectionExt, TreeViewExt,
+    BoxExt, ButtonExt, ComboBoxExt, ComboBoxTextExt, ContainerExt, LabelExt, PanedExt,
+    TreeModelExt, TreeSelectionExt, TreeViewExt, WidgetExt,
 };
 use gtk::Orientation;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
+use crate::blame_window::BlameWindow;
 use crate::commit_diff_util;
-use crate::commit_diff_util::ListCommitDiffResult;
-use crate::diff_text_view_util;
-use crate::diff_text_view_util::create_diff_text_buffer;
+use crate::commit_diff_util::{DiffSettings, ListCommitDiffResult};
+use crate::diff_content;
+use crate::error::MetalGitError;
+use crate::diff_options_bar::DiffOptionsBar;
+use crate::diff_view::DiffView;
+use crate::pickaxe::{PickaxeMatcher, PickaxeQuery};
 use crate::repository_manager::RepositoryManager;
 
 pub struct CommitDiffPanel {
-    paned: gtk::Paned,
+    container: gtk::Box,
+
+    compare_box: gtk::Box,
+    compare_label: gtk::Label,
+    swap_button: gtk::Button,
+
+    merge_box: gtk::Box,
+    parent_combo_box: gtk::ComboBoxText,
+    merge_parent_count: Cell<usize>,
+    updating_parent_combo_box: Cell<bool>,
+
+    diff_options_bar: Rc<DiffOptionsBar>,
 
     diff_list_store: gtk::ListStore,
     diff_tree_view: gtk::TreeView,
+    summary_label: gtk::Label,
 
-    commit_text_view: gtk::TextView,
+    diff_view: Rc<DiffView>,
+    show_more_button: gtk::Button,
+    max_lines: Cell<Option<usize>>,
+    // None while the diff of all files is shown
+    selected_file: Cell<Option<u32>>,
+
+    blame_window: Rc<BlameWindow>,
 
     repository_manager: Rc<RepositoryManager>,
     current_list_result: RefCell<Option<Rc<ListCommitDiffResult>>>,
+    highlight: RefCell<Option<PickaxeMatcher>>,
 }
 
 const COLUMN_FILENAME: u32 = 0;
 const COLUMN_INDEX: u32 = 1;
+const COLUMN_STATUS: u32 = 2;
+const COLUMN_CHANGES: u32 = 3;
+const COLUMN_BAR: u32 = 4;
+const COLUMN_CHURN: u32 = 5;
+
+// in characters, like `git diff --stat`
+const STAT_BAR_WIDTH: usize = 20;
 
 impl CommitDiffPanel {
     pub fn new(repository_manager: Rc<RepositoryManager>) -> Rc<CommitDiffPanel> {
+        let container = gtk::Box::new(Orientation::Vertical, 0);
+
+        // shown only when two commits are compared
+        let compare_box = gtk::Box::new(Orientation::Horizontal, 6);
+        compare_box.set_no_show_all(true);
+        let compare_label = gtk::Label::new(None);
+        compare_label.set_xalign(0.0);
+        compare_label.show();
+        compare_box.pack_start(&compare_label, true, true, 6);
+        let swap_button = gtk::Button::with_label("Swap direction");
+        swap_button.show();
+        compare_box.pack_start(&swap_button, false, true, 0);
+        container.pack_start(&compare_box, false, true, 0);
+
+        // shown only when a merge commit is selected
+        let merge_box = gtk::Box::new(Orientation::Horizontal, 6);
+        merge_box.set_no_show_all(true);
+        let merge_label = gtk::Label::new(Some("Diff against:"));
+        merge_label.show();
+        merge_box.pack_start(&merge_label, false, true, 6);
+        let parent_combo_box = gtk::ComboBoxText::new();
+        parent_combo_box.show();
+        merge_box.pack_start(&parent_combo_box, true, true, 0);
+        container.pack_start(&merge_box, false, true, 0);
+
+        let diff_options_bar = DiffOptionsBar::new();
+        container.pack_start(&diff_options_bar.widget(), false, true, 0);
+
         let paned = gtk::Paned::new(Orientation::Horizontal);
 
         let diff_list_store = gtk::ListStore::new(&[
             String::static_type(), // COLUMN_FILENAME
             u32::static_type(),    // COLUMN_INDEX
+            String::static_type(), // COLUMN_STATUS
+            String::static_type(), // COLUMN_CHANGES
+            String::static_type(), // COLUMN_BAR
+            u32::static_type(),    // COLUMN_CHURN
         ]);
 
         let diff_tree_view = gtk::TreeView::new();
         diff_tree_view.set_model(Some(&diff_list_store));
 
-        let renderer = gtk::CellRendererText::new();
-        let col = gtk::TreeViewColumn::new();
-        col.set_title("Filename");
-        col.pack_start(&renderer, false);
-        col.add_attribute(&renderer, "text", COLUMN_FILENAME as i32);
-        diff_tree_view.append_column(&col);
+        // the list is sorted by clicking the headers; the bar is sorted by churn too
+        let columns = [
+            ("", "text", COLUMN_STATUS, COLUMN_STATUS),
+            ("Filename", "text", COLUMN_FILENAME, COLUMN_FILENAME),
+            ("Changes", "text", COLUMN_CHANGES, COLUMN_CHURN),
+            ("", "markup", COLUMN_BAR, COLUMN_CHURN),
+        ];
+        for (title, attribute, column, sort_column) in columns {
+            let renderer = gtk::CellRendererText::new();
+            let col = gtk::TreeViewColumn::new();
+            col.set_title(title);
+            col.pack_start(&renderer, false);
+            col.add_attribute(&renderer, attribute, column as i32);
+            col.set_sort_column_id(sort_column as i32);
+            col.set_resizable(column == COLUMN_FILENAME);
+            diff_tree_view.append_column(&col);
+        }
 
+        let list_box = gtk::Box::new(Orientation::Vertical, 0);
         let scrolled = gtk::ScrolledWindow::builder().build();
         scrolled.add(&diff_tree_view);
-        paned.pack1(&scrolled, true, false);
+        list_box.pack_start(&scrolled, true, true, 0);
+        let summary_label = gtk::Label::new(None);
+        summary_label.set_xalign(0.0);
+        list_box.pack_start(&summary_label, false, true, 3);
+        paned.pack1(&list_box, true, false);
 
-        let scrolled = gtk::ScrolledWindow::builder().build();
+        let diff_view = DiffView::new();
+
+        let text_box = gtk::Box::new(Orientation::Vertical, 0);
+        text_box.pack_start(&diff_view.widget(), true, true, 0);
+
+        // shown only when a diff is too long
+        let show_more_button = gtk::Button::with_label("Show more");
+        show_more_button.set_no_show_all(true);
+        text_box.pack_start(&show_more_button, false, true, 0);
+
+        paned.pack2(&text_box, true, false);
 
-        let diff_text_buffer = create_diff_text_buffer();
-        let commit_text_view = gtk::TextView::builder()
-            .editable(false)
-            .buffer(&diff_text_buffer)
-            .monospace(true)
-            .build();
-        scrolled.add(&commit_text_view);
+        container.pack_start(&paned, true, true, 0);
 
-        paned.pack2(&scrolled, true, false);
+        let blame_window = BlameWindow::new(Rc::clone(&repository_manager));
 
         let commit_diff_panel = Rc::new(CommitDiffPanel {
-            paned,
+            container,
+            compare_box,
+            compare_label,
+            swap_button,
+            merge_box,
+            parent_combo_box,
+            merge_parent_count: Cell::new(0),
+            updating_parent_combo_box: Cell::new(false),
+            diff_options_bar,
             diff_list_store,
             diff_tree_view,
-            commit_text_view,
+            summary_label,
+            diff_view,
+            show_more_button,
+            max_lines: Cell::new(Some(diff_content::MAX_DIFF_LINES)),
+            selected_file: Cell::new(None),
+            blame_window,
             repository_manager,
             current_list_result: RefCell::new(None),
+            highlight: RefCell::new(None),
         });
 
         commit_diff_panel.setup_tree_view();
 
+        let w = Rc::downgrade(&commit_diff_panel);
+        commit_diff_panel.swap_button.connect_clicked(move |_| {
+            dialog_when_error!(
+                "Failed to swap direction: {}",
+                w.upgrade().unwrap().swap_direction()
+            );
+        });
+
+        let w = Rc::downgrade(&commit_diff_panel);
+        commit_diff_panel.diff_options_bar.connect_changed(move || {
+            dialog_when_error!(
+                "Failed to apply the diff options: {}",
+                w.upgrade().unwrap().refresh_list()
+            );
+        });
+
+        let w = Rc::downgrade(&commit_diff_panel);
+        commit_diff_panel.show_more_button.connect_clicked(move |_| {
+            dialog_when_error!(
+                "Failed to show the diff: {}",
+                w.upgrade().unwrap().show_more()
+            );
+        });
+
+        let w = Rc::downgrade(&commit_diff_panel);
+        commit_diff_panel
+            .parent_combo_box
+            .connect_changed(move |combo_box| {
+                let w = w.upgrade().unwrap();
+                if w.updating_parent_combo_box.get() {
+                    return;
+                }
+
+                if let Some(index) = combo_box.active() {
+                    dialog_when_error!(
+                        "Failed to diff against the parent: {}",
+                        w.parent_selected(index as usize)
+                    );
+                }
+            });
+
         commit_diff_panel
     }
 
     pub fn container(&self) -> gtk::Container {
-        self.paned.clone().upcast::<gtk::Container>()
+        self.container.clone().upcast::<gtk::Container>()
+    }
+
+    pub fn update_commit(&self, oid: Oid) -> Result<(), MetalGitError> {
+        let result = commit_diff_util::list_commit_diff_files(
+            self.repository_manager.as_ref(),
+            oid,
+            &self.settings(),
+        )?;
+
+        self.compare_box.hide();
+        self.update_parent_combo_box(oid)?;
+        self.show_list_result(result)
+    }
+
+    pub fn update_work_tree(&self) -> Result<(), MetalGitError> {
+        let result = commit_diff_util::list_work_tree_diff_files(
+            self.repository_manager.as_ref(),
+            &self.settings(),
+        )?;
+
+        self.compare_box.hide();
+        self.merge_box.hide();
+        self.show_list_result(result)
+    }
+
+    fn update_parent_combo_box(&self, oid: Oid) -> Result<(), MetalGitError> {
+        let repo = self.repository_manager.open()?;
+        let commit = repo.find_commit(oid)?;
+
+        self.merge_parent_count.set(commit.parent_count());
+        if commit.parent_count() < 2 {
+            self.merge_box.hide();
+            return Ok(());
+        }
+
+        self.updating_parent_combo_box.set(true);
+        self.parent_combo_box.remove_all();
+        for (i, parent) in commit.parents().enumerate() {
+            let text = format!(
+                "Parent {}: {} {}",
+                i + 1,
+                &parent.id().to_string()[..7],
+                parent.summary().unwrap_or("")
+            );
+            self.parent_combo_box.append_text(&text);
+        }
+        self.parent_combo_box.append_text("Combined");
+        self.parent_combo_box.set_active(Some(0));
+        self.updating_parent_combo_box.set(false);
+
+        self.merge_box.show();
+
+        Ok(())
+    }
+
+    fn parent_selected(&self, index: usize) -> Result<(), MetalGitError> {
+        let oid = match self.current_list_result.borrow().as_ref() {
+            Some(list_result) => list_result.current_oid,
+            None => return Ok(()),
+        };
+
+        // the last item is the combined diff
+        let result = if index == self.merge_parent_count.get() {
+            commit_diff_util::list_combined_diff_files(
+                self.repository_manager.as_ref(),
+                oid,
+                &self.settings(),
+            )?
+        } else {
+            commit_diff_util::list_commit_parent_diff_files(
+                self.repository_manager.as_ref(),
+                oid,
+                index,
+                &self.settings(),
+            )?
+        };
+
+        self.show_list_result(result)
+    }
+
+    pub fn compare_commits(&self, old_oid: Oid, new_oid: Oid) -> Result<(), MetalGitError> {
+        let result = commit_diff_util::list_tree_diff_files(
+            self.repository_manager.as_ref(),
+            Some(old_oid),
+            new_oid,
+            &self.settings(),
+        )?;
+
+        self.compare_label.set_text(&format!("{}..{}", old_oid, new_oid));
+        self.compare_box.show();
+        self.merge_box.hide();
+        self.show_list_result(result)
     }
 
-    pub fn update_commit(&self, oid: Oid) -> Result<(), Error> {
-        let result =
-            commit_diff_util::list_commit_diff_files(self.repository_manager.as_ref(), oid)?;
+    fn swap_direction(&self) -> Result<(), MetalGitError> {
+        let oids = self
+            .current_list_result
+            .borrow()
+            .as_ref()
+            .and_then(|x| x.parent_oid.map(|parent_oid| (parent_oid, x.current_oid)));
 
+        if let Some((old_oid, new_oid)) = oids {
+            self.compare_commits(new_oid, old_oid)?;
+        }
+
+        Ok(())
+    }
+
+    fn settings(&self) -> DiffSettings {
+        self.diff_options_bar.settings()
+    }
+
+    // lists the files of the current diff again, as renames depend on the options
+    fn refresh_list(&self) -> Result<(), MetalGitError> {
+        let list_result = match self.current_list_result.borrow().as_ref() {
+            Some(list_result) => Rc::clone(list_result),
+            None => return Ok(()),
+        };
+
+        let repository_manager = self.repository_manager.as_ref();
+        let settings = self.settings();
+        let result = if list_result.work_tree {
+            commit_diff_util::list_work_tree_diff_files(repository_manager, &settings)?
+        } else if list_result.combined {
+            commit_diff_util::list_combined_diff_files(repository_manager, list_result.current_oid, &settings)?
+        } else {
+            commit_diff_util::list_tree_diff_files(
+                repository_manager,
+                list_result.parent_oid,
+                list_result.current_oid,
+                &settings,
+            )?
+        };
+
+        self.show_list_result(result)
+    }
+
+    fn show_list_result(&self, result: ListCommitDiffResult) -> Result<(), MetalGitError> {
         self.diff_list_store.clear();
 
+        let max_changes = result
+            .files
+            .iter()
+            .map(|x| x.insertions + x.deletions)
+            .max()
+            .unwrap_or(0);
+
         for (i, x) in result.files.iter().enumerate() {
             let index: u32 = i as u32;
+            let churn = (x.insertions + x.deletions) as u32;
+            let changes = if x.binary {
+                "Bin".to_string()
+            } else {
+                format!("+{} -{}", x.insertions, x.deletions)
+            };
+            let (insertions, deletions) =
+                commit_diff_util::stat_bar(x.insertions, x.deletions, max_changes, STAT_BAR_WIDTH);
+            let bar = format!(
+                "<tt><span foreground=\"#2e7d32\">{}</span><span foreground=\"#c62828\">{}</span></tt>",
+                "+".repeat(insertions),
+                "-".repeat(deletions)
+            );
+
             self.diff_list_store.insert_with_values(
                 None,
                 &[
                     (COLUMN_FILENAME, &x.format_file_move()),
                     (COLUMN_INDEX, &index),
+                    (COLUMN_STATUS, &x.status.to_string()),
+                    (COLUMN_CHANGES, &changes),
+                    (COLUMN_BAR, &bar),
+                    (COLUMN_CHURN, &churn),
                 ],
             );
         }
+        self.summary_label.set_text(&result.format_summary());
 
         self.current_list_result.replace(Some(Rc::new(result)));
+        self.max_lines.set(Some(diff_content::MAX_DIFF_LINES));
 
         self.show_all_files_diff()?;
 
@@ -113,62 +411,161 @@ impl CommitDiffPanel {
         let w = Rc::downgrade(self);
         selection.connect_changed(move |x| {
             if let Some((model, iter)) = x.selected() {
-                let index = model
-                    .value(&iter, COLUMN_INDEX as i32)
-                    .get::<u32>()
-                    .expect("Incorrect column type");
-                w.upgrade()
-                    .unwrap()
-                    .file_selected(index)
-                    .expect("Failed to select a file");
+                if let Ok(index) = model.value(&iter, COLUMN_INDEX as i32).get::<u32>() {
+                    let w = w.upgrade().unwrap();
+                    w.max_lines.set(Some(diff_content::MAX_DIFF_LINES));
+                    dialog_when_error!("Failed to diff the file: {}", w.file_selected(index));
+                }
             }
         });
+
+        let w = Rc::downgrade(self);
+        self.diff_tree_view
+            .connect_row_activated(move |tree_view, tree_path, _column| {
+                let model = tree_view.model().unwrap();
+                if let Some(iter) = model.iter(tree_path) {
+                    if let Ok(index) = model.value(&iter, COLUMN_INDEX as i32).get::<u32>() {
+                        dialog_when_error!("{}", w.upgrade().unwrap().blame_file(index));
+                    }
+                }
+            });
     }
 
-    fn file_selected(self: &Rc<Self>, file_index: u32) -> Result<(), Error> {
+    fn blame_file(&self, file_index: u32) -> Result<(), MetalGitError> {
         if let Some(list_result) = self.current_list_result.borrow().as_ref() {
-            let repo = self.repository_manager.open()?;
-
             let entry = &list_result.files[file_index as usize];
+            // uncommitted lines are not blamed
+            if list_result.work_tree {
+                return Ok(());
+            }
+            // a deleted file only exists in the parent
+            if entry.status == 'D' {
+                if let (Some(old_file_path), Some(parent_oid)) = (entry.old_file_path.as_ref(), list_result.parent_oid) {
+                    self.blame_window.open(old_file_path, parent_oid)?;
+                }
+            } else if let Some(new_file_path) = entry.new_file_path.as_ref() {
+                self.blame_window.open(new_file_path, list_result.current_oid)?;
+            }
+        }
 
-            let current_commit = repo.find_commit(list_result.current_oid)?;
-            let parent_commit = repo.find_commit(list_result.parent_oid)?;
+        Ok(())
+    }
 
-            let parent_tree = parent_commit.tree()?;
-            let current_tree = current_commit.tree()?;
+    pub fn close(&self) {
+        self.blame_window.close();
+    }
 
-            let new_file_path = entry.new_file_path.as_ref().unwrap();
-            let mut opts = DiffOptions::new();
-            opts.pathspec(new_file_path);
+    // highlights the matches of the pickaxe search
+    pub fn set_highlight(&self, highlight: Option<PickaxeQuery>) {
+        // an invalid pattern highlights nothing
+        self.highlight.replace(highlight.and_then(|x| x.matcher().ok()));
+    }
+
+    pub fn connect_commit_selected<F>(&self, callback: F)
+    where
+        F: Fn(Oid) + 'static,
+    {
+        self.blame_window.connect_commit_selected(callback);
+    }
 
-            let diff =
-                repo.diff_tree_to_tree(Some(&parent_tree), Some(&current_tree), Some(&mut opts))?;
+    fn file_selected(self: &Rc<Self>, file_index: u32) -> Result<(), MetalGitError> {
+        self.selected_file.set(Some(file_index));
+        self.show_more_button.hide();
 
-            if let Some(buffer) = self.commit_text_view.buffer() {
-                diff_text_view_util::print_diff_to_text_view(&diff, &buffer);
+        if let Some(list_result) = self.current_list_result.borrow().as_ref() {
+            let repo = self.repository_manager.open()?;
+
+            let entry = &list_result.files[file_index as usize];
+            let new_file_path = entry.new_file_path.as_ref().unwrap();
+            let settings = self.settings();
+
+            if list_result.combined {
+                let files = commit_diff_util::combined_diff(
+                    self.repository_manager.as_ref(),
+                    list_result.current_oid,
+                    Some(new_file_path),
+                    &settings,
+                )?;
+                self.diff_view.show_combined_diff(&files);
+                return Ok(());
             }
+
+            // a renamed file is diffed with both paths to keep it paired
+            let diff = if list_result.work_tree {
+                commit_diff_util::diff_work_tree(&repo, &entry.paths(), &settings)?
+            } else {
+                commit_diff_util::diff_trees(
+                    &repo,
+                    list_result.parent_oid,
+                    list_result.current_oid,
+                    &entry.paths(),
+                    &settings,
+                )?
+            };
+
+            let truncated = self.diff_view.show_diff(
+                &repo,
+                &diff,
+                self.highlight.borrow().as_ref(),
+                self.max_lines.get(),
+            )?;
+            self.show_more_button.set_visible(truncated);
         }
 
         Ok(())
     }
 
-    fn show_all_files_diff(&self) -> Result<(), Error> {
+    fn show_all_files_diff(&self) -> Result<(), MetalGitError> {
+        self.selected_file.set(None);
+        self.show_more_button.hide();
+
         if let Some(list_result) = self.current_list_result.borrow().as_ref() {
+            let settings = self.settings();
+
+            if list_result.combined {
+                let files = commit_diff_util::combined_diff(
+                    self.repository_manager.as_ref(),
+                    list_result.current_oid,
+                    None,
+                    &settings,
+                )?;
+                self.diff_view.show_combined_diff(&files);
+                return Ok(());
+            }
+
             let repo = self.repository_manager.open()?;
 
-            let current_commit = repo.find_commit(list_result.current_oid)?;
-            let parent_commit = repo.find_commit(list_result.parent_oid)?;
+            let diff = if list_result.work_tree {
+                commit_diff_util::diff_work_tree(&repo, &[], &settings)?
+            } else {
+                commit_diff_util::diff_trees(
+                    &repo,
+                    list_result.parent_oid,
+                    list_result.current_oid,
+                    &[],
+                    &settings,
+                )?
+            };
+
+            let truncated = self.diff_view.show_diff(
+                &repo,
+                &diff,
+                self.highlight.borrow().as_ref(),
+                self.max_lines.get(),
+            )?;
+            self.show_more_button.set_visible(truncated);
+        }
 
-            let parent_tree = parent_commit.tree()?;
-            let current_tree = current_commit.tree()?;
+        Ok(())
+    }
 
-            let diff = repo.diff_tree_to_tree(Some(&parent_tree), Some(&current_tree), None)?;
+    // shows the current diff without the line limit
+    fn show_more(self: &Rc<Self>) -> Result<(), MetalGitError> {
+        self.max_lines.set(None);
 
-            if let Some(buffer) = self.commit_text_view.buffer() {
-                diff_text_view_util::print_diff_to_text_view(&diff, &buffer);
-            }
+        match self.selected_file.get() {
+            Some(index) => self.file_selected(index),
+            None => self.show_all_files_diff(),
         }
-
-        Ok(())
     }
 }
@@ -1,50 +1,498 @@
-use git2::{Error, Oid};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
 
+use git2::{Delta, Diff, DiffDelta, DiffFindOptions, DiffOptions, Error, Oid, Patch, Repository, Tree};
+
+use crate::error::{MetalGitError, ResultExt};
 use crate::repository_manager::RepositoryManager;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WhitespaceMode {
+    Show,
+    IgnoreAll,
+    IgnoreChange,
+    IgnoreEol,
+}
+
+// the options of the diff toolbar, applied to every diff of a window
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DiffSettings {
+    pub whitespace: WhitespaceMode,
+    pub context_lines: u32,
+    pub renames: bool,
+    pub copies: bool,
+    // percent of similarity for renames and copies
+    pub similarity_threshold: u16,
+    pub show_untracked_content: bool,
+}
+
+impl Default for DiffSettings {
+    fn default() -> DiffSettings {
+        DiffSettings {
+            whitespace: WhitespaceMode::Show,
+            context_lines: 3,
+            renames: true,
+            copies: false,
+            similarity_threshold: 50,
+            show_untracked_content: true,
+        }
+    }
+}
+
+impl DiffSettings {
+    // both paths of a rename are given so that find_similar can pair them
+    pub fn diff_options(&self, paths: &[&str]) -> DiffOptions {
+        let mut opts = DiffOptions::new();
+        opts.context_lines(self.context_lines)
+            .ignore_whitespace(self.whitespace == WhitespaceMode::IgnoreAll)
+            .ignore_whitespace_change(self.whitespace == WhitespaceMode::IgnoreChange)
+            .ignore_whitespace_eol(self.whitespace == WhitespaceMode::IgnoreEol)
+            // copies of files unchanged in the diff are looked up among the unmodified ones
+            .include_unmodified(self.copies);
+        for path in paths {
+            opts.pathspec(path);
+        }
+
+        opts
+    }
+
+    pub fn find_similar(&self, diff: &mut Diff) -> Result<(), Error> {
+        let mut opts = DiffFindOptions::new();
+        opts.renames(self.renames)
+            .for_untracked(self.renames)
+            .copies(self.copies)
+            .copies_from_unmodified(self.copies)
+            .remove_unmodified(self.copies)
+            .rename_threshold(self.similarity_threshold)
+            .copy_threshold(self.similarity_threshold);
+
+        diff.find_similar(Some(&mut opts))
+    }
+}
+
 pub struct ListCommitDiffFileEntry {
     pub new_file_path: Option<String>,
     pub old_file_path: Option<String>,
+    // like `git diff --name-status`: A, M, D, R, C or T
+    pub status: char,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub binary: bool,
+}
+
+impl ListCommitDiffFileEntry {
+    fn from_delta(delta: &DiffDelta) -> ListCommitDiffFileEntry {
+        ListCommitDiffFileEntry {
+            old_file_path: delta.old_file().path().and_then(|p| p.to_str()).map(|s| s.to_string()),
+            new_file_path: delta.new_file().path().and_then(|p| p.to_str()).map(|s| s.to_string()),
+            status: status_letter(delta.status()),
+            insertions: 0,
+            deletions: 0,
+            binary: delta.flags().is_binary(),
+        }
+    }
+
+    // the old and the new path of a renamed or copied file
+    pub fn paths(&self) -> Vec<&str> {
+        let mut paths = Vec::new();
+        paths.extend(self.old_file_path.as_deref());
+        if self.new_file_path != self.old_file_path {
+            paths.extend(self.new_file_path.as_deref());
+        }
+        paths
+    }
 }
 
 pub struct ListCommitDiffResult {
     pub current_oid: Oid,
-    pub parent_oid: Oid,
-    pub files: Vec<ListCommitDiffFileEntry>
+    // None for a root commit, which is compared with the empty tree
+    pub parent_oid: Option<Oid>,
+    pub combined: bool,
+    // HEAD against the work tree and the index; current_oid is zero
+    pub work_tree: bool,
+    pub files: Vec<ListCommitDiffFileEntry>,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl ListCommitDiffResult {
+    // like the last line of `git diff --stat`
+    pub fn format_summary(&self) -> String {
+        let plural = |count: usize, word: &str| format!("{} {}{}", count, word, if count == 1 { "" } else { "s" });
+
+        format!("{} changed, {}(+), {}(-)",
+                plural(self.files.len(), "file"),
+                plural(self.insertions, "insertion"),
+                plural(self.deletions, "deletion"))
+    }
+}
+
+fn status_letter(status: Delta) -> char {
+    match status {
+        Delta::Added | Delta::Untracked => 'A',
+        Delta::Deleted => 'D',
+        Delta::Renamed => 'R',
+        Delta::Copied => 'C',
+        Delta::Typechange => 'T',
+        Delta::Conflicted => 'U',
+        _ => 'M',
+    }
+}
+
+// the lengths of the insertion and deletion parts of a bar, scaled like `git diff --stat`
+pub fn stat_bar(insertions: usize, deletions: usize, max_changes: usize, width: usize) -> (usize, usize) {
+    if max_changes <= width {
+        return (insertions, deletions);
+    }
+
+    // a non-zero count keeps at least one column
+    let scale = |count: usize| if count == 0 { 0 } else { 1 + count * width.saturating_sub(1) / max_changes };
+    (scale(insertions), scale(deletions))
 }
 
-pub fn list_commit_diff_files(repository_manager: &RepositoryManager, oid: Oid) -> Result<ListCommitDiffResult, Error> {
+// the files of a diff with their line counts
+fn list_diff_files(diff: &Diff) -> Result<(Vec<ListCommitDiffFileEntry>, usize, usize), Error> {
+    let mut files = Vec::new();
+    for (i, delta) in diff.deltas().enumerate() {
+        let mut entry = ListCommitDiffFileEntry::from_delta(&delta);
+        if let Some(patch) = Patch::from_diff(diff, i)? {
+            let (_, insertions, deletions) = patch.line_stats()?;
+            entry.insertions = insertions;
+            entry.deletions = deletions;
+            entry.binary = patch.delta().flags().is_binary();
+        }
+        files.push(entry);
+    }
+
+    let stats = diff.stats()?;
+    Ok((files, stats.insertions(), stats.deletions()))
+}
+
+pub struct CombinedDiffLine {
+    // one origin character (' ', '+' or '-') per parent
+    pub origins: Vec<char>,
+    pub content: String,
+}
+
+pub struct CombinedDiffHunk {
+    pub header: String,
+    pub lines: Vec<CombinedDiffLine>,
+}
+
+pub struct CombinedDiffFile {
+    pub path: String,
+    pub is_binary: bool,
+    pub hunks: Vec<CombinedDiffHunk>,
+}
+
+pub fn list_commit_diff_files(repository_manager: &RepositoryManager, oid: Oid, settings: &DiffSettings) -> Result<ListCommitDiffResult, MetalGitError> {
+    list_commit_parent_diff_files(repository_manager, oid, 0, settings)
+}
+
+pub fn list_commit_parent_diff_files(repository_manager: &RepositoryManager, oid: Oid, parent_index: usize, settings: &DiffSettings) -> Result<ListCommitDiffResult, MetalGitError> {
     let repo = repository_manager.open()?;
-    let current_commit = repo.find_commit(oid)?;
-    // TODO: treat multiple commit
-    let parent_commit = current_commit.parent(0)?;
+    let current_commit = repo.find_commit(oid).oid_context("Failed to find the commit", oid)?;
+    let parent_oid = if current_commit.parent_count() == 0 && parent_index == 0 {
+        None
+    } else {
+        Some(current_commit.parent_id(parent_index)
+                           .oid_context(&format!("Failed to find parent {} of", parent_index + 1), oid)?)
+    };
 
-    let parent_tree = parent_commit.tree()?;
-    let current_tree = current_commit.tree()?;
-    let diff = repo.diff_tree_to_tree(Some(&parent_tree), Some(&current_tree), None)?;
-
-    let mut files: Vec<ListCommitDiffFileEntry> = Vec::new();
-    diff.foreach(
-        &mut |delta, _| {
-            let file_entry = ListCommitDiffFileEntry {
-                old_file_path: delta.old_file().path().and_then(|p| p.to_str()).map(|s| s.to_string()),
-                new_file_path: delta.new_file().path().and_then(|p| p.to_str()).map(|s| s.to_string())
-            };
-            files.push(file_entry);
-            true
-        },
-        None,
-        None,
-        None,
-    )?;
+    list_tree_diff_files(repository_manager, parent_oid, oid, settings)
+}
+
+pub fn list_combined_diff_files(repository_manager: &RepositoryManager, oid: Oid, settings: &DiffSettings) -> Result<ListCommitDiffResult, MetalGitError> {
+    let repo = repository_manager.open()?;
+    let current_commit = repo.find_commit(oid).oid_context("Failed to find the commit", oid)?;
+
+    // lines added or deleted against any parent
+    let files = combined_diff(repository_manager, oid, None, settings)?
+        .into_iter()
+        .map(|x| {
+            let lines = x.hunks.iter().flat_map(|hunk| hunk.lines.iter()).collect::<Vec<_>>();
+            ListCommitDiffFileEntry {
+                old_file_path: Some(x.path.clone()),
+                new_file_path: Some(x.path),
+                status: 'M',
+                insertions: lines.iter().filter(|line| line.origins.contains(&'+')).count(),
+                deletions: lines.iter().filter(|line| line.origins.contains(&'-')).count(),
+                binary: x.is_binary,
+            }
+        })
+        .collect::<Vec<_>>();
 
     Ok(ListCommitDiffResult {
         current_oid: oid,
-        parent_oid: parent_commit.id(),
+        parent_oid: Some(current_commit.parent_id(0).oid_context("Failed to find the parent of", oid)?),
+        combined: true,
+        work_tree: false,
+        insertions: files.iter().map(|x| x.insertions).sum(),
+        deletions: files.iter().map(|x| x.deletions).sum(),
         files
     })
 }
 
+pub fn list_tree_diff_files(repository_manager: &RepositoryManager, old_oid: Option<Oid>, new_oid: Oid, settings: &DiffSettings) -> Result<ListCommitDiffResult, MetalGitError> {
+    let repo = repository_manager.open()?;
+    let diff = diff_trees(&repo, old_oid, new_oid, &[], settings).oid_context("Failed to diff the commit", new_oid)?;
+    let (files, insertions, deletions) = list_diff_files(&diff).oid_context("Failed to count the changes of", new_oid)?;
+
+    Ok(ListCommitDiffResult {
+        current_oid: new_oid,
+        parent_oid: old_oid,
+        combined: false,
+        work_tree: false,
+        files,
+        insertions,
+        deletions,
+    })
+}
+
+// the commit of old_oid against the one of new_oid; None is the empty tree
+pub fn diff_trees<'repo>(repo: &'repo Repository, old_oid: Option<Oid>, new_oid: Oid, paths: &[&str], settings: &DiffSettings) -> Result<Diff<'repo>, Error> {
+    let old_tree = match old_oid {
+        Some(old_oid) => Some(repo.find_commit(old_oid)?.tree()?),
+        None => None,
+    };
+    let new_tree = repo.find_commit(new_oid)?.tree()?;
+
+    let mut opts = settings.diff_options(paths);
+    let mut diff = repo.diff_tree_to_tree(old_tree.as_ref(), Some(&new_tree), Some(&mut opts))?;
+    settings.find_similar(&mut diff)?;
+
+    Ok(diff)
+}
+
+// staged and unstaged changes including untracked files
+pub fn diff_work_tree<'repo>(repo: &'repo Repository, paths: &[&str], settings: &DiffSettings) -> Result<Diff<'repo>, Error> {
+    let head_tree = repo.head()?.peel_to_tree()?;
+
+    let mut opts = settings.diff_options(paths);
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(settings.show_untracked_content);
+
+    let mut diff = repo.diff_tree_to_workdir_with_index(Some(&head_tree), Some(&mut opts))?;
+    settings.find_similar(&mut diff)?;
+
+    Ok(diff)
+}
+
+pub fn list_work_tree_diff_files(repository_manager: &RepositoryManager, settings: &DiffSettings) -> Result<ListCommitDiffResult, MetalGitError> {
+    let repo = repository_manager.open()?;
+    let head_oid = repo.head().context("Failed to read HEAD")?.target();
+    let diff = diff_work_tree(&repo, &[], settings).context("Failed to diff the work tree")?;
+    let (files, insertions, deletions) = list_diff_files(&diff).context("Failed to count the changes of the work tree")?;
+
+    Ok(ListCommitDiffResult {
+        current_oid: Oid::zero(),
+        parent_oid: head_oid,
+        combined: false,
+        work_tree: true,
+        files,
+        insertions,
+        deletions,
+    })
+}
+
+// like `git show --cc`: only files and hunks which differ from every parent
+pub fn combined_diff(repository_manager: &RepositoryManager, oid: Oid, pathspec: Option<&str>, settings: &DiffSettings) -> Result<Vec<CombinedDiffFile>, MetalGitError> {
+    let repo = repository_manager.open()?;
+
+    collect_combined_diff(&repo, oid, pathspec, settings).oid_context("Failed to make the combined diff of", oid)
+}
+
+fn collect_combined_diff(repo: &Repository, oid: Oid, pathspec: Option<&str>, settings: &DiffSettings) -> Result<Vec<CombinedDiffFile>, Error> {
+    let current_commit = repo.find_commit(oid)?;
+    let current_tree = current_commit.tree()?;
+    let parent_trees = current_commit.parents().map(|x| x.tree()).collect::<Result<Vec<_>, _>>()?;
+
+    let mut paths: Option<Vec<String>> = None;
+    for parent_tree in &parent_trees {
+        let mut opts = settings.diff_options(&pathspec.into_iter().collect::<Vec<_>>());
+        let diff = repo.diff_tree_to_tree(Some(parent_tree), Some(&current_tree), Some(&mut opts))?;
+        // the options include unmodified files when copies are detected
+        let changed = diff.deltas()
+                          .filter(|delta| delta.status() != Delta::Unmodified)
+                          .filter_map(|delta| delta.new_file().path().and_then(|p| p.to_str()).map(|s| s.to_string()))
+                          .collect::<Vec<_>>();
+
+        paths = Some(match paths {
+            None => changed,
+            Some(paths) => {
+                let changed = changed.into_iter().collect::<HashSet<_>>();
+                paths.into_iter().filter(|x| changed.contains(x)).collect()
+            }
+        });
+    }
+
+    let mut files = Vec::new();
+    for path in paths.unwrap_or_default() {
+        if let Some(file) = combined_diff_file(repo, &path, &current_tree, &parent_trees, settings)? {
+            files.push(file);
+        }
+    }
+
+    Ok(files)
+}
+
+struct ParentLineChanges {
+    // new line numbers added against the parent
+    added: HashSet<u32>,
+    // lines deleted from the parent, keyed by the new line number they precede
+    deleted: BTreeMap<u32, Vec<String>>,
+}
+
+impl ParentLineChanges {
+    fn is_changed_at(&self, new_line: u32) -> bool {
+        self.added.contains(&new_line) || self.deleted.contains_key(&new_line)
+    }
+
+    // the parent's line number of the first parent line at or after new_line
+    fn old_line_at(&self, new_line: u32) -> u32 {
+        let added_before = self.added.iter().filter(|&&x| x < new_line).count() as u32;
+        let deleted_before = self.deleted.range(..new_line).map(|(_, lines)| lines.len()).sum::<usize>() as u32;
+        new_line - added_before + deleted_before
+    }
+}
+
+fn blob_content(repo: &Repository, tree: &Tree, path: &str) -> Result<Vec<u8>, Error> {
+    match tree.get_path(Path::new(path)) {
+        Ok(entry) => Ok(repo.find_blob(entry.id())?.content().to_vec()),
+        Err(ref e) if e.code() == git2::ErrorCode::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn collect_parent_line_changes(patch: &Patch) -> Result<ParentLineChanges, Error> {
+    let mut changes = ParentLineChanges {
+        added: HashSet::new(),
+        deleted: BTreeMap::new(),
+    };
+
+    for hunk_index in 0..patch.num_hunks() {
+        let (hunk, line_count) = patch.hunk(hunk_index)?;
+        // a hunk only with deletions starts after new_start
+        let mut next_new_line = if hunk.new_lines() == 0 { hunk.new_start() + 1 } else { hunk.new_start() };
+
+        for line_index in 0..line_count {
+            let line = patch.line_in_hunk(hunk_index, line_index)?;
+            match line.origin() {
+                ' ' | '+' => {
+                    let new_line = line.new_lineno().unwrap_or(next_new_line);
+                    if line.origin() == '+' {
+                        changes.added.insert(new_line);
+                    }
+                    next_new_line = new_line + 1;
+                }
+                '-' => {
+                    let content = String::from_utf8_lossy(line.content()).trim_end_matches('\n').to_string();
+                    changes.deleted.entry(next_new_line).or_default().push(content);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(changes)
+}
+
+fn combined_diff_file(repo: &Repository, path: &str, current_tree: &Tree, parent_trees: &[Tree], settings: &DiffSettings) -> Result<Option<CombinedDiffFile>, Error> {
+    let new_content = blob_content(repo, current_tree, path)?;
+    let new_lines = String::from_utf8_lossy(&new_content).lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let new_line_count = new_lines.len() as u32;
+
+    let mut parent_changes = Vec::new();
+    for parent_tree in parent_trees {
+        let old_content = blob_content(repo, parent_tree, path)?;
+        let mut opts = settings.diff_options(&[]);
+        let patch = Patch::from_buffers(&old_content, Some(Path::new(path)), &new_content, Some(Path::new(path)), Some(&mut opts))?;
+        if patch.delta().flags().is_binary() {
+            return Ok(Some(CombinedDiffFile {
+                path: path.to_string(),
+                is_binary: true,
+                hunks: Vec::new(),
+            }));
+        }
+
+        parent_changes.push(collect_parent_line_changes(&patch)?);
+    }
+
+    // new_line_count + 1 is the position of lines deleted at the end of the file
+    let interesting_lines = (1..=new_line_count + 1)
+        .filter(|&x| parent_changes.iter().all(|changes| changes.is_changed_at(x)))
+        .collect::<Vec<_>>();
+
+    if interesting_lines.is_empty() {
+        return Ok(None);
+    }
+
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for line in interesting_lines {
+        let start = line.saturating_sub(settings.context_lines).max(1);
+        let end = (line + settings.context_lines).min(new_line_count + 1);
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    let hunks = ranges.into_iter()
+                      .map(|(start, end)| combined_diff_hunk(start, end, &new_lines, &parent_changes))
+                      .collect();
+
+    Ok(Some(CombinedDiffFile {
+        path: path.to_string(),
+        is_binary: false,
+        hunks,
+    }))
+}
+
+fn combined_diff_hunk(start: u32, end: u32, new_lines: &[String], parent_changes: &[ParentLineChanges]) -> CombinedDiffHunk {
+    let new_line_count = new_lines.len() as u32;
+    let mut lines = Vec::new();
+    let mut header = "@@@".to_string();
+
+    for (i, changes) in parent_changes.iter().enumerate() {
+        let mut count = 0;
+        for new_line in start..=end {
+            count += changes.deleted.get(&new_line).map(|x| x.len()).unwrap_or(0);
+            if new_line <= new_line_count && !changes.added.contains(&new_line) {
+                count += 1;
+            }
+
+            for content in changes.deleted.get(&new_line).into_iter().flatten() {
+                let mut origins = vec![' '; parent_changes.len()];
+                origins[i] = '-';
+                lines.push((new_line, CombinedDiffLine { origins, content: content.clone() }));
+            }
+        }
+
+        let old_start = changes.old_line_at(start);
+        header.push_str(&format!(" -{},{}", if count == 0 { old_start - 1 } else { old_start }, count));
+    }
+
+    let mut lines = lines.into_iter().map(|x| (x.0, 0, x.1)).collect::<Vec<_>>();
+    for new_line in start..=end.min(new_line_count) {
+        let origins = parent_changes.iter()
+                                    .map(|changes| if changes.added.contains(&new_line) { '+' } else { ' ' })
+                                    .collect();
+        let content = new_lines[(new_line - 1) as usize].clone();
+        lines.push((new_line, 1, CombinedDiffLine { origins, content }));
+    }
+    // deleted lines are placed before the new line they precede
+    lines.sort_by_key(|x| (x.0, x.1));
+
+    let new_count = (start..=end).filter(|&x| x <= new_line_count).count() as u32;
+    header.push_str(&format!(" +{},{} @@@", if new_count == 0 { start - 1 } else { start }, new_count));
+
+    CombinedDiffHunk {
+        header,
+        lines: lines.into_iter().map(|x| x.2).collect(),
+    }
+}
+
 impl ListCommitDiffFileEntry {
     pub fn format_file_move(&self) -> String {
         let old_file_path = self.old_file_path.as_deref();
@@ -1,23 +1,28 @@
 use gtk::prelude::*;
 
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
-use std::cell::RefCell;
 use std::str;
 use std::path::Path;
 use std::fs;
-use std::fs::File;
-use std::io::Read;
-use std::collections::HashSet;
-use std::error;
+use std::collections::{HashMap, HashSet};
 
-use git2::{Error, StatusOptions};
+use git2::{Error, Repository, StatusOptions};
 use git2::build::CheckoutBuilder;
 
 use crate::repository_manager::RepositoryManager;
+use crate::event_bus::{EventBus, RepositoryEvent, SubscriptionId};
 use crate::gtk_utils;
+use crate::error::{MetalGitError, ResultExt};
+use crate::error_bar::ErrorBar;
 use crate::repository_ext::RepositoryExt;
-use crate::diff_text_view_util;
-use crate::diff_text_view_util::create_diff_text_buffer;
+use crate::commit_diff_util::DiffSettings;
+use crate::diff_content;
+use crate::diff_content::FileContent;
+use crate::diff_options_bar::DiffOptionsBar;
+use crate::diff_view::DiffView;
+use crate::stash_util;
+use crate::syntax_highlight;
 
 pub struct CommitWindow {
     window: gtk::Window,
@@ -31,35 +36,107 @@ pub struct CommitWindow {
     amend_checkbutton: gtk::CheckButton,
     commit_button: gtk::Button,
 
+    stash_message_entry: gtk::Entry,
+    include_untracked_checkbutton: gtk::CheckButton,
+    keep_index_checkbutton: gtk::CheckButton,
+    stash_button: gtk::Button,
+
     work_tree_files_list_store: gtk::ListStore,
     work_tree_files_tree_view: gtk::TreeView,
 
     staged_files_list_store: gtk::ListStore,
     staged_files_tree_view: gtk::TreeView,
 
-    diff_text_view: gtk::TextView,
+    diff_options_bar: Rc<DiffOptionsBar>,
+    diff_view: Rc<DiffView>,
+    show_more_button: gtk::Button,
+    max_lines: Cell<Option<usize>>,
+    shown_file: RefCell<Option<(TreeType, String)>>,
+    // the old paths of renamed files, which are listed by their new paths
+    renamed_from: RefCell<HashMap<(TreeType, String), String>>,
     message_text_view: gtk::TextView,
 
     repository_manager: Rc<RepositoryManager>,
-
-    commited: RefCell<Box<dyn Fn() -> ()>>,
+    event_bus: Rc<EventBus>,
+    subscription: Cell<Option<SubscriptionId>>,
+    error_bar: Rc<ErrorBar>,
 }
 
 const FILENAME_COLUMN: u32 = 0;
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum TreeType {
     WorkDir,
     Index,
 }
 
 impl CommitWindow {
-    pub fn new(repository_manager: Rc<RepositoryManager>) -> Rc<CommitWindow> {
+    pub fn new(repository_manager: Rc<RepositoryManager>, event_bus: Rc<EventBus>) -> Rc<CommitWindow> {
         let builder = gtk::Builder::from_resource("/org/sunnyone/MetalGit/commit_window.ui");
 
+        let message_box: gtk::Box = builder.object("box4").unwrap();
+        let stash_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        stash_box.set_margin_start(5);
+        stash_box.set_margin_end(5);
+        stash_box.set_margin_bottom(5);
+        let stash_message_entry = gtk::Entry::new();
+        stash_message_entry.set_placeholder_text(Some("stash message"));
+        stash_box.pack_start(&stash_message_entry, true, true, 0);
+        let include_untracked_checkbutton = gtk::CheckButton::with_label("include untracked");
+        stash_box.pack_start(&include_untracked_checkbutton, false, true, 0);
+        let keep_index_checkbutton = gtk::CheckButton::with_label("keep index");
+        stash_box.pack_start(&keep_index_checkbutton, false, true, 0);
+        let stash_button = gtk::Button::with_label("Stash");
+        stash_box.pack_end(&stash_button, false, true, 0);
+        message_box.pack_start(&stash_box, false, true, 0);
+
+        // the error bar is put above the paned
+        let window: gtk::Window = builder.object("commit_window").unwrap();
+        let paned: gtk::Paned = builder.object("paned1").unwrap();
+        let error_bar = ErrorBar::new();
+        let main_box = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        window.remove(&paned);
+        main_box.pack_start(&error_bar.widget(), false, true, 0);
+        main_box.pack_start(&paned, true, true, 0);
+        window.add(&main_box);
+
+        // the options bar, the diff view and the "Show more" button replace the text view of the ui
+        let paned2: gtk::Paned = builder.object("paned2").unwrap();
+        let scrolledwindow3: gtk::ScrolledWindow = builder.object("scrolledwindow3").unwrap();
+        paned2.remove(&scrolledwindow3);
+        let diff_box = gtk::Box::new(gtk::Orientation::Vertical, 0);
+
+        let diff_options_bar = DiffOptionsBar::new();
+        let diff_options_widget = diff_options_bar.widget();
+        diff_options_widget.set_margin_start(5);
+        diff_options_widget.set_margin_top(5);
+        diff_box.pack_start(&diff_options_widget, false, true, 0);
+
+        let diff_view = DiffView::new();
+        let diff_widget = diff_view.widget();
+        diff_widget.set_margin_start(5);
+        diff_widget.set_margin_end(4);
+        diff_widget.set_margin_top(5);
+        diff_widget.set_margin_bottom(5);
+        diff_widget.set_size_request(-1, 400);
+        diff_box.pack_start(&diff_widget, true, true, 0);
+
+        // shown only when a diff is too long
+        let show_more_button = gtk::Button::with_label("Show more");
+        show_more_button.set_no_show_all(true);
+        show_more_button.set_margin_start(5);
+        show_more_button.set_margin_end(4);
+        diff_box.pack_start(&show_more_button, false, true, 0);
+
+        paned2.pack1(&diff_box, false, true);
+
         let commit_window = CommitWindow {
             repository_manager: repository_manager,
+            event_bus,
+            subscription: Cell::new(None),
 
-            window: builder.object("commit_window").unwrap(),
+            window,
+            error_bar,
 
             refresh_button: builder.object("refresh_button").unwrap(),
             revert_button: builder.object("revert_button").unwrap(),
@@ -76,17 +153,22 @@ impl CommitWindow {
             staged_files_list_store: builder.object("staged_files_list_store").unwrap(),
             staged_files_tree_view: builder.object("staged_files_tree_view").unwrap(),
 
-            diff_text_view: builder.object("diff_text_view").unwrap(),
+            diff_options_bar,
+            diff_view,
+            show_more_button,
+            max_lines: Cell::new(Some(diff_content::MAX_DIFF_LINES)),
+            shown_file: RefCell::new(None),
+            renamed_from: RefCell::new(HashMap::new()),
             message_text_view: builder.object("message_text_view").unwrap(),
 
-            commited: RefCell::new(Box::new(|| {})),
+            stash_message_entry,
+            include_untracked_checkbutton,
+            keep_index_checkbutton,
+            stash_button,
         };
 
         let commit_window = Rc::new(commit_window);
-        commit_window.diff_text_view.set_monospace(true);
-
-        let diff_text_buffer = create_diff_text_buffer();
-        commit_window.diff_text_view.set_buffer(Some(&diff_text_buffer));
+        gtk_utils::register_error_bar(&commit_window.window, &commit_window.error_bar);
 
         let w = Rc::downgrade(&commit_window);
         commit_window.window.connect_delete_event(move |_, _| {
@@ -94,6 +176,21 @@ impl CommitWindow {
             Inhibit(true)
         });
 
+        let w = Rc::downgrade(&commit_window);
+        let subscription = commit_window.event_bus.subscribe(move |event| {
+            match event {
+                RepositoryEvent::IndexChanged | RepositoryEvent::WorkTreeChanged | RepositoryEvent::HeadMoved => {
+                    if let Some(w) = w.upgrade() {
+                        if w.is_visible() {
+                            w.refresh();
+                        }
+                    }
+                }
+                RepositoryEvent::RefsChanged => {}
+            }
+        });
+        commit_window.subscription.set(Some(subscription));
+
         let w = Rc::downgrade(&commit_window);
         commit_window.refresh_button.connect_clicked(move |_| {
             w.upgrade().unwrap().refresh();
@@ -104,8 +201,9 @@ impl CommitWindow {
             let file = Self::get_selection_selected_file_single(selection);
 
             if let Some(file) = file {
-                dialog_when_error!("Failed to diff: {:?}",
-                                   w.upgrade().unwrap().work_tree_files_selected(&file));
+                let w = w.upgrade().unwrap();
+                w.max_lines.set(Some(diff_content::MAX_DIFF_LINES));
+                dialog_when_error!("Failed to diff: {}", w.work_tree_files_selected(&file));
             }
         });
 
@@ -114,11 +212,25 @@ impl CommitWindow {
             let file = Self::get_selection_selected_file_single(selection);
 
             if let Some(file) = file {
-                dialog_when_error!("Failed to diff: {:?}",
-                                   w.upgrade().unwrap().index_files_selected(&file));
+                let w = w.upgrade().unwrap();
+                w.max_lines.set(Some(diff_content::MAX_DIFF_LINES));
+                dialog_when_error!("Failed to diff: {}", w.index_files_selected(&file));
             }
         });
 
+        let w = Rc::downgrade(&commit_window);
+        commit_window.show_more_button.connect_clicked(move |_| {
+            dialog_when_error!("Failed to diff: {}", w.upgrade().unwrap().show_more());
+        });
+
+        let w = Rc::downgrade(&commit_window);
+        commit_window.diff_options_bar.connect_changed(move || {
+            // renames change the lists too
+            let w = w.upgrade().unwrap();
+            w.refresh();
+            dialog_when_error!("Failed to diff: {}", w.show_shown_file());
+        });
+
         let w = Rc::downgrade(&commit_window);
         commit_window.work_tree_files_tree_view
                      .connect_row_activated(move |_tree_view, tree_path, _column| {
@@ -127,8 +239,8 @@ impl CommitWindow {
                                                                   tree_path);
 
                          if let Some(file) = file {
-                             dialog_when_error!("Failed to stage: {:?}",
-                                                w_.stage_files(vec![file]));
+                             dialog_when_error!("Failed to stage: {}",
+                                                w_.stage_files(w_.paths(TreeType::WorkDir, &file)));
                          }
                      });
 
@@ -140,26 +252,26 @@ impl CommitWindow {
                                                                   tree_path);
 
                          if let Some(file) = file {
-                             dialog_when_error!("Failed to unstage: {:?}",
-                                                w_.unstage_files(vec![file]));
+                             dialog_when_error!("Failed to unstage: {}",
+                                                w_.unstage_files(w_.paths(TreeType::Index, &file)));
                          }
                      });
 
         let w = Rc::downgrade(&commit_window);
         commit_window.revert_button.connect_clicked(move |_| {
-            dialog_when_error!("Failed to revert: {:?}",
+            dialog_when_error!("Failed to revert: {}",
                                w.upgrade().unwrap().revert_button_clicked());
         });
 
         let w = Rc::downgrade(&commit_window);
         commit_window.stage_button.connect_clicked(move |_| {
-            dialog_when_error!("Failed to stage: {:?}",
+            dialog_when_error!("Failed to stage: {}",
                                w.upgrade().unwrap().stage_button_clicked());
         });
 
         let w = Rc::downgrade(&commit_window);
         commit_window.unstage_button.connect_clicked(move |_| {
-            dialog_when_error!("Failed to unstage: {:?}",
+            dialog_when_error!("Failed to unstage: {}",
                                w.upgrade().unwrap().unstage_button_clicked());
         });
 
@@ -170,7 +282,7 @@ impl CommitWindow {
                 // TODO: works?
                 // TODO: "KP_Enter" is nessessary?
                 if key.keyval().name().map(|n| n == "Return").unwrap_or(false) {
-                    dialog_when_error!("Failed to commit: {:?}",
+                    dialog_when_error!("Failed to commit: {}",
                                        w.upgrade().unwrap().commit_or_amend());
                     return Inhibit(true);
                 }
@@ -180,16 +292,21 @@ impl CommitWindow {
 
         let w = Rc::downgrade(&commit_window);
         commit_window.amend_checkbutton.connect_clicked(move |_| {
-            dialog_when_error!("Failed to toggle amend: {:?}",
+            dialog_when_error!("Failed to toggle amend: {}",
                                w.upgrade().unwrap().amend_checkbutton_clicked());
         });
 
         let w = Rc::downgrade(&commit_window);
         commit_window.commit_button.connect_clicked(move |_| {
-            dialog_when_error!("Failed to commit: {:?}",
+            dialog_when_error!("Failed to commit: {}",
                                w.upgrade().unwrap().commit_or_amend());
         });
 
+        let w = Rc::downgrade(&commit_window);
+        commit_window.stash_button.connect_clicked(move |_| {
+            dialog_when_error!("{}", w.upgrade().unwrap().stash_button_clicked());
+        });
+
         commit_window
     }
 
@@ -245,17 +362,17 @@ impl CommitWindow {
                   })
     }
 
-    fn revert_button_clicked(&self) -> Result<(), Error> {
+    fn revert_button_clicked(&self) -> Result<(), MetalGitError> {
         let selection = self.work_tree_files_tree_view.selection();
         let files = Self::get_selection_selected_files(&selection);
         if files.len() == 0 {
             return Ok(());
         }
 
-        self.revert(files)
+        self.revert(self.paths_of_files(TreeType::WorkDir, files))
     }
 
-    fn revert(&self, files: Vec<String>) -> Result<(), Error> {
+    fn revert(&self, files: Vec<String>) -> Result<(), MetalGitError> {
         if files.len() == 0 {
             return Ok(());
         }
@@ -282,25 +399,27 @@ impl CommitWindow {
             repo.checkout_head(Some(&mut builder))?;
         }
 
+        // removes the rest even when one fails, and reports the first failure
+        let mut result = Ok(());
         for file_path in &remove_file_paths {
             let path = repo.get_full_path(file_path).unwrap();
-            
-            // TODO: convert error
-            if let Err(error) = fs::remove_file(&path) {
-                println!("Failed to remove {}: {}", path.to_string_lossy(), error);
+
+            let removed = fs::remove_file(&path).path_context("Failed to remove", &path);
+            if result.is_ok() {
+                result = removed;
             }
         }
 
-        self.refresh();
+        self.event_bus.publish(RepositoryEvent::WorkTreeChanged);
 
-        Ok(())
+        result
     }
 
     fn stage_button_clicked(&self) -> Result<(), Error> {
         let selection = self.work_tree_files_tree_view.selection();
         let files = Self::get_selection_selected_files(&selection);
 
-        self.stage_files(files)?;
+        self.stage_files(self.paths_of_files(TreeType::WorkDir, files))?;
 
         Ok(())
     }
@@ -326,7 +445,7 @@ impl CommitWindow {
         index.write()?;
 
         // TODO: partial update
-        self.refresh();
+        self.event_bus.publish(RepositoryEvent::IndexChanged);
 
         Ok(())
     }
@@ -335,7 +454,7 @@ impl CommitWindow {
         let selection = self.staged_files_tree_view.selection();
         let files = Self::get_selection_selected_files(&selection);
 
-        self.unstage_files(files)?;
+        self.unstage_files(self.paths_of_files(TreeType::Index, files))?;
 
         Ok(())
     }
@@ -353,7 +472,7 @@ impl CommitWindow {
         repo.reset_default(Some(&head_object), files)?;
 
         // TODO: partial update
-        self.refresh();
+        self.event_bus.publish(RepositoryEvent::IndexChanged);
 
         Ok(())
     }
@@ -424,10 +543,9 @@ impl CommitWindow {
         }
 
         // self.hide();
-        self.refresh();
         self.set_commit_message("");
 
-        self.commited.borrow()();
+        self.event_bus.publish(RepositoryEvent::HeadMoved);
 
         Ok(())
     }
@@ -438,6 +556,20 @@ impl CommitWindow {
         self.commit(to_amend)
     }
 
+    fn stash_button_clicked(&self) -> Result<(), MetalGitError> {
+        stash_util::save_stash(&self.repository_manager,
+                               &self.stash_message_entry.text(),
+                               self.include_untracked_checkbutton.is_active(),
+                               self.keep_index_checkbutton.is_active())?;
+
+        self.stash_message_entry.set_text("");
+
+        // the index is reset too
+        self.event_bus.publish_all(&[RepositoryEvent::RefsChanged, RepositoryEvent::WorkTreeChanged]);
+
+        Ok(())
+    }
+
     pub fn show(&self) {
         self.window.show_all();
         self.refresh();
@@ -447,6 +579,22 @@ impl CommitWindow {
         self.window.hide();
     }
 
+    // releases the window and its diff view; it is not shown again
+    pub fn close(&self) {
+        if let Some(subscription) = self.subscription.take() {
+            self.event_bus.unsubscribe(subscription);
+        }
+        gtk_utils::unregister_error_bar(&self.window);
+        // nothing shows or refreshes the window after this
+        unsafe {
+            self.window.destroy();
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.window.is_visible()
+    }
+
     pub fn refresh(&self) {
         let work_tree_selection = self.work_tree_files_tree_view.selection();
         let staged_selection = self.staged_files_tree_view.selection();
@@ -461,8 +609,13 @@ impl CommitWindow {
         self.work_tree_files_list_store.clear();
         self.staged_files_list_store.clear();
 
-        match collect_changed_status_items(&self.repository_manager) {
-            Err(_) => crate::gtk_utils::message_box_error("Error!"),
+        self.renamed_from.borrow_mut().clear();
+
+        match collect_changed_status_items(&self.repository_manager, &self.settings()) {
+            Err(err) => {
+                let err = MetalGitError::from(err);
+                gtk_utils::report_error(&format!("Failed to load the status: {}", err), &err.details());
+            }
             Ok(list) => {
                 for item in list {
                     let list_store = match item.tree_type {
@@ -471,6 +624,9 @@ impl CommitWindow {
                     };
 
                     let _ = list_store.insert_with_values(None, &[(FILENAME_COLUMN, &item.path)]);
+                    if let Some(old_path) = item.old_path {
+                        self.renamed_from.borrow_mut().insert((item.tree_type, item.path), old_path);
+                    }
                 }
             }
         }
@@ -484,120 +640,154 @@ impl CommitWindow {
     }
 
     pub fn work_tree_files_selected(&self, filename: &str) -> Result<(), Error> {
+        self.shown_file.replace(Some((TreeType::WorkDir, filename.to_string())));
         let repo = self.repository_manager.open()?;
 
+        // the new path of a renamed file is untracked
+        let paths = self.paths(TreeType::WorkDir, filename);
         let path = Path::new(filename);
         let status = repo.status_file(path)?;
-        if status == git2::Status::WT_NEW {
-            self.show_new_file(path);
+        if status == git2::Status::WT_NEW && paths.len() == 1 {
+            self.show_new_file(&repo, path);
             Ok(())
         } else {
-            let mut diff_opts = git2::DiffOptions::new();
-            diff_opts.pathspec(filename);
-            let diff = repo.diff_index_to_workdir(None, Some(&mut diff_opts))?;
-
-            self.show_diff(&diff);
-
-            Ok(())
+            let settings = self.settings();
+            let paths = paths.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+            let mut diff_opts = settings.diff_options(&paths);
+            diff_opts.include_untracked(paths.len() > 1);
+            let mut diff = repo.diff_index_to_workdir(None, Some(&mut diff_opts))?;
+            settings.find_similar(&mut diff)?;
+
+            self.show_diff(&repo, &diff)
         }
     }
 
     pub fn index_files_selected(&self, filename: &str) -> Result<(), Error> {
+        self.shown_file.replace(Some((TreeType::Index, filename.to_string())));
         let repo = self.repository_manager.open()?;
 
-        let mut diff_opts = git2::DiffOptions::new();
-        diff_opts.pathspec(filename);
+        let settings = self.settings();
+        let paths = self.paths(TreeType::Index, filename);
+        let mut diff_opts = settings.diff_options(&paths.iter().map(|x| x.as_str()).collect::<Vec<_>>());
 
         let head_ref = repo.head()?;
         let head_tree_object = head_ref.peel(git2::ObjectType::Tree)?;
         let head_tree = head_tree_object.as_tree().unwrap();
 
-        let diff = repo.diff_tree_to_index(Some(&head_tree), None, Some(&mut diff_opts))?;
-
-        self.show_diff(&diff);
+        let mut diff = repo.diff_tree_to_index(Some(&head_tree), None, Some(&mut diff_opts))?;
+        settings.find_similar(&mut diff)?;
 
-        Ok(())
+        self.show_diff(&repo, &diff)
     }
 
-    fn show_diff(&self, diff: &git2::Diff) {
-        let buffer = self.diff_text_view.buffer().unwrap();
+    fn settings(&self) -> DiffSettings {
+        self.diff_options_bar.settings()
+    }
 
-        diff_text_view_util::print_diff_to_text_view(diff, &buffer);
+    // a listed file and the old path when it is renamed, so that both are diffed or staged together
+    fn paths(&self, tree_type: TreeType, filename: &str) -> Vec<String> {
+        let mut paths = vec![filename.to_string()];
+        paths.extend(self.renamed_from.borrow().get(&(tree_type, filename.to_string())).cloned());
+        paths
     }
 
-    pub fn set_diff_all_add_text(&self, text: &str) {
-        let buffer = self.diff_text_view.buffer().unwrap();
-        buffer.set_text("");
+    fn paths_of_files(&self, tree_type: TreeType, files: Vec<String>) -> Vec<String> {
+        files.iter().flat_map(|x| self.paths(tree_type, x)).collect()
+    }
 
-        let mut iter = buffer.start_iter();
-        gtk_utils::text_buffer_insert_with_tag_by_name(&buffer, &mut iter, text, "add");
+    // shows the selected file without the size limit
+    fn show_more(&self) -> Result<(), Error> {
+        self.max_lines.set(None);
+        self.show_shown_file()
     }
 
-    fn show_new_file(&self, path_in_repository: &Path) {
-        match self.read_contents(&path_in_repository) {
-            Ok(s) => {
-                self.set_diff_all_add_text(&s);
-            }
-            Err(err) => {
-                let msg = format!("This file is not browsable: {}", err.to_string());
-                self.set_diff_all_add_text(&msg);
-            }
+    fn show_shown_file(&self) -> Result<(), Error> {
+        let shown_file = self.shown_file.borrow().clone();
+        match shown_file {
+            Some((TreeType::WorkDir, filename)) => self.work_tree_files_selected(&filename),
+            Some((TreeType::Index, filename)) => self.index_files_selected(&filename),
+            None => Ok(()),
         }
     }
 
-    fn read_contents(&self, path_in_repository: &Path) -> Result<String, Box<dyn error::Error>> {
-        let repo = self.repository_manager.open()?;
+    fn show_diff(&self, repo: &Repository, diff: &git2::Diff) -> Result<(), Error> {
+        let truncated = self.diff_view.show_diff(repo, diff, None, self.max_lines.get())?;
+        self.show_more_button.set_visible(truncated);
 
-        let path = repo.get_full_path(path_in_repository).unwrap();
-        
-        let mut s = String::new();
-        let mut f = File::open(path)?;
-        f.read_to_string(&mut s)?;
-        Ok(s)
+        Ok(())
     }
 
-    pub fn connect_commited<F>(&self, callback: F)
-        where F: Fn() -> () + 'static
-    {
-        *self.commited.borrow_mut() = Box::new(callback);
+    fn show_new_file(&self, repo: &Repository, path_in_repository: &Path) {
+        if !self.settings().show_untracked_content {
+            let text = format!("Untracked file {}\n", path_in_repository.to_string_lossy());
+            self.diff_view.show_text(&text, "other");
+            self.show_more_button.hide();
+            return;
+        }
+
+        // the limit in lines is applied to bytes for new files
+        let max_bytes = self.max_lines.get().map(|_| diff_content::MAX_FILE_BYTES);
+
+        match diff_content::read_work_tree_file(repo, path_in_repository, max_bytes) {
+            Ok(FileContent::Binary { size }) => {
+                let path = path_in_repository.to_string_lossy();
+                self.diff_view.show_text(&diff_content::binary_summary(&path, size), "other");
+                self.show_more_button.hide();
+            }
+            Ok(FileContent::Text { text, hidden_bytes }) => {
+                let language = syntax_highlight::language_for_file(repo, path_in_repository);
+                self.diff_view.show_file_text(&text, language, hidden_bytes);
+                self.show_more_button.set_visible(hidden_bytes > 0);
+            }
+            Err(err) => {
+                let msg = format!("This file is not browsable: {}", err);
+                self.diff_view.show_text(&msg, "add");
+                self.show_more_button.hide();
+            }
+        }
     }
 }
 
 struct StatusItem {
     path: String,
+    // Some for a renamed file
+    old_path: Option<String>,
     tree_type: TreeType,
 }
 
-fn collect_changed_status_items(repository_manager: &RepositoryManager)
+impl StatusItem {
+    fn from_delta(delta: &git2::DiffDelta, tree_type: TreeType) -> Result<StatusItem, Error> {
+        let path_of = |file: git2::DiffFile| file.path().and_then(|x| x.to_str()).map(|x| x.to_string());
+        let path = path_of(delta.new_file()).ok_or_else(|| Error::from_str("Invalid file path exist"))?;
+        let old_path = path_of(delta.old_file()).filter(|x| *x != path);
+
+        Ok(StatusItem { path, old_path, tree_type })
+    }
+}
+
+fn collect_changed_status_items(repository_manager: &RepositoryManager, settings: &DiffSettings)
                                 -> Result<Vec<StatusItem>, Error> {
     let repo = repository_manager.open()?;
     if repo.is_bare() {
         return Err(Error::from_str("cannot report status on bare repository"));
     }
 
+    // a renamed file is listed once by its new path
     let mut opts = StatusOptions::new();
-    opts.include_untracked(true).recurse_untracked_dirs(true);
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .renames_head_to_index(settings.renames)
+        .renames_index_to_workdir(settings.renames);
 
     let statuses = repo.statuses(Some(&mut opts))?;
     let mut status_items: Vec<StatusItem> = Vec::new();
 
     for status in statuses.iter() {
-        if status.path().is_none() {
-            return Err(Error::from_str("Invalid file path exist"));
-        }
-
-        let path = status.path().unwrap();
-        if status.index_to_workdir().is_some() {
-            status_items.push(StatusItem {
-                tree_type: TreeType::WorkDir,
-                path: path.to_string(),
-            });
+        if let Some(delta) = status.index_to_workdir() {
+            status_items.push(StatusItem::from_delta(&delta, TreeType::WorkDir)?);
         }
-        if status.head_to_index().is_some() {
-            status_items.push(StatusItem {
-                tree_type: TreeType::Index,
-                path: path.to_string(),
-            });
+        if let Some(delta) = status.head_to_index() {
+            status_items.push(StatusItem::from_delta(&delta, TreeType::Index)?);
         }
     }
 
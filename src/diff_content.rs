@@ -0,0 +1,499 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use git2::{AttrCheckFlags, AttrValue, Diff, DiffFile, Error, Repository};
+
+use crate::error::{MetalGitError, ResultExt};
+use crate::repository_ext::RepositoryExt;
+use crate::syntax_highlight::{self, Highlighter, Language, SyntaxSpan};
+
+// more lines are hidden behind "Show more"
+pub const MAX_DIFF_LINES: usize = 5000;
+pub const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+const MAX_WORD_DIFF_TOKENS: usize = 500;
+
+// git looks for a NUL byte in the same amount of bytes
+const BINARY_CHECK_BYTES: usize = 8000;
+
+pub struct DiffTextLine {
+    pub origin: char,
+    // with the origin character for added, deleted and context lines
+    pub text: String,
+    pub old_lineno: Option<u32>,
+    pub new_lineno: Option<u32>,
+}
+
+pub struct DiffText {
+    pub lines: Vec<DiffTextLine>,
+    pub hidden_lines: usize,
+    pub files: Vec<DiffTextFile>,
+}
+
+pub struct DiffTextFile {
+    // the index of the file header line
+    pub first_line: usize,
+    pub path: String,
+    pub language: Option<&'static Language>,
+}
+
+// a row of the side-by-side view
+#[derive(PartialEq, Debug)]
+pub enum SplitRow {
+    // file and hunk headers span both columns
+    Header(usize),
+    // line indices; None leaves the column empty
+    Lines { old: Option<usize>, new: Option<usize>, changed: bool },
+}
+
+#[derive(PartialEq, Debug)]
+pub enum InlineRow {
+    // the index of a line shown as it is
+    Line(usize),
+    // the indices of the paired lines
+    Words { old: usize, new: usize, segments: Vec<WordSegment> },
+}
+
+#[derive(PartialEq, Debug)]
+pub enum WordSegment {
+    Same(String),
+    Deleted(String),
+    Added(String),
+}
+
+// changed words as char ranges
+#[derive(PartialEq, Debug)]
+pub struct WordDiff {
+    pub old_ranges: Vec<(usize, usize)>,
+    pub new_ranges: Vec<(usize, usize)>,
+}
+
+pub enum FileContent {
+    Binary { size: u64 },
+    Text { text: String, hidden_bytes: u64 },
+}
+
+// UTF-8 or UTF-16 with a BOM, UTF-8, and Latin-1 for anything else
+pub fn decode_text(bytes: &[u8]) -> String {
+    if let Some(rest) = bytes.strip_prefix(b"\xef\xbb\xbf") {
+        return String::from_utf8_lossy(rest).into_owned();
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xff\xfe") {
+        return decode_utf16(rest, u16::from_le_bytes);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xfe\xff") {
+        return decode_utf16(rest, u16::from_be_bytes);
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&x| x as char).collect(),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], to_u16: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks(2).map(|x| to_u16([x[0], *x.get(1).unwrap_or(&0)]));
+    char::decode_utf16(units)
+        .map(|x| x.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+pub fn is_binary_content(bytes: &[u8]) -> bool {
+    let len = bytes.len().min(BINARY_CHECK_BYTES);
+    let head = &bytes[..len];
+
+    // UTF-16 text has NUL bytes too
+    if head.starts_with(b"\xff\xfe") || head.starts_with(b"\xfe\xff") {
+        return false;
+    }
+    head.contains(&0)
+}
+
+pub fn binary_summary(path: &str, size: u64) -> String {
+    format!("Binary file {}, {} bytes", path, size)
+}
+
+// "binary" or "-diff" in .gitattributes
+pub fn is_binary_by_attributes(repo: &Repository, path_in_repository: &Path) -> Result<bool, Error> {
+    let flags = AttrCheckFlags::FILE_THEN_INDEX;
+
+    let binary = repo.get_attr(path_in_repository, "binary", flags)?;
+    if AttrValue::from_string(binary) == AttrValue::True {
+        return Ok(true);
+    }
+
+    let diff = repo.get_attr(path_in_repository, "diff", flags)?;
+    Ok(AttrValue::from_string(diff) == AttrValue::False)
+}
+
+// decodes the lines of the diff, stopping after max_lines
+pub fn collect_diff_text(repo: &Repository, diff: &Diff, max_lines: Option<usize>) -> Result<DiffText, Error> {
+    let mut lines = Vec::new();
+    let mut hidden_lines = 0;
+    let mut files = Vec::new();
+
+    diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
+        if max_lines.is_some_and(|x| lines.len() >= x) {
+            hidden_lines += 1;
+            return true;
+        }
+
+        let origin = line.origin();
+        if origin == 'F' {
+            let file = if delta.new_file().exists() { delta.new_file() } else { delta.old_file() };
+            if let Some(path) = file.path() {
+                files.push(DiffTextFile {
+                    first_line: lines.len(),
+                    path: path.to_string_lossy().into_owned(),
+                    language: syntax_highlight::language_for_file(repo, path),
+                });
+            }
+        }
+
+        let text = match origin {
+            '+' | '-' | ' ' => format!("{}{}", origin, decode_text(line.content())),
+            // "Binary files a/x and b/x differ"
+            'B' => {
+                let file = if delta.new_file().exists() { delta.new_file() } else { delta.old_file() };
+                let path = file.path().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+                format!("{}\n", binary_summary(&path, diff_file_size(repo, &file)))
+            }
+            _ => decode_text(line.content()),
+        };
+
+        lines.push(DiffTextLine {
+            origin,
+            text,
+            old_lineno: line.old_lineno(),
+            new_lineno: line.new_lineno(),
+        });
+        true
+    })?;
+
+    Ok(DiffText { lines, hidden_lines, files })
+}
+
+enum Visit<'a> {
+    Line(usize),
+    // indices of a run of deleted lines and the added lines after it
+    Block(&'a [usize], &'a [usize]),
+}
+
+// "\ No newline at end of file" belongs to the line above and is skipped
+fn visit_blocks<F>(lines: &[DiffTextLine], mut visit: F)
+    where F: FnMut(Visit)
+{
+    let mut deleted = Vec::new();
+    let mut added = Vec::new();
+
+    let flush = |deleted: &mut Vec<usize>, added: &mut Vec<usize>, visit: &mut F| {
+        if !deleted.is_empty() || !added.is_empty() {
+            visit(Visit::Block(deleted, added));
+            deleted.clear();
+            added.clear();
+        }
+    };
+
+    for (i, line) in lines.iter().enumerate() {
+        match line.origin {
+            '-' => {
+                // a deletion after additions starts a new block
+                if !added.is_empty() {
+                    flush(&mut deleted, &mut added, &mut visit);
+                }
+                deleted.push(i);
+            }
+            '+' => added.push(i),
+            '=' | '>' | '<' => {}
+            _ => {
+                flush(&mut deleted, &mut added, &mut visit);
+                visit(Visit::Line(i));
+            }
+        }
+    }
+    flush(&mut deleted, &mut added, &mut visit);
+}
+
+// the text after the origin character, ending with a newline
+pub fn line_content(line: &DiffTextLine) -> String {
+    let mut content = line.text[1..].to_string();
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content
+}
+
+// pairs the deleted and added lines of each block, leaving empty rows on the shorter side
+pub fn split_rows(lines: &[DiffTextLine]) -> Vec<SplitRow> {
+    let mut rows = Vec::new();
+
+    visit_blocks(lines, |visit| match visit {
+        Visit::Line(i) if lines[i].origin == ' ' => {
+            rows.push(SplitRow::Lines { old: Some(i), new: Some(i), changed: false });
+        }
+        Visit::Line(i) => rows.push(SplitRow::Header(i)),
+        Visit::Block(deleted, added) => {
+            for k in 0..deleted.len().max(added.len()) {
+                rows.push(SplitRow::Lines {
+                    old: deleted.get(k).copied(),
+                    new: added.get(k).copied(),
+                    changed: true,
+                });
+            }
+        }
+    });
+
+    rows
+}
+
+// the changed words of each paired line, as char ranges after the origin character
+pub fn changed_word_ranges(lines: &[DiffTextLine]) -> Vec<Vec<(usize, usize)>> {
+    let mut ranges = vec![Vec::new(); lines.len()];
+
+    visit_blocks(lines, |visit| {
+        if let Visit::Block(deleted, added) = visit {
+            for (&old, &new) in deleted.iter().zip(added) {
+                let diff = word_diff(&line_content(&lines[old]), &line_content(&lines[new]));
+                ranges[old] = diff.old_ranges;
+                ranges[new] = diff.new_ranges;
+            }
+        }
+    });
+
+    ranges
+}
+
+// a paired deleted and added line becomes one row of words
+pub fn inline_rows(lines: &[DiffTextLine]) -> Vec<InlineRow> {
+    let mut rows = Vec::new();
+
+    visit_blocks(lines, |visit| match visit {
+        Visit::Line(i) => rows.push(InlineRow::Line(i)),
+        Visit::Block(deleted, added) => {
+            for (&old, &new) in deleted.iter().zip(added) {
+                let old_content = line_content(&lines[old]);
+                let new_content = line_content(&lines[new]);
+                let segments = word_segments(old_content.trim_end_matches('\n'),
+                                             new_content.trim_end_matches('\n'));
+                rows.push(InlineRow::Words { old, new, segments });
+            }
+            let paired = deleted.len().min(added.len());
+            rows.extend(deleted[paired..].iter().map(|&i| InlineRow::Line(i)));
+            rows.extend(added[paired..].iter().map(|&i| InlineRow::Line(i)));
+        }
+    });
+
+    rows
+}
+
+// syntax spans of each line after the origin character; the old and new sides are highlighted separately
+pub fn syntax_spans(diff_text: &DiffText) -> Vec<Vec<SyntaxSpan>> {
+    let mut spans = Vec::with_capacity(diff_text.lines.len());
+    let mut files = diff_text.files.iter().peekable();
+    let mut language = None;
+    let mut old = None;
+    let mut new = None;
+
+    for (i, line) in diff_text.lines.iter().enumerate() {
+        if line.origin == 'F' {
+            language = files.next_if(|x| x.first_line == i).and_then(|x| x.language);
+        }
+
+        // a comment left open in one hunk does not run into the next
+        if line.origin == 'F' || line.origin == 'H' {
+            old = language.map(Highlighter::new);
+            new = language.map(Highlighter::new);
+        }
+
+        let content = line.text.get(1..).unwrap_or("");
+        let line_spans = match (line.origin, old.as_mut(), new.as_mut()) {
+            ('-', Some(old), _) => old.highlight_line(content),
+            ('+', _, Some(new)) => new.highlight_line(content),
+            (' ', Some(old), Some(new)) => {
+                old.highlight_line(content);
+                new.highlight_line(content)
+            }
+            _ => Vec::new(),
+        };
+        spans.push(line_spans);
+    }
+
+    spans
+}
+
+// the lines hidden by collapsing the files or hunks of the given header lines; headers stay
+pub fn collapsed_lines(lines: &[DiffTextLine], collapsed: &HashSet<usize>) -> Vec<bool> {
+    let mut file_header = None;
+    let mut hunk_header = None;
+
+    lines.iter()
+         .enumerate()
+         .map(|(i, line)| {
+             match line.origin {
+                 'F' => {
+                     file_header = Some(i);
+                     hunk_header = None;
+                 }
+                 'H' => hunk_header = Some(i),
+                 _ => {}
+             }
+
+             let hidden_by = |header: Option<usize>| header.is_some_and(|x| x != i && collapsed.contains(&x));
+             hidden_by(file_header) || hidden_by(hunk_header)
+         })
+         .collect()
+}
+
+// runs of word characters, runs of spaces, and single other characters
+fn tokenize(text: &str) -> Vec<&str> {
+    let class = |c: char| if c.is_alphanumeric() || c == '_' { 0 } else if c.is_whitespace() { 1 } else { 2 };
+
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut prev_class = None;
+    for (i, c) in text.char_indices() {
+        let current = class(c);
+        if i > start && (prev_class != Some(current) || current == 2) {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        prev_class = Some(current);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+
+    tokens
+}
+
+pub fn word_segments(old: &str, new: &str) -> Vec<WordSegment> {
+    let old_tokens = tokenize(old);
+    let new_tokens = tokenize(new);
+
+    let mut segments = Vec::new();
+    let mut push = |segment: WordSegment| {
+        match (segments.last_mut(), segment) {
+            (Some(WordSegment::Same(x)), WordSegment::Same(y))
+            | (Some(WordSegment::Deleted(x)), WordSegment::Deleted(y))
+            | (Some(WordSegment::Added(x)), WordSegment::Added(y)) => x.push_str(&y),
+            (_, segment) => segments.push(segment),
+        }
+    };
+
+    // too long to compare in a reasonable time
+    if old_tokens.len() > MAX_WORD_DIFF_TOKENS || new_tokens.len() > MAX_WORD_DIFF_TOKENS {
+        push(WordSegment::Deleted(old.to_string()));
+        push(WordSegment::Added(new.to_string()));
+        return segments;
+    }
+
+    // lengths of the longest common subsequences of the suffixes
+    let n = old_tokens.len();
+    let m = new_tokens.len();
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_tokens[i] == new_tokens[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_tokens[i] == new_tokens[j] {
+            push(WordSegment::Same(old_tokens[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push(WordSegment::Deleted(old_tokens[i].to_string()));
+            i += 1;
+        } else {
+            push(WordSegment::Added(new_tokens[j].to_string()));
+            j += 1;
+        }
+    }
+
+    segments
+}
+
+pub fn word_diff(old: &str, new: &str) -> WordDiff {
+    let mut diff = WordDiff { old_ranges: Vec::new(), new_ranges: Vec::new() };
+    let mut old_offset = 0;
+    let mut new_offset = 0;
+
+    for segment in word_segments(old, new) {
+        match segment {
+            WordSegment::Same(text) => {
+                old_offset += text.chars().count();
+                new_offset += text.chars().count();
+            }
+            WordSegment::Deleted(text) => {
+                let len = text.chars().count();
+                diff.old_ranges.push((old_offset, old_offset + len));
+                old_offset += len;
+            }
+            WordSegment::Added(text) => {
+                let len = text.chars().count();
+                diff.new_ranges.push((new_offset, new_offset + len));
+                new_offset += len;
+            }
+        }
+    }
+
+    diff
+}
+
+// the size is not loaded when a file is binary by its attributes
+fn diff_file_size(repo: &Repository, file: &DiffFile) -> u64 {
+    if file.size() > 0 || file.id().is_zero() {
+        return file.size();
+    }
+
+    repo.find_blob(file.id())
+        .map(|x| x.size() as u64)
+        .or_else(|_| {
+            let path = file.path().and_then(|x| repo.get_full_path(x)).ok_or(())?;
+            std::fs::metadata(path).map(|x| x.len()).map_err(|_| ())
+        })
+        .unwrap_or(0)
+}
+
+// reads up to max_bytes of a file in the work tree
+pub fn read_work_tree_file(repo: &Repository, path_in_repository: &Path, max_bytes: Option<u64>)
+                           -> Result<FileContent, MetalGitError> {
+    let path = repo.get_full_path(path_in_repository)
+                   .ok_or_else(|| MetalGitError::new("The repository has no work tree"))?;
+
+    let file = File::open(&path).path_context("Failed to read", &path)?;
+    let size = file.metadata().path_context("Failed to read", &path)?.len();
+
+    if is_binary_by_attributes(repo, path_in_repository).path_context("Failed to read the attributes of", &path)? {
+        return Ok(FileContent::Binary { size });
+    }
+
+    let mut bytes = Vec::new();
+    file.take(max_bytes.unwrap_or(u64::MAX))
+        .read_to_end(&mut bytes)
+        .path_context("Failed to read", &path)?;
+
+    if is_binary_content(&bytes) {
+        return Ok(FileContent::Binary { size });
+    }
+
+    // a multi-byte character may be cut off at the limit
+    if let Err(err) = std::str::from_utf8(&bytes) {
+        if err.error_len().is_none() && (bytes.len() as u64) < size {
+            bytes.truncate(err.valid_up_to());
+        }
+    }
+
+    Ok(FileContent::Text {
+        text: decode_text(&bytes),
+        hidden_bytes: size.saturating_sub(bytes.len() as u64),
+    })
+}
@@ -0,0 +1,138 @@
+use glib::Cast;
+use gtk::prelude::{ComboBoxExtManual, SpinButtonSignals};
+use gtk::traits::{BoxExt, ComboBoxExt, ComboBoxTextExt, SpinButtonExt, ToggleButtonExt, WidgetExt};
+use gtk::Orientation;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::commit_diff_util::{DiffSettings, WhitespaceMode};
+
+// whitespace, context lines, rename and copy detection for the diffs of a window
+pub struct DiffOptionsBar {
+    container: gtk::Box,
+
+    whitespace_combo_box: gtk::ComboBoxText,
+    context_spin_button: gtk::SpinButton,
+    renames_check_button: gtk::CheckButton,
+    copies_check_button: gtk::CheckButton,
+    similarity_spin_button: gtk::SpinButton,
+    untracked_check_button: gtk::CheckButton,
+
+    changed: RefCell<Box<dyn Fn()>>,
+}
+
+// the order of items in whitespace_combo_box
+const WHITESPACE_MODES: [(WhitespaceMode, &str); 4] = [
+    (WhitespaceMode::Show, "Show whitespace"),
+    (WhitespaceMode::IgnoreAll, "Ignore all whitespace"),
+    (WhitespaceMode::IgnoreChange, "Ignore whitespace changes"),
+    (WhitespaceMode::IgnoreEol, "Ignore whitespace at EOL"),
+];
+
+impl DiffOptionsBar {
+    pub fn new() -> Rc<DiffOptionsBar> {
+        let defaults = DiffSettings::default();
+        let container = gtk::Box::new(Orientation::Horizontal, 6);
+
+        let whitespace_combo_box = gtk::ComboBoxText::new();
+        for (_, label) in WHITESPACE_MODES.iter() {
+            whitespace_combo_box.append_text(label);
+        }
+        whitespace_combo_box.set_active(Some(0));
+        container.pack_start(&whitespace_combo_box, false, true, 0);
+
+        container.pack_start(&gtk::Label::new(Some("Context:")), false, true, 0);
+        let context_spin_button = gtk::SpinButton::with_range(0.0, 100.0, 1.0);
+        context_spin_button.set_value(defaults.context_lines as f64);
+        container.pack_start(&context_spin_button, false, true, 0);
+
+        let renames_check_button = gtk::CheckButton::with_label("Renames");
+        renames_check_button.set_active(defaults.renames);
+        container.pack_start(&renames_check_button, false, true, 0);
+
+        let copies_check_button = gtk::CheckButton::with_label("Copies");
+        copies_check_button.set_active(defaults.copies);
+        copies_check_button.set_tooltip_text(Some("Also looks for the sources among unchanged files, which is slow"));
+        container.pack_start(&copies_check_button, false, true, 0);
+
+        container.pack_start(&gtk::Label::new(Some("Similarity %:")), false, true, 0);
+        let similarity_spin_button = gtk::SpinButton::with_range(1.0, 100.0, 5.0);
+        similarity_spin_button.set_value(defaults.similarity_threshold as f64);
+        container.pack_start(&similarity_spin_button, false, true, 0);
+
+        let untracked_check_button = gtk::CheckButton::with_label("Untracked content");
+        untracked_check_button.set_active(defaults.show_untracked_content);
+        container.pack_start(&untracked_check_button, false, true, 0);
+
+        let diff_options_bar = Rc::new(DiffOptionsBar {
+            container,
+            whitespace_combo_box,
+            context_spin_button,
+            renames_check_button,
+            copies_check_button,
+            similarity_spin_button,
+            untracked_check_button,
+            changed: RefCell::new(Box::new(|| {})),
+        });
+
+        let w = Rc::downgrade(&diff_options_bar);
+        diff_options_bar.whitespace_combo_box.connect_changed(move |_| {
+            w.upgrade().unwrap().changed.borrow()();
+        });
+
+        for spin_button in [&diff_options_bar.context_spin_button, &diff_options_bar.similarity_spin_button] {
+            let w = Rc::downgrade(&diff_options_bar);
+            spin_button.connect_value_changed(move |_| {
+                w.upgrade().unwrap().changed.borrow()();
+            });
+        }
+
+        for check_button in [&diff_options_bar.renames_check_button,
+                             &diff_options_bar.copies_check_button,
+                             &diff_options_bar.untracked_check_button] {
+            let w = Rc::downgrade(&diff_options_bar);
+            check_button.connect_toggled(move |_| {
+                let w = w.upgrade().unwrap();
+                w.update_sensitive();
+                w.changed.borrow()();
+            });
+        }
+
+        diff_options_bar.update_sensitive();
+
+        diff_options_bar
+    }
+
+    pub fn widget(&self) -> gtk::Widget {
+        self.container.clone().upcast::<gtk::Widget>()
+    }
+
+    pub fn settings(&self) -> DiffSettings {
+        let whitespace = self.whitespace_combo_box
+                             .active()
+                             .and_then(|x| WHITESPACE_MODES.get(x as usize))
+                             .map(|x| x.0)
+                             .unwrap_or(WhitespaceMode::Show);
+
+        DiffSettings {
+            whitespace,
+            context_lines: self.context_spin_button.value_as_int() as u32,
+            renames: self.renames_check_button.is_active(),
+            copies: self.copies_check_button.is_active(),
+            similarity_threshold: self.similarity_spin_button.value_as_int() as u16,
+            show_untracked_content: self.untracked_check_button.is_active(),
+        }
+    }
+
+    pub fn connect_changed<F>(&self, callback: F)
+        where F: Fn() + 'static
+    {
+        *self.changed.borrow_mut() = Box::new(callback);
+    }
+
+    fn update_sensitive(&self) {
+        let detecting = self.renames_check_button.is_active() || self.copies_check_button.is_active();
+        self.similarity_spin_button.set_sensitive(detecting);
+    }
+}
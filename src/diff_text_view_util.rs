@@ -1,8 +1,12 @@
-use git2::Diff;
 use gtk::TextBuffer;
 use gtk::traits::{TextBufferExt, TextTagTableExt};
+use crate::commit_diff_util::CombinedDiffFile;
+use crate::diff_content;
+use crate::diff_content::{DiffText, DiffTextLine, InlineRow, SplitRow, WordSegment};
 use crate::gtk_utils;
-use std::str;
+use crate::pickaxe::PickaxeMatcher;
+use crate::syntax_highlight::{self, Highlighter, Language, SyntaxSpan, TokenKind};
+use std::collections::HashSet;
 
 pub fn create_diff_text_buffer() -> gtk::TextBuffer {
     let tag_table = gtk::TextTagTable::new();
@@ -39,31 +43,463 @@ pub fn create_diff_text_buffer() -> gtk::TextBuffer {
         .build();
     tag_table.add(&other_tag);
 
+    // foreground only, so that the add and delete backgrounds stay
+    let syntax_tags = [
+        ("syntax_keyword", "#0033b3", 700, gtk::pango::Style::Normal),
+        ("syntax_string", "#067d17", 400, gtk::pango::Style::Normal),
+        ("syntax_comment", "#7a7a7a", 400, gtk::pango::Style::Italic),
+        ("syntax_number", "#1750eb", 400, gtk::pango::Style::Normal),
+    ];
+    for (name, foreground, weight, style) in syntax_tags {
+        let tag = gtk::TextTag::builder()
+            .name(name)
+            .foreground(foreground)
+            .weight(weight)
+            .style(style)
+            .build();
+        tag_table.add(&tag);
+    }
+
+    let gutter_tag = gtk::TextTag::builder()
+        .name("gutter")
+        .foreground("#8c8c8c")
+        .font("Normal")
+        .build();
+    tag_table.add(&gutter_tag);
+
+    // the changed words of a changed line
+    let add_word_tag = gtk::TextTag::builder()
+        .name("add_word")
+        .background("#8fd19a")
+        .build();
+    tag_table.add(&add_word_tag);
+
+    let delete_word_tag = gtk::TextTag::builder()
+        .name("delete_word")
+        .background("#e59898")
+        .build();
+    tag_table.add(&delete_word_tag);
+
+    // added last to take priority over the tags above
+    let match_tag = gtk::TextTag::builder()
+        .name("match")
+        .background("#fff176")
+        .weight(700)
+        .build();
+    tag_table.add(&match_tag);
+
     gtk::TextBuffer::builder()
         .tag_table(&tag_table)
         .build()
 }
 
-pub fn print_diff_to_text_view(diff: &Diff, buffer: &TextBuffer) {
+pub struct PrintOptions<'a> {
+    pub highlight: Option<&'a PickaxeMatcher>,
+    pub syntax: bool,
+    // indices of the file and hunk headers whose lines are hidden
+    pub collapsed: &'a HashSet<usize>,
+}
+
+// where the file and hunk headers are printed, in buffer lines
+#[derive(Default)]
+pub struct DiffLayout {
+    pub headers: Vec<HeaderLine>,
+}
+
+pub struct HeaderLine {
+    // the index in DiffText::lines
+    pub index: usize,
+    pub first_line: i32,
+    pub last_line: i32,
+    pub file: bool,
+}
+
+impl DiffLayout {
+    pub fn header_at(&self, line: i32) -> Option<&HeaderLine> {
+        self.headers.iter().find(|x| x.first_line <= line && line <= x.last_line)
+    }
+}
+
+// line numbers, or a marker for headers, by the lines of a text buffer
+struct Gutter {
+    width: usize,
+    entries: Vec<(i32, String)>,
+}
+
+impl Gutter {
+    fn new(columns: usize) -> Gutter {
+        Gutter { width: columns * 6, entries: Vec::new() }
+    }
+
+    fn numbers(&mut self, line: i32, numbers: &[Option<u32>]) {
+        let text = numbers.iter()
+                          .map(|x| match x {
+                              Some(number) => format!("{:>5} ", number),
+                              None => "      ".to_string(),
+                          })
+                          .collect::<String>();
+        self.entries.push((line, text));
+    }
+
+    // folded headers are marked with a right-pointing triangle
+    fn header(&mut self, line: i32, collapsed: bool) {
+        let marker = if collapsed { "\u{25b8}" } else { "\u{25be}" };
+        self.entries.push((line, format!("{:<width$}", marker, width = self.width)));
+    }
+
+    fn print(&self, buffer: &TextBuffer, line_count: i32) {
+        let mut lines = vec![String::new(); line_count.max(0) as usize];
+        for (line, text) in &self.entries {
+            if let Some(x) = lines.get_mut(*line as usize) {
+                *x = text.clone();
+            }
+        }
+
+        buffer.set_text("");
+        let mut iter = buffer.start_iter();
+        gtk_utils::text_buffer_insert_with_tag_by_name(buffer, &mut iter, &lines.join("\n"), "gutter");
+    }
+}
+
+fn print_header(buffer: &TextBuffer,
+                iter: &mut gtk::TextIter,
+                line: &DiffTextLine,
+                index: usize,
+                layout: &mut DiffLayout) {
+    let first_line = iter.line();
+    gtk_utils::text_buffer_insert_with_tag_by_name(buffer, iter, &line.text, "other");
+
+    if line.origin == 'F' || line.origin == 'H' {
+        layout.headers.push(HeaderLine {
+            index,
+            first_line,
+            last_line: (iter.line() - 1).max(first_line),
+            file: line.origin == 'F',
+        });
+    }
+}
+
+fn is_header(line: &DiffTextLine) -> bool {
+    line.origin == 'F' || line.origin == 'H'
+}
+
+pub fn print_diff_text(diff_text: &DiffText, buffer: &TextBuffer, gutter_buffer: &TextBuffer, options: &PrintOptions)
+                       -> DiffLayout {
     buffer.set_text("");
 
+    let word_ranges = diff_content::changed_word_ranges(&diff_text.lines);
+    let syntax_spans = syntax_spans(diff_text, options.syntax);
+    let hidden = diff_content::collapsed_lines(&diff_text.lines, options.collapsed);
+    let mut layout = DiffLayout::default();
+    let mut gutter = Gutter::new(2);
+
     let mut iter = buffer.start_iter();
-    let _ = diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
-        let o = line.origin();
-        let tag_name = match o {
-            ' ' => "normal",
-            '+' => "add",
-            '-' => "delete",
-            _ => "other",
-        };
+    for (i, line) in diff_text.lines.iter().enumerate() {
+        if hidden[i] {
+            continue;
+        }
+
+        let o = line.origin;
+        if is_header(line) {
+            gutter.header(iter.line(), options.collapsed.contains(&i));
+            print_header(buffer, &mut iter, line, i, &mut layout);
+            continue;
+        }
 
-        let mut str = String::new();
         if o == '+' || o == '-' || o == ' ' {
-            str.push(line.origin());
+            gutter.numbers(iter.line(), &[line.old_lineno, line.new_lineno]);
+        }
+        let line_offset = iter.offset();
+        gtk_utils::text_buffer_insert_with_tag_by_name(&buffer, &mut iter, &line.text, line_tag_name(o));
+
+        // skip the origin character
+        apply_syntax_tags(buffer, &syntax_spans[i], line_offset + 1);
+        apply_word_tags(buffer, &word_ranges[i], line_offset + 1, o == '+');
+
+        if let Some(matcher) = options.highlight.filter(|_| o == '+' || o == '-') {
+            highlight_matches(buffer, matcher, &line.text[1..], line_offset + 1);
+        }
+    }
+
+    print_hidden_lines(diff_text, buffer, &mut iter);
+    gutter.print(gutter_buffer, buffer.line_count());
+
+    layout
+}
+
+// prints the old and new sides into two buffers with the same number of lines
+pub fn print_split_diff_text(diff_text: &DiffText,
+                             (old_buffer, old_gutter_buffer): (&TextBuffer, &TextBuffer),
+                             (new_buffer, new_gutter_buffer): (&TextBuffer, &TextBuffer),
+                             options: &PrintOptions)
+                             -> DiffLayout {
+    old_buffer.set_text("");
+    new_buffer.set_text("");
+
+    let syntax_spans = syntax_spans(diff_text, options.syntax);
+    let hidden = diff_content::collapsed_lines(&diff_text.lines, options.collapsed);
+    let mut layout = DiffLayout::default();
+    let mut old_gutter = Gutter::new(1);
+    let mut new_gutter = Gutter::new(1);
+
+    let mut old_iter = old_buffer.start_iter();
+    let mut new_iter = new_buffer.start_iter();
+    for row in diff_content::split_rows(&diff_text.lines) {
+        match row {
+            SplitRow::Header(index) if !hidden[index] => {
+                let line = &diff_text.lines[index];
+                if is_header(line) {
+                    let collapsed = options.collapsed.contains(&index);
+                    old_gutter.header(old_iter.line(), collapsed);
+                    new_gutter.header(new_iter.line(), collapsed);
+                }
+                print_header(old_buffer, &mut old_iter, line, index, &mut layout);
+                print_header(new_buffer, &mut new_iter, line, index, &mut DiffLayout::default());
+            }
+            SplitRow::Header(_) => {}
+            SplitRow::Lines { old, new, changed } => {
+                if old.or(new).is_some_and(|i| hidden[i]) {
+                    continue;
+                }
+
+                let (old_tag, new_tag) = if changed { ("delete", "add") } else { ("normal", "normal") };
+                let old_text = old.map(|i| diff_content::line_content(&diff_text.lines[i]));
+                let new_text = new.map(|i| diff_content::line_content(&diff_text.lines[i]));
+                let (old_offset, new_offset) = (old_iter.offset(), new_iter.offset());
+                let highlight = options.highlight.filter(|_| changed);
+
+                old_gutter.numbers(old_iter.line(), &[old.and_then(|i| diff_text.lines[i].old_lineno)]);
+                new_gutter.numbers(new_iter.line(), &[new.and_then(|i| diff_text.lines[i].new_lineno)]);
+                print_split_line(old_buffer, &mut old_iter, old_text.as_deref(), old_tag, highlight);
+                print_split_line(new_buffer, &mut new_iter, new_text.as_deref(), new_tag, highlight);
+
+                if let Some(i) = old {
+                    apply_syntax_tags(old_buffer, &syntax_spans[i], old_offset);
+                }
+                if let Some(i) = new {
+                    apply_syntax_tags(new_buffer, &syntax_spans[i], new_offset);
+                }
+
+                if let (true, Some(old_text), Some(new_text)) = (changed, &old_text, &new_text) {
+                    let word_diff = diff_content::word_diff(old_text, new_text);
+                    apply_word_tags(old_buffer, &word_diff.old_ranges, old_offset, false);
+                    apply_word_tags(new_buffer, &word_diff.new_ranges, new_offset, true);
+                }
+            }
         }
-        str.push_str(str::from_utf8(line.content()).unwrap());
+    }
 
-        gtk_utils::text_buffer_insert_with_tag_by_name(&buffer, &mut iter, &str, tag_name);
-        true
-    });
+    print_hidden_lines(diff_text, old_buffer, &mut old_iter);
+    print_hidden_lines(diff_text, new_buffer, &mut new_iter);
+    old_gutter.print(old_gutter_buffer, old_buffer.line_count());
+    new_gutter.print(new_gutter_buffer, new_buffer.line_count());
+
+    layout
+}
+
+fn print_split_line(buffer: &TextBuffer,
+                    iter: &mut gtk::TextIter,
+                    text: Option<&str>,
+                    tag_name: &str,
+                    highlight: Option<&PickaxeMatcher>) {
+    match text {
+        Some(text) => {
+            let line_offset = iter.offset();
+            gtk_utils::text_buffer_insert_with_tag_by_name(buffer, iter, text, tag_name);
+
+            if let Some(matcher) = highlight {
+                highlight_matches(buffer, matcher, text, line_offset);
+            }
+        }
+        // an empty row keeps both sides aligned
+        None => gtk_utils::text_buffer_insert_with_tag_by_name(buffer, iter, "\n", "other"),
+    }
+}
+
+// like git diff --word-diff: a changed line shows its deleted and added words together
+pub fn print_word_diff_text(diff_text: &DiffText,
+                            buffer: &TextBuffer,
+                            gutter_buffer: &TextBuffer,
+                            options: &PrintOptions)
+                            -> DiffLayout {
+    buffer.set_text("");
+
+    let syntax_spans = syntax_spans(diff_text, options.syntax);
+    let hidden = diff_content::collapsed_lines(&diff_text.lines, options.collapsed);
+    let mut layout = DiffLayout::default();
+    let mut gutter = Gutter::new(2);
+
+    let mut iter = buffer.start_iter();
+    for row in diff_content::inline_rows(&diff_text.lines) {
+        match row {
+            InlineRow::Line(index) if !hidden[index] => {
+                let line = &diff_text.lines[index];
+                let o = line.origin;
+                if is_header(line) {
+                    gutter.header(iter.line(), options.collapsed.contains(&index));
+                    print_header(buffer, &mut iter, line, index, &mut layout);
+                    continue;
+                }
+
+                if o == '+' || o == '-' || o == ' ' {
+                    gutter.numbers(iter.line(), &[line.old_lineno, line.new_lineno]);
+                }
+                let tag_name = line_tag_name(o);
+                let line_offset = iter.offset();
+                gtk_utils::text_buffer_insert_with_tag_by_name(buffer, &mut iter, &line.text, tag_name);
+                if !line.text.ends_with('\n') {
+                    gtk_utils::text_buffer_insert_with_tag_by_name(buffer, &mut iter, "\n", tag_name);
+                }
+                apply_syntax_tags(buffer, &syntax_spans[index], line_offset + 1);
+
+                if let Some(matcher) = options.highlight.filter(|_| o == '+' || o == '-') {
+                    highlight_matches(buffer, matcher, &line.text[1..], line_offset + 1);
+                }
+            }
+            InlineRow::Words { old, new, segments } if !hidden[old] => {
+                gutter.numbers(iter.line(), &[diff_text.lines[old].old_lineno, diff_text.lines[new].new_lineno]);
+                gtk_utils::text_buffer_insert_with_tag_by_name(buffer, &mut iter, " ", "normal");
+                for segment in &segments {
+                    let (text, tag_name) = match segment {
+                        WordSegment::Same(text) => (text, "normal"),
+                        WordSegment::Deleted(text) => (text, "delete_word"),
+                        WordSegment::Added(text) => (text, "add_word"),
+                    };
+                    gtk_utils::text_buffer_insert_with_tag_by_name(buffer, &mut iter, text, tag_name);
+                }
+                gtk_utils::text_buffer_insert_with_tag_by_name(buffer, &mut iter, "\n", "normal");
+            }
+            _ => {}
+        }
+    }
+
+    print_hidden_lines(diff_text, buffer, &mut iter);
+    gutter.print(gutter_buffer, buffer.line_count());
+
+    layout
+}
+
+// a whole file, like an untracked one, in the add color
+pub fn print_file_text(text: &str,
+                       language: Option<&'static Language>,
+                       buffer: &TextBuffer,
+                       gutter_buffer: &TextBuffer,
+                       syntax: bool) {
+    buffer.set_text("");
+
+    let mut iter = buffer.start_iter();
+    gtk_utils::text_buffer_insert_with_tag_by_name(buffer, &mut iter, text, "add");
+
+    let mut gutter = Gutter::new(1);
+    for line in 0..text.lines().count() {
+        gutter.numbers(line as i32, &[Some(line as u32 + 1)]);
+    }
+    gutter.print(gutter_buffer, buffer.line_count());
+
+    let mut highlighter = match language {
+        Some(language) if syntax => Highlighter::new(language),
+        _ => return,
+    };
+
+    let mut line_offset = 0;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        if i >= syntax_highlight::MAX_SYNTAX_LINES {
+            break;
+        }
+        apply_syntax_tags(buffer, &highlighter.highlight_line(line), line_offset);
+        line_offset += line.chars().count() as i32;
+    }
+}
+
+fn line_tag_name(origin: char) -> &'static str {
+    match origin {
+        ' ' => "normal",
+        '+' => "add",
+        '-' => "delete",
+        _ => "other",
+    }
+}
+
+// nothing is highlighted when disabled or too long
+fn syntax_spans(diff_text: &DiffText, syntax: bool) -> Vec<Vec<SyntaxSpan>> {
+    if syntax && diff_text.lines.len() <= syntax_highlight::MAX_SYNTAX_LINES {
+        diff_content::syntax_spans(diff_text)
+    } else {
+        vec![Vec::new(); diff_text.lines.len()]
+    }
+}
+
+fn apply_syntax_tags(buffer: &TextBuffer, spans: &[SyntaxSpan], line_offset: i32) {
+    for span in spans {
+        let tag_name = match span.kind {
+            TokenKind::Keyword => "syntax_keyword",
+            TokenKind::String => "syntax_string",
+            TokenKind::Comment => "syntax_comment",
+            TokenKind::Number => "syntax_number",
+        };
+        let start_iter = buffer.iter_at_offset(line_offset + span.start as i32);
+        let end_iter = buffer.iter_at_offset(line_offset + span.end as i32);
+        buffer.apply_tag_by_name(tag_name, &start_iter, &end_iter);
+    }
+}
+
+fn apply_word_tags(buffer: &TextBuffer, ranges: &[(usize, usize)], line_offset: i32, added: bool) {
+    let tag_name = if added { "add_word" } else { "delete_word" };
+
+    for (start, end) in ranges {
+        let start_iter = buffer.iter_at_offset(line_offset + *start as i32);
+        let end_iter = buffer.iter_at_offset(line_offset + *end as i32);
+        buffer.apply_tag_by_name(tag_name, &start_iter, &end_iter);
+    }
+}
+
+fn highlight_matches(buffer: &TextBuffer, matcher: &PickaxeMatcher, text: &str, line_offset: i32) {
+    for (start, end) in matcher.find_in_line(text) {
+        let start_iter = buffer.iter_at_offset(line_offset + start as i32);
+        let end_iter = buffer.iter_at_offset(line_offset + end as i32);
+        buffer.apply_tag_by_name("match", &start_iter, &end_iter);
+    }
+}
+
+fn print_hidden_lines(diff_text: &DiffText, buffer: &TextBuffer, iter: &mut gtk::TextIter) {
+    if diff_text.hidden_lines > 0 {
+        let text = format!("... {} more lines\n", diff_text.hidden_lines);
+        gtk_utils::text_buffer_insert_with_tag_by_name(buffer, iter, &text, "other");
+    }
+}
+
+pub fn print_combined_diff_to_text_view(files: &[CombinedDiffFile], buffer: &TextBuffer) {
+    buffer.set_text("");
+
+    let mut iter = buffer.start_iter();
+    for file in files {
+        let header = format!("diff --cc {}\n", file.path);
+        gtk_utils::text_buffer_insert_with_tag_by_name(&buffer, &mut iter, &header, "other");
+
+        if file.is_binary {
+            gtk_utils::text_buffer_insert_with_tag_by_name(&buffer, &mut iter, "Binary files differ\n", "other");
+            continue;
+        }
+
+        for hunk in &file.hunks {
+            let header = format!("{}\n", hunk.header);
+            gtk_utils::text_buffer_insert_with_tag_by_name(&buffer, &mut iter, &header, "other");
+
+            for line in &hunk.lines {
+                let tag_name = if line.origins.contains(&'+') {
+                    "add"
+                } else if line.origins.contains(&'-') {
+                    "delete"
+                } else {
+                    "normal"
+                };
+
+                let mut str = line.origins.iter().collect::<String>();
+                str.push_str(&line.content);
+                str.push('\n');
+
+                gtk_utils::text_buffer_insert_with_tag_by_name(&buffer, &mut iter, &str, tag_name);
+            }
+        }
+    }
 }
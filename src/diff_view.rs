@@ -0,0 +1,419 @@
+use git2::{Diff, Error, Repository};
+use glib::Cast;
+use gtk::gdk;
+use gtk::traits::{
+    BoxExt, ButtonExt, ComboBoxExt, ComboBoxTextExt, ContainerExt, ScrolledWindowExt, TextBufferExt,
+    TextViewExt, ToggleButtonExt, WidgetExt,
+};
+use gtk::prelude::StackExt;
+use gtk::{Inhibit, Orientation};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::commit_diff_util::CombinedDiffFile;
+use crate::diff_content;
+use crate::diff_content::DiffText;
+use crate::diff_text_view_util;
+use crate::diff_text_view_util::{create_diff_text_buffer, DiffLayout, PrintOptions};
+use crate::gtk_utils;
+use crate::pickaxe::PickaxeMatcher;
+use crate::syntax_highlight::Language;
+
+// a diff shown as a unified patch, side by side or word by word
+pub struct DiffView {
+    container: gtk::Box,
+    mode_combo_box: gtk::ComboBoxText,
+    syntax_check_button: gtk::CheckButton,
+    navigation_box: gtk::Box,
+    stack: gtk::Stack,
+
+    unified_text_view: gtk::TextView,
+    unified_gutter_view: gtk::TextView,
+    old_text_view: gtk::TextView,
+    old_gutter_view: gtk::TextView,
+    new_text_view: gtk::TextView,
+    new_gutter_view: gtk::TextView,
+
+    // kept to print again when the mode is switched; None while a plain text is shown
+    diff_text: RefCell<Option<DiffText>>,
+    file_text: RefCell<Option<(String, Option<&'static Language>, u64)>>,
+    highlight: RefCell<Option<PickaxeMatcher>>,
+    // indices of the folded file and hunk headers in diff_text
+    collapsed: RefCell<HashSet<usize>>,
+    layout: RefCell<DiffLayout>,
+}
+
+// also the names of the stack pages; the word diff uses the unified page
+const UNIFIED_MODE: &str = "unified";
+const SPLIT_MODE: &str = "split";
+const WORD_MODE: &str = "word";
+
+impl DiffView {
+    pub fn new() -> Rc<DiffView> {
+        let container = gtk::Box::new(Orientation::Vertical, 0);
+
+        let mode_box = gtk::Box::new(Orientation::Horizontal, 6);
+
+        // n/p and N/P in the text views do the same
+        let navigation_box = gtk::Box::new(Orientation::Horizontal, 0);
+        navigation_box.set_sensitive(false);
+        let navigation_buttons = [
+            ("\u{2191} File", "Previous file (Shift+P)", false, true),
+            ("\u{2191} Hunk", "Previous hunk (p)", false, false),
+            ("\u{2193} Hunk", "Next hunk (n)", true, false),
+            ("\u{2193} File", "Next file (Shift+N)", true, true),
+        ];
+        let navigation_buttons = navigation_buttons.map(|(label, tooltip, forward, file)| {
+            let button = gtk::Button::with_label(label);
+            button.set_tooltip_text(Some(tooltip));
+            navigation_box.pack_start(&button, false, true, 0);
+            (button, forward, file)
+        });
+        mode_box.pack_start(&navigation_box, false, true, 0);
+
+        let mode_combo_box = gtk::ComboBoxText::new();
+        mode_combo_box.append(Some(UNIFIED_MODE), "Unified");
+        mode_combo_box.append(Some(SPLIT_MODE), "Side by side");
+        mode_combo_box.append(Some(WORD_MODE), "Word diff");
+        mode_combo_box.set_active_id(Some(UNIFIED_MODE));
+        mode_combo_box.set_sensitive(false);
+        mode_box.pack_end(&mode_combo_box, false, true, 0);
+        let syntax_check_button = gtk::CheckButton::with_label("Syntax");
+        syntax_check_button.set_active(true);
+        syntax_check_button.set_tooltip_text(Some("Highlight the syntax of diffs up to a few thousand lines"));
+        mode_box.pack_end(&syntax_check_button, false, true, 0);
+        container.pack_start(&mode_box, false, true, 0);
+
+        let stack = gtk::Stack::new();
+
+        let unified_box = gtk::Box::new(Orientation::Horizontal, 0);
+        let unified_text_view = Self::create_text_view();
+        let unified_scrolled = gtk::ScrolledWindow::builder().build();
+        unified_scrolled.add(&unified_text_view);
+        let unified_gutter_view = Self::create_gutter_view(&unified_box, &unified_scrolled.vadjustment());
+        unified_box.pack_start(&unified_scrolled, true, true, 0);
+        stack.add_named(&unified_box, UNIFIED_MODE);
+
+        // both columns and their gutters share the vertical scrolling
+        let split_box = gtk::Box::new(Orientation::Horizontal, 3);
+        split_box.set_homogeneous(true);
+
+        let old_box = gtk::Box::new(Orientation::Horizontal, 0);
+        let old_text_view = Self::create_text_view();
+        let old_scrolled = gtk::ScrolledWindow::builder().build();
+        old_scrolled.add(&old_text_view);
+        let vadjustment = old_scrolled.vadjustment();
+        let old_gutter_view = Self::create_gutter_view(&old_box, &vadjustment);
+        old_box.pack_start(&old_scrolled, true, true, 0);
+        split_box.pack_start(&old_box, true, true, 0);
+
+        let new_box = gtk::Box::new(Orientation::Horizontal, 0);
+        let new_text_view = Self::create_text_view();
+        let new_scrolled = gtk::ScrolledWindow::builder()
+            .vadjustment(&vadjustment)
+            .build();
+        new_scrolled.add(&new_text_view);
+        let new_gutter_view = Self::create_gutter_view(&new_box, &vadjustment);
+        new_box.pack_start(&new_scrolled, true, true, 0);
+        split_box.pack_start(&new_box, true, true, 0);
+
+        stack.add_named(&split_box, SPLIT_MODE);
+        container.pack_start(&stack, true, true, 0);
+
+        let diff_view = Rc::new(DiffView {
+            container,
+            mode_combo_box,
+            syntax_check_button,
+            navigation_box,
+            stack,
+            unified_text_view,
+            unified_gutter_view,
+            old_text_view,
+            old_gutter_view,
+            new_text_view,
+            new_gutter_view,
+            diff_text: RefCell::new(None),
+            file_text: RefCell::new(None),
+            highlight: RefCell::new(None),
+            collapsed: RefCell::new(HashSet::new()),
+            layout: RefCell::new(DiffLayout::default()),
+        });
+
+        let w = Rc::downgrade(&diff_view);
+        diff_view.mode_combo_box.connect_changed(move |_| {
+            w.upgrade().unwrap().print();
+        });
+
+        let w = Rc::downgrade(&diff_view);
+        diff_view.syntax_check_button.connect_toggled(move |_| {
+            let w = w.upgrade().unwrap();
+            w.print();
+            w.print_file_text();
+        });
+
+        for (button, forward, file) in navigation_buttons {
+            let w = Rc::downgrade(&diff_view);
+            button.connect_clicked(move |_| {
+                w.upgrade().unwrap().jump(forward, file);
+            });
+        }
+
+        for text_view in [&diff_view.unified_text_view, &diff_view.old_text_view, &diff_view.new_text_view] {
+            let w = Rc::downgrade(&diff_view);
+            text_view.connect_key_press_event(move |_, event| {
+                if event.state().intersects(gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::MOD1_MASK) {
+                    return Inhibit(false);
+                }
+
+                let key = event.keyval();
+                let (forward, file) = if key == gdk::keys::constants::n {
+                    (true, false)
+                } else if key == gdk::keys::constants::p {
+                    (false, false)
+                } else if key == gdk::keys::constants::N {
+                    (true, true)
+                } else if key == gdk::keys::constants::P {
+                    (false, true)
+                } else {
+                    return Inhibit(false);
+                };
+
+                w.upgrade().unwrap().jump(forward, file);
+                Inhibit(true)
+            });
+
+            // a click on a file or hunk header folds or unfolds it
+            let w = Rc::downgrade(&diff_view);
+            text_view.connect_button_release_event(move |text_view, event| {
+                let selecting = text_view.buffer().is_some_and(|x| x.has_selection());
+                if event.button() != 1 || selecting {
+                    return Inhibit(false);
+                }
+
+                let (x, y) = event.position();
+                let (x, y) = text_view.window_to_buffer_coords(gtk::TextWindowType::Widget, x as i32, y as i32);
+                if let Some(iter) = text_view.iter_at_location(x, y) {
+                    w.upgrade().unwrap().toggle_header(iter.line());
+                }
+                Inhibit(false)
+            });
+        }
+
+        diff_view
+    }
+
+    fn create_text_view() -> gtk::TextView {
+        gtk::TextView::builder()
+            .editable(false)
+            .buffer(&create_diff_text_buffer())
+            .monospace(true)
+            .build()
+    }
+
+    // line numbers scrolled along with the text view packed after it
+    fn create_gutter_view(parent: &gtk::Box, vadjustment: &gtk::Adjustment) -> gtk::TextView {
+        let gutter_view = gtk::TextView::builder()
+            .editable(false)
+            .cursor_visible(false)
+            .can_focus(false)
+            .buffer(&create_diff_text_buffer())
+            .monospace(true)
+            .build();
+
+        let scrolled = gtk::ScrolledWindow::builder()
+            .vadjustment(vadjustment)
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .vscrollbar_policy(gtk::PolicyType::External)
+            .build();
+        scrolled.add(&gutter_view);
+        parent.pack_start(&scrolled, false, true, 0);
+
+        gutter_view
+    }
+
+    pub fn widget(&self) -> gtk::Widget {
+        self.container.clone().upcast::<gtk::Widget>()
+    }
+
+    // returns whether some lines are left out because of max_lines
+    pub fn show_diff(&self,
+                     repo: &Repository,
+                     diff: &Diff,
+                     highlight: Option<&PickaxeMatcher>,
+                     max_lines: Option<usize>)
+                     -> Result<bool, Error> {
+        let diff_text = diff_content::collect_diff_text(repo, diff, max_lines)?;
+        let truncated = diff_text.hidden_lines > 0;
+
+        self.diff_text.replace(Some(diff_text));
+        self.highlight.replace(highlight.cloned());
+        self.collapsed.borrow_mut().clear();
+        self.print();
+
+        Ok(truncated)
+    }
+
+    // combined diffs are shown only as unified patches
+    pub fn show_combined_diff(&self, files: &[CombinedDiffFile]) {
+        self.show_plain();
+        if let Some(buffer) = self.unified_text_view.buffer() {
+            diff_text_view_util::print_combined_diff_to_text_view(files, &buffer);
+        }
+    }
+
+    pub fn show_text(&self, text: &str, tag_name: &str) {
+        self.show_plain();
+        if let Some(buffer) = self.unified_text_view.buffer() {
+            buffer.set_text("");
+            let mut iter = buffer.start_iter();
+            gtk_utils::text_buffer_insert_with_tag_by_name(&buffer, &mut iter, text, tag_name);
+        }
+    }
+
+    // a whole file, highlighted like the new side of a diff
+    pub fn show_file_text(&self, text: &str, language: Option<&'static Language>, hidden_bytes: u64) {
+        self.show_plain();
+        self.file_text.replace(Some((text.to_string(), language, hidden_bytes)));
+        self.print_file_text();
+    }
+
+    fn show_plain(&self) {
+        self.diff_text.replace(None);
+        self.file_text.replace(None);
+        self.layout.replace(DiffLayout::default());
+        self.mode_combo_box.set_sensitive(false);
+        self.navigation_box.set_sensitive(false);
+        if let Some(buffer) = self.unified_gutter_view.buffer() {
+            buffer.set_text("");
+        }
+        self.stack.set_visible_child_name(UNIFIED_MODE);
+    }
+
+    fn print(&self) {
+        let diff_text = self.diff_text.borrow();
+        let diff_text = match diff_text.as_ref() {
+            Some(diff_text) => diff_text,
+            None => return,
+        };
+        let highlight = self.highlight.borrow();
+        let collapsed = self.collapsed.borrow();
+        let options = PrintOptions {
+            highlight: highlight.as_ref(),
+            syntax: self.syntax_check_button.is_active(),
+            collapsed: &collapsed,
+        };
+        self.mode_combo_box.set_sensitive(true);
+        self.navigation_box.set_sensitive(true);
+
+        let (buffer, gutter_buffer) = Self::buffers(&self.unified_text_view, &self.unified_gutter_view);
+        let layout = match self.mode_combo_box.active_id().as_deref() {
+            Some(SPLIT_MODE) => {
+                self.stack.set_visible_child_name(SPLIT_MODE);
+                let old_buffers = Self::buffers(&self.old_text_view, &self.old_gutter_view);
+                let new_buffers = Self::buffers(&self.new_text_view, &self.new_gutter_view);
+                diff_text_view_util::print_split_diff_text(diff_text,
+                                                           (&old_buffers.0, &old_buffers.1),
+                                                           (&new_buffers.0, &new_buffers.1),
+                                                           &options)
+            }
+            Some(WORD_MODE) => {
+                self.stack.set_visible_child_name(UNIFIED_MODE);
+                diff_text_view_util::print_word_diff_text(diff_text, &buffer, &gutter_buffer, &options)
+            }
+            _ => {
+                self.stack.set_visible_child_name(UNIFIED_MODE);
+                diff_text_view_util::print_diff_text(diff_text, &buffer, &gutter_buffer, &options)
+            }
+        };
+        self.layout.replace(layout);
+    }
+
+    fn buffers(text_view: &gtk::TextView, gutter_view: &gtk::TextView) -> (gtk::TextBuffer, gtk::TextBuffer) {
+        (text_view.buffer().unwrap(), gutter_view.buffer().unwrap())
+    }
+
+    fn print_file_text(&self) {
+        if let Some((text, language, hidden_bytes)) = self.file_text.borrow().as_ref() {
+            let (buffer, gutter_buffer) = Self::buffers(&self.unified_text_view, &self.unified_gutter_view);
+            diff_text_view_util::print_file_text(text,
+                                                 *language,
+                                                 &buffer,
+                                                 &gutter_buffer,
+                                                 self.syntax_check_button.is_active());
+
+            if *hidden_bytes > 0 {
+                let mut iter = buffer.end_iter();
+                let text = format!("\n... {} more bytes\n", hidden_bytes);
+                gtk_utils::text_buffer_insert_with_tag_by_name(&buffer, &mut iter, &text, "other");
+            }
+        }
+    }
+
+    // the old column scrolls both columns
+    fn active_text_view(&self) -> &gtk::TextView {
+        if self.stack.visible_child_name().as_deref() == Some(SPLIT_MODE) {
+            &self.old_text_view
+        } else {
+            &self.unified_text_view
+        }
+    }
+
+    fn toggle_header(&self, line: i32) {
+        let index = match self.layout.borrow().header_at(line) {
+            Some(header) => header.index,
+            None => return,
+        };
+
+        {
+            let mut collapsed = self.collapsed.borrow_mut();
+            if !collapsed.remove(&index) {
+                collapsed.insert(index);
+            }
+        }
+        self.print();
+
+        let line = self.layout.borrow().headers.iter().find(|x| x.index == index).map(|x| x.first_line);
+        if let Some(line) = line {
+            self.scroll_to_line(line);
+        }
+    }
+
+    // to the next or previous hunk or file header from the top of the view
+    fn jump(&self, forward: bool, file: bool) {
+        let text_view = self.active_text_view();
+        let rect = text_view.visible_rect();
+        let (top_iter, _) = text_view.line_at_y(rect.y());
+        let top = top_iter.line();
+
+        let line = {
+            let layout = self.layout.borrow();
+            let mut headers = layout.headers.iter().filter(|x| x.file || !file).map(|x| x.first_line);
+            if forward {
+                headers.find(|&x| x > top)
+            } else {
+                headers.rfind(|&x| x < top)
+            }
+        };
+
+        if let Some(line) = line {
+            self.scroll_to_line(line);
+        }
+    }
+
+    fn scroll_to_line(&self, line: i32) {
+        let text_view = self.active_text_view();
+        let buffer = match text_view.buffer() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+
+        let iter = buffer.iter_at_line(line);
+        buffer.place_cursor(&iter);
+        let mark = buffer.mark("jump").or_else(|| buffer.create_mark(Some("jump"), &iter, true));
+        if let Some(mark) = mark {
+            buffer.move_mark(&mark, &iter);
+            text_view.scroll_to_mark(&mark, 0.0, true, 0.0, 0.0);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use git2::Oid;
+
+#[derive(Debug)]
+pub enum MetalGitError {
+    Git(git2::Error),
+    Io(io::Error),
+    Message(String),
+    // what was being done when the source error occurred
+    Context {
+        operation: String,
+        path: Option<PathBuf>,
+        oid: Option<Oid>,
+        source: Box<MetalGitError>,
+    },
+}
+
+impl MetalGitError {
+    pub fn new(message: &str) -> MetalGitError {
+        MetalGitError::Message(message.to_string())
+    }
+
+    fn with_context(self, operation: &str, path: Option<&Path>, oid: Option<Oid>) -> MetalGitError {
+        MetalGitError::Context {
+            operation: operation.to_string(),
+            path: path.map(|x| x.to_path_buf()),
+            oid,
+            source: Box::new(self),
+        }
+    }
+
+    // every context and the cause with its git2 class and code, one per line
+    pub fn details(&self) -> String {
+        match self {
+            MetalGitError::Git(err) => {
+                format!("{}\nclass: {:?}, code: {:?}", err.message(), err.class(), err.code())
+            }
+            MetalGitError::Io(err) => format!("{}\nkind: {:?}", err, err.kind()),
+            MetalGitError::Message(message) => message.clone(),
+            MetalGitError::Context { operation, path, oid, source } => {
+                let mut text = operation.clone();
+                if let Some(path) = path {
+                    text.push_str(&format!("\npath: {}", path.to_string_lossy()));
+                }
+                if let Some(oid) = oid {
+                    text.push_str(&format!("\ncommit: {}", oid));
+                }
+                text.push_str("\n\n");
+                text.push_str(&source.details());
+                text
+            }
+        }
+    }
+}
+
+impl fmt::Display for MetalGitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetalGitError::Git(err) => write!(f, "{}", err.message()),
+            MetalGitError::Io(err) => write!(f, "{}", err),
+            MetalGitError::Message(message) => write!(f, "{}", message),
+            MetalGitError::Context { operation, path, oid, source } => {
+                write!(f, "{}", operation)?;
+                if let Some(path) = path {
+                    write!(f, " {}", path.to_string_lossy())?;
+                }
+                if let Some(oid) = oid {
+                    write!(f, " {}", oid)?;
+                }
+                write!(f, ": {}", source)
+            }
+        }
+    }
+}
+
+impl error::Error for MetalGitError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            MetalGitError::Git(err) => Some(err),
+            MetalGitError::Io(err) => Some(err),
+            MetalGitError::Message(_) => None,
+            MetalGitError::Context { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
+
+impl From<git2::Error> for MetalGitError {
+    fn from(err: git2::Error) -> MetalGitError {
+        MetalGitError::Git(err)
+    }
+}
+
+impl From<io::Error> for MetalGitError {
+    fn from(err: io::Error) -> MetalGitError {
+        MetalGitError::Io(err)
+    }
+}
+
+// adds context to errors, like "Failed to remove" with the path
+pub trait ResultExt<T> {
+    fn context(self, operation: &str) -> Result<T, MetalGitError>;
+    fn path_context(self, operation: &str, path: &Path) -> Result<T, MetalGitError>;
+    fn oid_context(self, operation: &str, oid: Oid) -> Result<T, MetalGitError>;
+}
+
+impl<T, E> ResultExt<T> for Result<T, E>
+    where E: Into<MetalGitError>
+{
+    fn context(self, operation: &str) -> Result<T, MetalGitError> {
+        self.map_err(|e| e.into().with_context(operation, None, None))
+    }
+
+    fn path_context(self, operation: &str, path: &Path) -> Result<T, MetalGitError> {
+        self.map_err(|e| e.into().with_context(operation, Some(path), None))
+    }
+
+    fn oid_context(self, operation: &str, oid: Oid) -> Result<T, MetalGitError> {
+        self.map_err(|e| e.into().with_context(operation, None, Some(oid)))
+    }
+}
@@ -0,0 +1,64 @@
+use glib::Cast;
+use gtk::traits::{ContainerExt, ExpanderExt, InfoBarExt, LabelExt, WidgetExt};
+
+use std::rc::Rc;
+
+// shows errors at the top of a window without blocking it
+pub struct ErrorBar {
+    info_bar: gtk::InfoBar,
+    summary_label: gtk::Label,
+    details_expander: gtk::Expander,
+    details_label: gtk::Label,
+}
+
+impl ErrorBar {
+    pub fn new() -> Rc<ErrorBar> {
+        let info_bar = gtk::InfoBar::new();
+        info_bar.set_message_type(gtk::MessageType::Error);
+        info_bar.set_show_close_button(true);
+        info_bar.set_no_show_all(true);
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 3);
+
+        let summary_label = gtk::Label::new(None);
+        summary_label.set_xalign(0.0);
+        summary_label.set_line_wrap(true);
+        summary_label.set_selectable(true);
+        vbox.add(&summary_label);
+
+        let details_expander = gtk::Expander::new(Some("Details"));
+        let details_label = gtk::Label::new(None);
+        details_label.set_xalign(0.0);
+        details_label.set_selectable(true);
+        details_expander.add(&details_label);
+        vbox.add(&details_expander);
+
+        info_bar.content_area().add(&vbox);
+        vbox.show_all();
+
+        info_bar.connect_response(|info_bar, _| {
+            info_bar.hide();
+        });
+
+        Rc::new(ErrorBar {
+            info_bar,
+            summary_label,
+            details_expander,
+            details_label,
+        })
+    }
+
+    pub fn widget(&self) -> gtk::Widget {
+        self.info_bar.clone().upcast::<gtk::Widget>()
+    }
+
+    // details are hidden when empty
+    pub fn show_error(&self, summary: &str, details: &str) {
+        self.summary_label.set_text(summary);
+        self.details_label.set_text(details);
+        self.details_expander.set_expanded(false);
+        self.details_expander.set_visible(!details.is_empty());
+
+        self.info_bar.show();
+    }
+}
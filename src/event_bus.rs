@@ -0,0 +1,66 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RepositoryEvent {
+    // branches, tags, remote-tracking branches or stashes
+    RefsChanged,
+    IndexChanged,
+    // by commits, checkouts, pulls and so on
+    HeadMoved,
+    WorkTreeChanged,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SubscriptionId(usize);
+
+type Subscriber = Rc<dyn Fn(RepositoryEvent)>;
+
+// delivers repository events to any number of windows and panels of a session
+pub struct EventBus {
+    subscribers: RefCell<Vec<(SubscriptionId, Subscriber)>>,
+    next_id: Cell<usize>,
+}
+
+impl EventBus {
+    pub fn new() -> Rc<EventBus> {
+        Rc::new(EventBus {
+            subscribers: RefCell::new(Vec::new()),
+            next_id: Cell::new(0),
+        })
+    }
+
+    pub fn subscribe<F>(&self, callback: F) -> SubscriptionId
+        where F: Fn(RepositoryEvent) + 'static
+    {
+        let id = SubscriptionId(self.next_id.get());
+        self.next_id.set(id.0 + 1);
+
+        self.subscribers.borrow_mut().push((id, Rc::new(callback)));
+
+        id
+    }
+
+    pub fn unsubscribe(&self, id: SubscriptionId) {
+        self.subscribers.borrow_mut().retain(|(x, _)| *x != id);
+    }
+
+    pub fn publish(&self, event: RepositoryEvent) {
+        // subscribers may subscribe or publish while being notified
+        let subscribers = self.subscribers
+                              .borrow()
+                              .iter()
+                              .map(|(_, x)| Rc::clone(x))
+                              .collect::<Vec<_>>();
+
+        for subscriber in subscribers {
+            subscriber(event);
+        }
+    }
+
+    pub fn publish_all(&self, events: &[RepositoryEvent]) {
+        for event in events {
+            self.publish(*event);
+        }
+    }
+}
@@ -1,22 +1,100 @@
 use gtk::prelude::*;
 
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use crate::error_bar::ErrorBar;
+
 #[macro_export]
 macro_rules! dialog_when_error {
 	($message_template:expr, $e:expr) => (
 		if let Err(err) = $e {
+			let err = crate::error::MetalGitError::from(err);
 			let msg = format!($message_template, err);
-			crate::gtk_utils::message_box_error(&msg)
+			crate::gtk_utils::report_error(&msg, &err.details())
 		}
 	)
 }
 
-pub fn message_box_error(message: &str) {
+thread_local! {
+    // error bars of windows which are shown or hidden
+    static ERROR_BARS: RefCell<Vec<(glib::WeakRef<gtk::Window>, Weak<ErrorBar>)>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn register_error_bar(window: &gtk::Window, error_bar: &Rc<ErrorBar>) {
+    ERROR_BARS.with(|error_bars| {
+        let mut error_bars = error_bars.borrow_mut();
+        error_bars.retain(|(w, b)| w.upgrade().is_some() && b.upgrade().is_some());
+        error_bars.push((window.downgrade(), Rc::downgrade(error_bar)));
+    });
+}
+
+pub fn unregister_error_bar(window: &gtk::Window) {
+    ERROR_BARS.with(|error_bars| {
+        error_bars.borrow_mut().retain(|(w, _)| w.upgrade().is_some_and(|x| &x != window));
+    });
+}
+
+// the bar of the active window, or of a visible one
+fn find_error_bar() -> Option<Rc<ErrorBar>> {
+    ERROR_BARS.with(|error_bars| {
+        let error_bars = error_bars.borrow();
+        let visible = error_bars.iter()
+                                .filter_map(|(w, b)| Some((w.upgrade()?, b.upgrade()?)))
+                                .filter(|(w, _)| w.is_visible())
+                                .collect::<Vec<_>>();
+
+        visible.iter()
+               .find(|(w, _)| w.is_active())
+               .or_else(|| visible.first())
+               .map(|(_, b)| Rc::clone(b))
+    })
+}
+
+// shows the error in the error bar, or in a dialog which does not block when no window has a bar
+pub fn report_error(message: &str, details: &str) {
+    if let Some(error_bar) = find_error_bar() {
+        error_bar.show_error(message, details);
+        return;
+    }
+
     let dialog = gtk::MessageDialog::new(None::<&gtk::Window>,
                                          gtk::DialogFlags::empty(),
                                          gtk::MessageType::Error,
                                          gtk::ButtonsType::Ok,
                                          message);
-    dialog.run();
+    if !details.is_empty() && details != message {
+        dialog.set_secondary_text(Some(details));
+    }
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show();
+}
+
+pub fn message_box_error(message: &str) {
+    report_error(message, "");
+}
+
+pub fn message_box_info(message: &str) {
+    let dialog = gtk::MessageDialog::new(None::<&gtk::Window>,
+                                         gtk::DialogFlags::empty(),
+                                         gtk::MessageType::Info,
+                                         gtk::ButtonsType::Ok,
+                                         message);
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show();
+}
+
+// returns true when "Yes" is chosen
+pub fn message_box_question(message: &str) -> bool {
+    let dialog = gtk::MessageDialog::new(None::<&gtk::Window>,
+                                         gtk::DialogFlags::empty(),
+                                         gtk::MessageType::Question,
+                                         gtk::ButtonsType::YesNo,
+                                         message);
+    let response = dialog.run();
+    dialog.close();
+
+    response == gtk::ResponseType::Yes
 }
 
 pub fn text_buffer_insert_with_tag_by_name(buffer: &gtk::TextBuffer,
@@ -30,3 +108,58 @@ pub fn text_buffer_insert_with_tag_by_name(buffer: &gtk::TextBuffer,
 
     buffer.apply_tag_by_name(tag_name, &start_iter, &iter);
 }
+
+pub fn choose_directory(title: &str, parent: Option<&gtk::Window>) -> Option<std::path::PathBuf> {
+    let dialog = gtk::FileChooserDialog::with_buttons(Some(title),
+                                                      parent,
+                                                      gtk::FileChooserAction::SelectFolder,
+                                                      &[("Cancel", gtk::ResponseType::Cancel),
+                                                        ("OK", gtk::ResponseType::Accept)]);
+
+    let path = if dialog.run() == gtk::ResponseType::Accept {
+        dialog.filename()
+    } else {
+        None
+    };
+    dialog.close();
+
+    path
+}
+
+// asks values for the labels; returns None when cancelled
+pub fn input_box(title: &str, fields: &[(&str, &str)]) -> Option<Vec<String>> {
+    let dialog = gtk::Dialog::with_buttons(Some(title),
+                                           None::<&gtk::Window>,
+                                           gtk::DialogFlags::MODAL,
+                                           &[("Cancel", gtk::ResponseType::Cancel),
+                                             ("OK", gtk::ResponseType::Ok)]);
+    dialog.set_default_response(gtk::ResponseType::Ok);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(6);
+    grid.set_border_width(6);
+
+    let mut entries = Vec::new();
+    for (i, &(label, value)) in fields.iter().enumerate() {
+        let label = gtk::Label::new(Some(label));
+        label.set_xalign(0.0);
+        grid.attach(&label, 0, i as i32, 1, 1);
+
+        let entry = gtk::Entry::new();
+        entry.set_text(value);
+        entry.set_width_chars(40);
+        entry.set_activates_default(true);
+        grid.attach(&entry, 1, i as i32, 1, 1);
+        entries.push(entry);
+    }
+
+    dialog.content_area().add(&grid);
+    dialog.show_all();
+
+    let response = dialog.run();
+    let values = entries.iter().map(|x| x.text().to_string()).collect();
+    dialog.close();
+
+    if response == gtk::ResponseType::Ok { Some(values) } else { None }
+}
@@ -0,0 +1,69 @@
+use chrono::*;
+
+use crate::railway::RailwayStation;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FilterField {
+    Message,
+    Author,
+    Committer,
+    Sha,
+    RefName,
+}
+
+pub struct HistoryFilter {
+    pub field: FilterField,
+    pub text: String,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+impl HistoryFilter {
+    pub fn new(field: FilterField, text: &str) -> HistoryFilter {
+        HistoryFilter {
+            field,
+            text: text.to_string(),
+            since: None,
+            until: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty() && self.since.is_none() && self.until.is_none()
+    }
+
+    pub fn matches(&self, station: &RailwayStation) -> bool {
+        if self.since.map(|x| station.timestamp < x).unwrap_or(false) ||
+           self.until.map(|x| station.timestamp >= x).unwrap_or(false) {
+            return false;
+        }
+
+        if self.text.is_empty() {
+            return true;
+        }
+
+        let text = self.text.to_lowercase();
+        let contains = |s: &str| s.to_lowercase().contains(&text);
+
+        match self.field {
+            FilterField::Message => contains(&station.message),
+            FilterField::Author => contains(&station.author_name),
+            FilterField::Committer => contains(&station.committer_name),
+            FilterField::Sha => station.oid.to_string().starts_with(&text),
+            FilterField::RefName => station.refs.iter().any(|x| contains(&x.name)),
+        }
+    }
+}
+
+// returns the timestamp of the beginning of the day in the local time zone
+pub fn parse_date(date: &str) -> Option<i64> {
+    let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok()?;
+    let datetime = date.and_hms_opt(0, 0, 0)?;
+
+    Local.from_local_datetime(&datetime).earliest().map(|x| x.timestamp())
+}
+
+// returns the timestamp of the end of the day, which is exclusive
+pub fn parse_date_end(date: &str) -> Option<i64> {
+    parse_date(date).map(|x| x + 24 * 60 * 60)
+}
@@ -0,0 +1,251 @@
+use glib::Cast;
+use gtk::prelude::{ComboBoxExtManual, EditableSignals};
+use gtk::traits::{
+    BoxExt, ButtonExt, ComboBoxExt, ComboBoxTextExt, ContainerExt, EntryExt, LabelExt,
+    SearchBarExt, SearchEntryExt, StyleContextExt, WidgetExt,
+};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::history_filter;
+use crate::history_filter::{FilterField, HistoryFilter};
+use crate::pickaxe::PickaxeQuery;
+
+pub struct HistorySearchBar {
+    search_bar: gtk::SearchBar,
+
+    search_entry: gtk::SearchEntry,
+    field_combo_box: gtk::ComboBoxText,
+    since_entry: gtk::Entry,
+    until_entry: gtk::Entry,
+    previous_button: gtk::Button,
+    next_button: gtk::Button,
+    match_label: gtk::Label,
+
+    filter_changed: RefCell<Box<dyn Fn()>>,
+    next_requested: RefCell<Box<dyn Fn()>>,
+    previous_requested: RefCell<Box<dyn Fn()>>,
+}
+
+#[derive(Clone, Copy)]
+enum SearchMode {
+    Filter(FilterField),
+    PickaxeOccurrences,
+    PickaxeChangedLines,
+}
+
+// the order of items in field_combo_box
+const SEARCH_MODES: [(SearchMode, &str); 7] = [
+    (SearchMode::Filter(FilterField::Message), "Message"),
+    (SearchMode::Filter(FilterField::Author), "Author"),
+    (SearchMode::Filter(FilterField::Committer), "Committer"),
+    (SearchMode::Filter(FilterField::Sha), "SHA"),
+    (SearchMode::Filter(FilterField::RefName), "Ref"),
+    (SearchMode::PickaxeOccurrences, "Pickaxe -S"),
+    (SearchMode::PickaxeChangedLines, "Pickaxe -G"),
+];
+
+impl HistorySearchBar {
+    pub fn new() -> Rc<HistorySearchBar> {
+        let search_bar = gtk::SearchBar::new();
+        search_bar.set_show_close_button(true);
+
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+
+        let field_combo_box = gtk::ComboBoxText::new();
+        for (_, label) in SEARCH_MODES.iter() {
+            field_combo_box.append_text(label);
+        }
+        field_combo_box.set_active(Some(0));
+        hbox.pack_start(&field_combo_box, false, true, 0);
+
+        let search_entry = gtk::SearchEntry::new();
+        search_entry.set_width_chars(30);
+        hbox.pack_start(&search_entry, true, true, 0);
+
+        let since_entry = gtk::Entry::new();
+        since_entry.set_placeholder_text(Some("since (YYYY-MM-DD)"));
+        hbox.pack_start(&since_entry, false, true, 0);
+
+        let until_entry = gtk::Entry::new();
+        until_entry.set_placeholder_text(Some("until (YYYY-MM-DD)"));
+        hbox.pack_start(&until_entry, false, true, 0);
+
+        let previous_button = gtk::Button::from_icon_name(Some("go-up-symbolic"),
+                                                          gtk::IconSize::Button);
+        hbox.pack_start(&previous_button, false, true, 0);
+        let next_button = gtk::Button::from_icon_name(Some("go-down-symbolic"),
+                                                      gtk::IconSize::Button);
+        hbox.pack_start(&next_button, false, true, 0);
+
+        let match_label = gtk::Label::new(None);
+        hbox.pack_start(&match_label, false, true, 0);
+
+        search_bar.add(&hbox);
+        search_bar.connect_entry(&search_entry);
+
+        let history_search_bar = Rc::new(HistorySearchBar {
+            search_bar,
+            search_entry,
+            field_combo_box,
+            since_entry,
+            until_entry,
+            previous_button,
+            next_button,
+            match_label,
+            filter_changed: RefCell::new(Box::new(|| {})),
+            next_requested: RefCell::new(Box::new(|| {})),
+            previous_requested: RefCell::new(Box::new(|| {})),
+        });
+
+        let w = Rc::downgrade(&history_search_bar);
+        history_search_bar.search_entry.connect_search_changed(move |_| {
+            w.upgrade().unwrap().filter_changed.borrow()();
+        });
+
+        let w = Rc::downgrade(&history_search_bar);
+        history_search_bar.field_combo_box.connect_changed(move |_| {
+            w.upgrade().unwrap().filter_changed.borrow()();
+        });
+
+        for entry in &[&history_search_bar.since_entry, &history_search_bar.until_entry] {
+            let w = Rc::downgrade(&history_search_bar);
+            entry.connect_changed(move |entry| {
+                show_date_validity(entry);
+                w.upgrade().unwrap().filter_changed.borrow()();
+            });
+        }
+
+        let w = Rc::downgrade(&history_search_bar);
+        history_search_bar.search_entry.connect_activate(move |_| {
+            w.upgrade().unwrap().next_requested.borrow()();
+        });
+
+        let w = Rc::downgrade(&history_search_bar);
+        history_search_bar.search_entry.connect_next_match(move |_| {
+            w.upgrade().unwrap().next_requested.borrow()();
+        });
+
+        let w = Rc::downgrade(&history_search_bar);
+        history_search_bar.search_entry.connect_previous_match(move |_| {
+            w.upgrade().unwrap().previous_requested.borrow()();
+        });
+
+        let w = Rc::downgrade(&history_search_bar);
+        history_search_bar.next_button.connect_clicked(move |_| {
+            w.upgrade().unwrap().next_requested.borrow()();
+        });
+
+        let w = Rc::downgrade(&history_search_bar);
+        history_search_bar.previous_button.connect_clicked(move |_| {
+            w.upgrade().unwrap().previous_requested.borrow()();
+        });
+
+        let w = Rc::downgrade(&history_search_bar);
+        history_search_bar.search_bar.connect_search_mode_enabled_notify(move |search_bar| {
+            // leaving the search mode shows all the rows again
+            if !search_bar.is_search_mode() {
+                w.upgrade().unwrap().filter_changed.borrow()();
+            }
+        });
+
+        history_search_bar
+    }
+
+    pub fn widget(&self) -> gtk::Widget {
+        self.search_bar.clone().upcast::<gtk::Widget>()
+    }
+
+    pub fn toggle(&self) {
+        let search_mode = self.search_bar.is_search_mode();
+        self.search_bar.set_search_mode(!search_mode);
+    }
+
+    pub fn filter(&self) -> HistoryFilter {
+        if !self.search_bar.is_search_mode() {
+            return HistoryFilter::new(FilterField::Message, "");
+        }
+
+        // the text of pickaxe is not matched with the stations
+        let mut filter = match self.search_mode() {
+            SearchMode::Filter(field) => HistoryFilter::new(field, &self.search_entry.text()),
+            _ => HistoryFilter::new(FilterField::Message, ""),
+        };
+        filter.since = history_filter::parse_date(&self.since_entry.text());
+        filter.until = history_filter::parse_date_end(&self.until_entry.text());
+
+        filter
+    }
+
+    // returns the query only when the pickaxe mode is selected
+    pub fn pickaxe_query(&self) -> Option<PickaxeQuery> {
+        if !self.search_bar.is_search_mode() {
+            return None;
+        }
+
+        let text = self.search_entry.text().to_string();
+        if text.is_empty() {
+            return None;
+        }
+
+        match self.search_mode() {
+            SearchMode::Filter(_) => None,
+            SearchMode::PickaxeOccurrences => Some(PickaxeQuery::Occurrences(text)),
+            SearchMode::PickaxeChangedLines => Some(PickaxeQuery::ChangedLines(text)),
+        }
+    }
+
+    fn search_mode(&self) -> SearchMode {
+        self.field_combo_box
+            .active()
+            .map(|x| SEARCH_MODES[x as usize].0)
+            .unwrap_or(SearchMode::Filter(FilterField::Message))
+    }
+
+    pub fn set_match_count(&self, count: Option<usize>) {
+        match count {
+            Some(count) => self.match_label.set_text(&format!("{} matches", count)),
+            None => self.match_label.set_text(""),
+        }
+    }
+
+    pub fn set_status_text(&self, text: &str) {
+        self.match_label.set_text(text);
+    }
+
+    pub fn connect_filter_changed<F>(&self, callback: F)
+        where F: Fn() + 'static
+    {
+        *self.filter_changed.borrow_mut() = Box::new(callback);
+    }
+
+    pub fn connect_next_requested<F>(&self, callback: F)
+        where F: Fn() + 'static
+    {
+        *self.next_requested.borrow_mut() = Box::new(callback);
+    }
+
+    pub fn connect_previous_requested<F>(&self, callback: F)
+        where F: Fn() + 'static
+    {
+        *self.previous_requested.borrow_mut() = Box::new(callback);
+    }
+}
+
+// an unparsable date is not used as a bound, so tell it instead of filtering silently without it
+fn show_date_validity(entry: &gtk::Entry) {
+    let text = entry.text();
+    let valid = text.trim().is_empty() || history_filter::parse_date(&text).is_some();
+
+    let style_context = entry.style_context();
+    if valid {
+        style_context.remove_class("error");
+        entry.set_icon_from_icon_name(gtk::EntryIconPosition::Secondary, None);
+    } else {
+        style_context.add_class("error");
+        entry.set_icon_from_icon_name(gtk::EntryIconPosition::Secondary, Some("dialog-warning-symbolic"));
+        entry.set_icon_tooltip_text(gtk::EntryIconPosition::Secondary,
+                                    Some("Not a date like 2024-01-31; the bound is ignored"));
+    }
+}
@@ -1,29 +1,56 @@
 use crate::commit_diff_panel::CommitDiffPanel;
+use crate::error::{MetalGitError, ResultExt};
+use crate::error_bar::ErrorBar;
+use crate::event_bus::{EventBus, RepositoryEvent, SubscriptionId};
+use crate::history_search_bar::HistorySearchBar;
+use crate::patch_util;
+use crate::remote_bar::RemoteBar;
+use crate::remote_util;
+use crate::pickaxe;
+use crate::pickaxe::{PickaxeEvent, PickaxeQuery};
 use crate::railway;
 use crate::repository_manager::RepositoryManager;
 use crate::station_cell_renderer::StationCellRenderer;
 use crate::station_wrapper::StationWrapper;
-use crate::window_manager::WindowManager;
-use git2::Error;
+use crate::session::Session;
+use git2::{Error, Oid};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
 
+use glib::ToValue;
 use gtk::prelude::{BuilderExtManual, GtkListStoreExtManual, NotebookExtManual};
 use gtk::traits::{
-    ButtonExt, GtkListStoreExt, GtkWindowExt, TextBufferExt, TextViewExt, TreeModelExt,
-    TreeSelectionExt, TreeViewColumnExt, TreeViewExt, WidgetExt,
+    BoxExt, ButtonExt, DialogExt, ToggleButtonExt, FileChooserExt, GtkListStoreExt, GtkWindowExt, GtkMenuItemExt, MenuButtonExt, MenuShellExt, TextBufferExt, TextViewExt,
+    TreeModelExt, TreeSelectionExt, TreeViewColumnExt, TreeViewExt, WidgetExt,
 };
 use gtk::Inhibit;
 
 pub struct HistoryWindow {
     window: gtk::Window,
 
-    window_manager: Weak<WindowManager>,
+    session: Weak<Session>,
     repository_manager: Rc<RepositoryManager>,
+    event_bus: Rc<EventBus>,
+    subscription: Cell<Option<SubscriptionId>>,
 
     commit_diff_panel: Rc<CommitDiffPanel>,
+    search_bar: Rc<HistorySearchBar>,
+    remote_bar: Rc<RemoteBar>,
+    error_bar: Rc<ErrorBar>,
 
     commit_button: gtk::Button,
     refresh_button: gtk::Button,
+    export_button: gtk::Button,
+    apply_button: gtk::Button,
+    stash_button: gtk::Button,
+    open_button: gtk::Button,
+    windows_menu_button: gtk::MenuButton,
+    show_stashes_checkbutton: gtk::CheckButton,
 
     commit_notebook: gtk::Notebook,
 
@@ -31,17 +58,27 @@ pub struct HistoryWindow {
     commit_textview: gtk::TextView,
 
     history_list_store: gtk::ListStore,
+
+    pickaxe_search: RefCell<Option<PickaxeSearch>>,
+}
+
+struct PickaxeSearch {
+    query: PickaxeQuery,
+    cancelled: Arc<AtomicBool>,
+    matches: HashSet<Oid>,
 }
 
 const COLUMN_SUBJECT: u32 = 0;
 const COLUMN_STATION: u32 = 1;
 const COLUMN_AUTHOR_NAME: u32 = 2;
 const COLUMN_TIME: u32 = 3;
+const COLUMN_MATCHED: u32 = 4;
 
 impl HistoryWindow {
     pub fn new(
-        window_manager: Weak<WindowManager>,
+        session: Weak<Session>,
         repository_manager: Rc<RepositoryManager>,
+        event_bus: Rc<EventBus>,
     ) -> Rc<HistoryWindow> {
         let builder = gtk::Builder::from_resource("/org/sunnyone/MetalGit/history_window.ui");
 
@@ -50,30 +87,84 @@ impl HistoryWindow {
             glib::types::Type::OBJECT,
             glib::types::Type::STRING,
             glib::types::Type::STRING,
+            glib::types::Type::BOOL,
         ];
 
         let commit_diff_panel = CommitDiffPanel::new(Rc::clone(&repository_manager));
 
+        let search_bar = HistorySearchBar::new();
+        let main_box: gtk::Box = builder.object("box1").unwrap();
+        let search_bar_widget = search_bar.widget();
+        main_box.pack_start(&search_bar_widget, false, true, 0);
+        main_box.reorder_child(&search_bar_widget, 1);
+
+        let remote_bar = RemoteBar::new(Rc::clone(&repository_manager),
+                                        builder.object("statusbar1").unwrap(),
+                                        Rc::clone(&event_bus));
+        let remote_bar_widget = remote_bar.widget();
+        main_box.pack_start(&remote_bar_widget, false, true, 0);
+        main_box.reorder_child(&remote_bar_widget, 1);
+
+        let error_bar = ErrorBar::new();
+        let error_bar_widget = error_bar.widget();
+        main_box.pack_start(&error_bar_widget, false, true, 0);
+        main_box.reorder_child(&error_bar_widget, 0);
+
+        let button_box: gtk::ButtonBox = builder.object("buttonbox1").unwrap();
+        let export_button = gtk::Button::with_label("Export patches...");
+        button_box.pack_start(&export_button, false, true, 0);
+        let apply_button = gtk::Button::with_label("Apply patches...");
+        button_box.pack_start(&apply_button, false, true, 0);
+        let stash_button = gtk::Button::with_label("Stashes...");
+        button_box.pack_start(&stash_button, false, true, 0);
+        let open_button = gtk::Button::with_label("Open repository...");
+        button_box.pack_start(&open_button, false, true, 0);
+        let windows_menu_button = gtk::MenuButton::builder().label("Windows").build();
+        button_box.pack_start(&windows_menu_button, false, true, 0);
+        let show_stashes_checkbutton = gtk::CheckButton::with_label("Show stashes");
+        button_box.pack_start(&show_stashes_checkbutton, false, true, 0);
+
         let history_window = HistoryWindow {
-            window_manager,
+            session,
             repository_manager,
+            event_bus,
+            subscription: Cell::new(None),
             commit_diff_panel,
+            search_bar,
+            remote_bar,
+            error_bar,
 
             window: builder.object("history_window").unwrap(),
             commit_button: builder.object("commit_button").unwrap(),
             refresh_button: builder.object("refresh_button").unwrap(),
+            export_button,
+            apply_button,
+            stash_button,
+            open_button,
+            windows_menu_button,
+            show_stashes_checkbutton,
             history_treeview: builder.object("history_treeview").unwrap(),
             commit_textview: builder.object("commit_textview").unwrap(),
 
             commit_notebook: builder.object("commit_notebook").unwrap(),
 
             history_list_store: gtk::ListStore::new(&col_types),
+
+            pickaxe_search: RefCell::new(None),
         };
 
         let history_window = Rc::new(history_window);
+        crate::gtk_utils::register_error_bar(&history_window.window, &history_window.error_bar);
 
         history_window.setup_history_tree();
 
+        let w = Rc::downgrade(&history_window);
+        history_window
+            .commit_diff_panel
+            .connect_commit_selected(move |oid| {
+                w.upgrade().unwrap().select_commit(oid);
+            });
+
         let w = Rc::downgrade(&history_window);
         history_window.commit_button.connect_clicked(move |_| {
             w.upgrade().unwrap().commit_button_clicked();
@@ -84,6 +175,87 @@ impl HistoryWindow {
             w.upgrade().unwrap().refresh_button_clicked();
         });
 
+        let w = Rc::downgrade(&history_window);
+        history_window.export_button.connect_clicked(move |_| {
+            dialog_when_error!("Failed to export patches: {}",
+                               w.upgrade().unwrap().export_button_clicked());
+        });
+
+        let w = Rc::downgrade(&history_window);
+        history_window.apply_button.connect_clicked(move |_| {
+            dialog_when_error!("Failed to apply patches: {}",
+                               w.upgrade().unwrap().apply_button_clicked());
+        });
+
+        let w = Rc::downgrade(&history_window);
+        let subscription = history_window.event_bus.subscribe(move |event| {
+            match event {
+                RepositoryEvent::RefsChanged | RepositoryEvent::HeadMoved => {
+                    if let Some(w) = w.upgrade() {
+                        w.refresh();
+                    }
+                }
+                RepositoryEvent::IndexChanged | RepositoryEvent::WorkTreeChanged => {
+                    if let Some(w) = w.upgrade() {
+                        dialog_when_error!("Failed to load the uncommitted changes: {}", w.refresh_work_tree());
+                    }
+                }
+            }
+        });
+        history_window.subscription.set(Some(subscription));
+
+        let w = Rc::downgrade(&history_window);
+        history_window.stash_button.connect_clicked(move |_| {
+            w.upgrade().unwrap().session.upgrade().unwrap().show_stash_window();
+        });
+
+        let w = Rc::downgrade(&history_window);
+        history_window.open_button.connect_clicked(move |_| {
+            w.upgrade().unwrap().session.upgrade().unwrap().window_manager().choose_repository();
+        });
+
+        let w = Rc::downgrade(&history_window);
+        history_window.show_stashes_checkbutton.connect_toggled(move |_| {
+            w.upgrade().unwrap().refresh();
+        });
+
+        let w = Rc::downgrade(&history_window);
+        history_window.search_bar.connect_filter_changed(move || {
+            let w = w.upgrade().unwrap();
+            w.cancel_pickaxe_if_changed();
+            w.apply_filter();
+        });
+
+        let w = Rc::downgrade(&history_window);
+        history_window.search_bar.connect_next_requested(move || {
+            let w = w.upgrade().unwrap();
+            if w.search_bar.pickaxe_query().is_some() && w.pickaxe_search.borrow().is_none() {
+                dialog_when_error!("Failed to search: {}", w.start_pickaxe());
+            } else {
+                w.select_next_match(true);
+            }
+        });
+
+        let w = Rc::downgrade(&history_window);
+        history_window.search_bar.connect_previous_requested(move || {
+            w.upgrade().unwrap().select_next_match(false);
+        });
+
+        let w = Rc::downgrade(&history_window);
+        history_window
+            .window
+            .connect_key_press_event(move |_, key| {
+                if key
+                    .state()
+                    .intersects(gtk::gdk::ModifierType::CONTROL_MASK)
+                    && key.keyval().name().map(|n| n == "f").unwrap_or(false)
+                {
+                    w.upgrade().unwrap().search_bar.toggle();
+                    return Inhibit(true);
+                }
+                Inhibit(false)
+            });
+
         let container = history_window.commit_diff_panel.container();
         let label = gtk::Label::new(Some("Diff"));
         history_window
@@ -105,6 +277,7 @@ impl HistoryWindow {
         col.pack_start(&subject_renderer, false);
         col.add_attribute(&subject_renderer, "markup", COLUMN_SUBJECT as i32);
         col.add_attribute(&subject_renderer, "station", COLUMN_STATION as i32);
+        col.add_attribute(&subject_renderer, "sensitive", COLUMN_MATCHED as i32);
         treeview.append_column(&col);
 
         let renderer = gtk::CellRendererText::new();
@@ -112,6 +285,7 @@ impl HistoryWindow {
         col.set_title("Author");
         col.pack_start(&renderer, false);
         col.add_attribute(&renderer, "text", COLUMN_AUTHOR_NAME as i32);
+        col.add_attribute(&renderer, "sensitive", COLUMN_MATCHED as i32);
         treeview.append_column(&col);
 
         let renderer = gtk::CellRendererText::new();
@@ -119,22 +293,76 @@ impl HistoryWindow {
         col.set_title("Time");
         col.pack_start(&renderer, false);
         col.add_attribute(&renderer, "text", COLUMN_TIME as i32);
+        col.add_attribute(&renderer, "sensitive", COLUMN_MATCHED as i32);
         treeview.append_column(&col);
 
         let commit_diff_panel = Rc::downgrade(&self.commit_diff_panel);
         let selection = treeview.selection();
+        selection.set_mode(gtk::SelectionMode::Multiple);
         let w = Rc::downgrade(self);
         selection.connect_changed(move |x| {
-            if let Some((model, iter)) = x.selected() {
-                let station_wrapper =
-                    model.value(&iter, COLUMN_STATION as i32)
+            let (tree_paths, model) = x.selected_rows();
+            let stations = tree_paths
+                .iter()
+                .filter_map(|path| model.iter(path))
+                .filter_map(|iter| {
+                    model
+                        .value(&iter, COLUMN_STATION as i32)
                         .get::<StationWrapper>()
-                        .expect("Incorrect column type");
-                let station = station_wrapper.get_station().unwrap();
-                w.upgrade().unwrap().commit_selected(&station).expect("Failed to get a commit");
+                        .ok()
+                        .and_then(|x| x.get_station())
+                })
+                .collect::<Vec<_>>();
+
+            match stations.as_slice() {
+                [station] if station.is_work_tree => {
+                    w.upgrade().unwrap().work_tree_selected();
+
+                    if let Some(panel) = commit_diff_panel.upgrade() {
+                        dialog_when_error!("{}", panel.update_work_tree());
+                    }
+                }
+                [station] => {
+                    dialog_when_error!(
+                        "{}",
+                        w.upgrade()
+                            .unwrap()
+                            .commit_selected(station)
+                            .oid_context("Failed to show the commit", station.oid)
+                    );
+
+                    if let Some(panel) = commit_diff_panel.upgrade() {
+                        dialog_when_error!(
+                            "{}",
+                            panel
+                                .update_commit(station.oid)
+                                .oid_context("Failed to diff the commit", station.oid)
+                        );
+                    }
+                }
+                // rows are ordered from newer to older
+                [new_station, old_station] if !new_station.is_work_tree => {
+                    w.upgrade()
+                        .unwrap()
+                        .commits_compared(old_station, new_station);
+
+                    if let Some(panel) = commit_diff_panel.upgrade() {
+                        dialog_when_error!(
+                            "Failed to compare commits: {}",
+                            panel.compare_commits(old_station.oid, new_station.oid)
+                        );
+                    }
+                }
+                _ => {}
+            }
+        });
 
-                if let Some(panel) = commit_diff_panel.upgrade() {
-                    panel.update_commit(station.oid).expect("Failed to update commit diff panel");
+        let w = Rc::downgrade(self);
+        treeview.connect_row_activated(move |tree_view, tree_path, _column| {
+            let model = tree_view.model().unwrap();
+            if let Some(iter) = model.iter(tree_path) {
+                if Self::station_at(&model, &iter).is_work_tree {
+                    w.upgrade().unwrap().commit_button_clicked();
                 }
             }
         });
@@ -150,12 +378,52 @@ impl HistoryWindow {
         });
     }
 
+    pub fn window(&self) -> gtk::Window {
+        self.window.clone()
+    }
+
+    pub fn present(&self) {
+        self.window.present();
+    }
+
+    // called when sessions are opened or closed
+    pub fn update_windows_menu(&self, session_paths: &[String]) {
+        let window_manager = Rc::downgrade(&self.session.upgrade().unwrap().window_manager());
+        let current = self.repository_manager.work_dir_path();
+
+        let menu = gtk::Menu::new();
+        for path in session_paths {
+            let item = gtk::MenuItem::with_label(path);
+            item.set_sensitive(*path != current);
+
+            let w = window_manager.clone();
+            let path = path.clone();
+            item.connect_activate(move |_| {
+                w.upgrade().unwrap().present_session(&path);
+            });
+            menu.append(&item);
+        }
+        menu.show_all();
+
+        self.windows_menu_button.set_popup(Some(&menu));
+    }
+
     pub fn show(&self) {
         self.refresh();
         self.window.show_all();
     }
 
-    fn load_title(&self) -> Result<(), Error> {
+    // the window itself is destroyed when it is closed
+    pub fn close(&self) {
+        if let Some(subscription) = self.subscription.take() {
+            self.event_bus.unsubscribe(subscription);
+        }
+        crate::gtk_utils::unregister_error_bar(&self.window);
+        self.commit_diff_panel.close();
+        self.remote_bar.close();
+    }
+
+    fn load_title(&self) -> Result<(), MetalGitError> {
         let repo = self.repository_manager.open()?;
 
         let mut title = String::new();
@@ -163,6 +431,13 @@ impl HistoryWindow {
             if let Some(head_shorthand) = reference.shorthand() {
                 title.push('[');
                 title.push_str(head_shorthand);
+                if let Some(ahead_behind) = remote_util::head_ahead_behind(&repo)? {
+                    let text = remote_util::format_ahead_behind(ahead_behind);
+                    if !text.is_empty() {
+                        title.push(' ');
+                        title.push_str(&text);
+                    }
+                }
                 title.push_str("] ");
             }
         }
@@ -183,7 +458,11 @@ impl HistoryWindow {
     fn load_history(&self) -> Result<(), Error> {
         self.history_list_store.clear();
 
-        let stations = railway::collect_tree(&self.repository_manager)?;
+        let options = railway::CollectOptions {
+            with_stashes: self.show_stashes_checkbutton.is_active(),
+            with_work_tree: true,
+        };
+        let stations = railway::collect_tree_with_options(&self.repository_manager, &options)?;
         for station in stations {
             let subject = Self::create_subject_markup(&station);
             let author_name = station.author_name.clone();
@@ -199,20 +478,193 @@ impl HistoryWindow {
                     (COLUMN_STATION, &station_wrapper),
                     (COLUMN_AUTHOR_NAME, &author_name),
                     (COLUMN_TIME, &time),
+                    (COLUMN_MATCHED, &true),
                 ],
             );
         }
 
+        self.apply_filter();
+
         Ok(())
     }
 
+    fn station_at(model: &impl TreeModelExt, iter: &gtk::TreeIter) -> Rc<railway::RailwayStation> {
+        let station_wrapper = model
+            .value(iter, COLUMN_STATION as i32)
+            .get::<StationWrapper>()
+            .expect("Incorrect column type");
+        station_wrapper.get_station().unwrap()
+    }
+
+    // non-matching rows are dimmed instead of hidden to keep the graph connected
+    fn apply_filter(&self) {
+        let filter = self.search_bar.filter();
+        let pickaxe_search = self.pickaxe_search.borrow();
+        let store = &self.history_list_store;
+        let mut match_count = 0;
+
+        if let Some(iter) = store.iter_first() {
+            loop {
+                let station = Self::station_at(store, &iter);
+                let matched = filter.matches(&station)
+                    && pickaxe_search
+                        .as_ref()
+                        .map(|x| x.matches.contains(&station.oid))
+                        .unwrap_or(true);
+                if matched {
+                    match_count += 1;
+                }
+                store.set_value(&iter, COLUMN_MATCHED, &matched.to_value());
+
+                if !store.iter_next(&iter) {
+                    break;
+                }
+            }
+        }
+
+        self.search_bar.set_match_count(
+            Some(match_count).filter(|_| !filter.is_empty() || pickaxe_search.is_some()),
+        );
+    }
+
+    fn start_pickaxe(self: &Rc<Self>) -> Result<(), Error> {
+        let query = match self.search_bar.pickaxe_query() {
+            Some(query) => query,
+            None => return Ok(()),
+        };
+        query.validate()?;
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.pickaxe_search.replace(Some(PickaxeSearch {
+            query: query.clone(),
+            cancelled: Arc::clone(&cancelled),
+            matches: HashSet::new(),
+        }));
+        self.commit_diff_panel.set_highlight(Some(query.clone()));
+        self.apply_filter();
+        self.search_bar.set_status_text("Searching...");
+
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let work_dir_path = self.repository_manager.work_dir_path();
+        let thread_query = query.clone();
+        let thread_cancelled = Arc::clone(&cancelled);
+        thread::spawn(move || {
+            let result =
+                pickaxe::search(&work_dir_path, &thread_query, &thread_cancelled, |event| {
+                    let _ = sender.send(event);
+                });
+            if let Err(err) = result {
+                let _ = sender.send(PickaxeEvent::Failed(err.message().to_string()));
+            }
+        });
+
+        let w = Rc::downgrade(self);
+        receiver.attach(None, move |event| {
+            let w = match w.upgrade() {
+                Some(w) => w,
+                None => return glib::Continue(false),
+            };
+
+            // the search is cancelled or replaced by another search
+            if cancelled.load(Ordering::Relaxed) {
+                return glib::Continue(false);
+            }
+
+            match event {
+                PickaxeEvent::Matched(oid) => {
+                    if let Some(search) = w.pickaxe_search.borrow_mut().as_mut() {
+                        search.matches.insert(oid);
+                    }
+                }
+                PickaxeEvent::Progress(count) => {
+                    w.apply_filter();
+                    w.search_bar
+                        .set_status_text(&format!("Searching... {} commits", count));
+                }
+                PickaxeEvent::Finished => {
+                    w.apply_filter();
+                    return glib::Continue(false);
+                }
+                PickaxeEvent::Failed(message) => {
+                    w.search_bar.set_status_text("");
+                    crate::gtk_utils::message_box_error(&format!(
+                        "Failed to search: {}",
+                        message
+                    ));
+                    return glib::Continue(false);
+                }
+            }
+
+            glib::Continue(true)
+        });
+
+        Ok(())
+    }
+
+    fn cancel_pickaxe_if_changed(&self) {
+        let query = self.search_bar.pickaxe_query();
+        let changed = self
+            .pickaxe_search
+            .borrow()
+            .as_ref()
+            .map(|x| Some(&x.query) != query.as_ref())
+            .unwrap_or(false);
+
+        if changed {
+            if let Some(search) = self.pickaxe_search.replace(None) {
+                search.cancelled.store(true, Ordering::Relaxed);
+            }
+            self.commit_diff_panel.set_highlight(None);
+        }
+    }
+
+    fn select_next_match(&self, forward: bool) {
+        let store = &self.history_list_store;
+        let row_count = store.iter_n_children(None);
+        if row_count == 0 {
+            return;
+        }
+
+        let (selected_paths, _) = self.history_treeview.selection().selected_rows();
+        let current = selected_paths
+            .first()
+            .and_then(|x| x.indices().first().cloned());
+
+        for i in 1..=row_count {
+            let index = match (current, forward) {
+                (Some(current), true) => (current + i) % row_count,
+                (Some(current), false) => (current + row_count - i) % row_count,
+                (None, true) => i - 1,
+                (None, false) => row_count - i,
+            };
+
+            let iter = store.iter_nth_child(None, index).unwrap();
+            let matched = store
+                .value(&iter, COLUMN_MATCHED as i32)
+                .get::<bool>()
+                .unwrap();
+            if matched {
+                self.select_commit(Self::station_at(store, &iter).oid);
+                return;
+            }
+        }
+    }
+
     fn create_subject_markup(station: &railway::RailwayStation) -> String {
         let mut markup = String::new();
 
-        for ref_name in &station.ref_names {
+        for railway_ref in &station.refs {
+            let color = match railway_ref.kind {
+                railway::RefKind::LocalBranch => "#007000",
+                railway::RefKind::RemoteBranch => "#0050a0",
+                railway::RefKind::Tag => "#a06000",
+                railway::RefKind::Stash => "#707070",
+                railway::RefKind::Other => "#a00000",
+            };
             let tag = format!(
-                "<span foreground=\"#a00000\"><b>[{}]</b></span>",
-                glib::markup_escape_text(&ref_name)
+                "<span foreground=\"{}\"><b>[{}]</b></span>",
+                color,
+                glib::markup_escape_text(&railway_ref.name)
             );
             markup.push_str(&tag);
         }
@@ -223,19 +675,158 @@ impl HistoryWindow {
         markup
     }
 
+    pub fn select_commit(&self, oid: Oid) {
+        let store = &self.history_list_store;
+        if let Some(iter) = store.iter_first() {
+            loop {
+                let station_wrapper = store
+                    .value(&iter, COLUMN_STATION as i32)
+                    .get::<StationWrapper>()
+                    .expect("Incorrect column type");
+                if station_wrapper.get_station().map(|x| x.oid) == Some(oid) {
+                    let path = store.path(&iter).unwrap();
+                    let selection = self.history_treeview.selection();
+                    selection.unselect_all();
+                    selection.select_path(&path);
+                    self.history_treeview
+                        .scroll_to_cell(Some(&path), None::<&gtk::TreeViewColumn>, false, 0.0, 0.0);
+                    break;
+                }
+
+                if !store.iter_next(&iter) {
+                    break;
+                }
+            }
+        }
+    }
+
     pub fn refresh(&self) {
-        dialog_when_error!("Failed to load repository: {:?}", self.load_title());
-        dialog_when_error!("Failed to load history: {:?}", self.load_history());
+        dialog_when_error!("Failed to load repository: {}", self.load_title());
+        dialog_when_error!("Failed to load history: {}", self.load_history());
+        self.remote_bar.refresh();
+    }
+
+    // the work tree station appears with the first change and disappears when the changes are committed
+    fn refresh_work_tree(&self) -> Result<(), MetalGitError> {
+        let repo = self.repository_manager.open()?;
+        let has_station = railway::has_work_tree_station(&repo)?;
+
+        let first_station = self.history_list_store
+                                .iter_first()
+                                .map(|iter| Self::station_at(&self.history_list_store, &iter));
+        let shows_station = first_station.as_ref().is_some_and(|x| x.is_work_tree);
+
+        if has_station != shows_station {
+            self.load_history()?;
+            return Ok(());
+        }
+
+        // the diff of the selected station is updated in place
+        let (tree_paths, model) = self.history_treeview.selection().selected_rows();
+        if let [tree_path] = tree_paths.as_slice() {
+            if let Some(iter) = model.iter(tree_path) {
+                if Self::station_at(&model, &iter).is_work_tree {
+                    self.commit_diff_panel.update_work_tree()?;
+                }
+            }
+        }
+
+        Ok(())
     }
 
     fn commit_button_clicked(&self) {
-        self.window_manager.upgrade().unwrap().show_commit_window();
+        self.session.upgrade().unwrap().show_commit_window();
     }
 
     fn refresh_button_clicked(&self) {
         self.refresh();
     }
 
+    fn selected_oids(&self) -> Vec<Oid> {
+        let (tree_paths, model) = self.history_treeview.selection().selected_rows();
+        tree_paths
+            .iter()
+            .filter_map(|path| model.iter(path))
+            .map(|iter| Self::station_at(&model, &iter))
+            .filter(|station| !station.is_work_tree)
+            .map(|station| station.oid)
+            .collect()
+    }
+
+    fn choose_files(&self, title: &str, action: gtk::FileChooserAction) -> Vec<PathBuf> {
+        let dialog = gtk::FileChooserDialog::with_buttons(
+            Some(title),
+            Some(&self.window),
+            action,
+            &[
+                ("Cancel", gtk::ResponseType::Cancel),
+                ("OK", gtk::ResponseType::Accept),
+            ],
+        );
+        dialog.set_select_multiple(action == gtk::FileChooserAction::Open);
+
+        let paths = if dialog.run() == gtk::ResponseType::Accept {
+            dialog.filenames()
+        } else {
+            Vec::new()
+        };
+        dialog.close();
+
+        paths
+    }
+
+    fn export_button_clicked(&self) -> Result<(), MetalGitError> {
+        // rows are ordered from newer to older, patches from older to newer
+        let mut oids = self.selected_oids();
+        if oids.is_empty() {
+            return Err(MetalGitError::new("No commit is selected"));
+        }
+        oids.reverse();
+
+        let output_dir = match self
+            .choose_files("Export patches to", gtk::FileChooserAction::SelectFolder)
+            .pop()
+        {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        patch_util::export_patches(&self.repository_manager, &oids, &output_dir)?;
+
+        Ok(())
+    }
+
+    fn apply_button_clicked(&self) -> Result<(), MetalGitError> {
+        let mut paths = self.choose_files("Apply patches", gtk::FileChooserAction::Open);
+        if paths.is_empty() {
+            return Ok(());
+        }
+        // numbered files like 0001-*.patch are applied in order
+        paths.sort();
+
+        let patches = patch_util::read_mbox_files(&paths)?;
+        if patches.is_empty() {
+            return Err(MetalGitError::new("No patch is found in the files"));
+        }
+
+        let orig_head = patch_util::head_oid(&self.repository_manager)?;
+        let result = patch_util::apply_patches(&self.repository_manager, &patches);
+        self.event_bus.publish(RepositoryEvent::HeadMoved);
+
+        match (result, orig_head) {
+            (Err(e), Some(orig_head)) => {
+                // applied commits are kept unless the user aborts
+                let message = format!("{}\n\nAbort and move HEAD back to the original commit? The applied commits are dropped.", e);
+                if crate::gtk_utils::message_box_question(&message) {
+                    patch_util::abort_apply(&self.repository_manager, orig_head)?;
+                    self.event_bus.publish(RepositoryEvent::HeadMoved);
+                }
+                Ok(())
+            }
+            (result, _) => result.map(|_| ()),
+        }
+    }
+
     fn commit_selected(&self, station: &railway::RailwayStation) -> Result<(), git2::Error> {
         let repo = self.repository_manager.open()?;
         let commit = repo.find_commit(station.oid)?;
@@ -257,4 +848,31 @@ Date: {}
 
         Ok(())
     }
+
+    fn work_tree_selected(&self) {
+        if let Some(buffer) = self.commit_textview.buffer() {
+            buffer.set_text("Uncommitted changes\n\nDouble-click to open the commit window.");
+        }
+    }
+
+    fn commits_compared(
+        &self,
+        old_station: &railway::RailwayStation,
+        new_station: &railway::RailwayStation,
+    ) {
+        let text = format!("compare {}..{}
+
+old: {} {}
+new: {} {}",
+                           old_station.oid,
+                           new_station.oid,
+                           old_station.oid,
+                           old_station.subject,
+                           new_station.oid,
+                           new_station.subject);
+
+        if let Some(buffer) = self.commit_textview.buffer() {
+            buffer.set_text(&text);
+        }
+    }
 }
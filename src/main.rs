@@ -7,6 +7,8 @@ pub mod repository_manager;
 mod static_resource;
 #[macro_use]
 mod gtk_utils;
+mod error_bar;
+pub mod error;
 
 mod station_renderer;
 mod station_cell_renderer;
@@ -15,26 +17,46 @@ mod station_wrapper;
 mod commit_window;
 mod history_window;
 mod window_manager;
+mod session;
+pub mod event_bus;
+mod repository_watcher;
+pub mod watch_util;
 mod commit_diff_panel;
-mod commit_diff_util;
+pub mod commit_diff_util;
 mod diff_text_view_util;
+pub mod diff_content;
+mod diff_view;
+mod diff_options_bar;
+pub mod syntax_highlight;
+mod blame_window;
+pub mod blame_util;
+mod history_search_bar;
+pub mod history_filter;
+pub mod pickaxe;
+pub mod patch_util;
+mod stash_window;
+pub mod stash_util;
+mod remote_bar;
+mod remote_window;
+pub mod remote_util;
+mod start_window;
+pub mod recent_repositories;
 
 mod repository_ext;
 
 pub mod railway;
 
-use std::rc::Rc;
 
 #[allow(dead_code)]
 fn main() {
     gtk::init().unwrap();
     static_resource::init();
 
-    let repository_manager = repository_manager::RepositoryManager::new();
-    repository_manager.set_work_dir_path(".");
+    // metal-git [PATH...]
+    let paths = std::env::args().skip(1).collect::<Vec<_>>();
 
-    let window_manager = Rc::new(window_manager::WindowManager::new(repository_manager));
-    window_manager.start();
+    let window_manager = window_manager::WindowManager::new();
+    window_manager.start(&paths);
 
     // railway::collect_tree(".").unwrap();
 
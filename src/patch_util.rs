@@ -0,0 +1,413 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::DateTime;
+use git2::build::CheckoutBuilder;
+use git2::{ApplyLocation, Commit, Diff, Email, EmailCreateOptions, Error, Oid, Repository, Signature, StatusOptions, Time};
+
+use crate::error::{MetalGitError, ResultExt};
+use crate::repository_manager::RepositoryManager;
+
+const MAX_FILE_NAME_SUBJECT_LENGTH: usize = 52;
+
+pub struct MailPatch {
+    pub author_name: String,
+    pub author_email: String,
+    pub time: Time,
+    pub subject: String,
+    pub message: String,
+    pub diff: Vec<u8>,
+}
+
+// writes `git format-patch` compatible files; oids are ordered from the oldest
+pub fn export_patches(repository_manager: &RepositoryManager,
+                      oids: &[Oid],
+                      output_dir: &Path)
+                      -> Result<Vec<PathBuf>, MetalGitError> {
+    let repo = repository_manager.open()?;
+
+    let mut commits = Vec::new();
+    for oid in oids {
+        let commit = repo.find_commit(*oid).oid_context("Failed to find the commit", *oid)?;
+        // merge commits are skipped like format-patch
+        if commit.parent_count() <= 1 {
+            commits.push(commit);
+        }
+    }
+
+    let mut paths = Vec::new();
+    for (i, commit) in commits.iter().enumerate() {
+        let email = create_email(&repo, commit, i + 1, commits.len())
+            .oid_context("Failed to format the patch of", commit.id())?;
+
+        let path = output_dir.join(patch_file_name(i + 1, commit.summary().unwrap_or("")));
+        fs::write(&path, email.as_slice()).path_context("Failed to write", &path)?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+fn create_email(repo: &Repository, commit: &Commit, number: usize, count: usize) -> Result<Email, Error> {
+    let parent_tree = if commit.parent_count() > 0 {
+        Some(commit.parent(0)?.tree()?)
+    } else {
+        None
+    };
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+
+    let mut opts = EmailCreateOptions::new();
+    Email::from_diff(&diff,
+                     number,
+                     count,
+                     &commit.id(),
+                     commit.summary().unwrap_or(""),
+                     commit.body().unwrap_or(""),
+                     &commit.author(),
+                     &mut opts)
+}
+
+// e.g. 0001-Fix-a-bug.patch
+fn patch_file_name(number: usize, summary: &str) -> String {
+    let mut name = String::new();
+    for c in summary.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            name.push(c);
+        } else if !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+
+    let mut name = name.trim_matches(|c| c == '-' || c == '.').to_string();
+    name.truncate(MAX_FILE_NAME_SUBJECT_LENGTH);
+
+    format!("{:04}-{}.patch", number, name.trim_end_matches('-'))
+}
+
+pub fn read_mbox_files(paths: &[PathBuf]) -> Result<Vec<MailPatch>, MetalGitError> {
+    let mut patches = Vec::new();
+    for path in paths {
+        let content = fs::read(path).path_context("Failed to read", path)?;
+        patches.extend(parse_mbox(&String::from_utf8_lossy(&content)).path_context("Failed to parse", path)?);
+    }
+
+    Ok(patches)
+}
+
+pub fn parse_mbox(content: &str) -> Result<Vec<MailPatch>, Error> {
+    let mut messages: Vec<Vec<&str>> = Vec::new();
+    for line in content.split_inclusive('\n') {
+        if line.starts_with("From ") || messages.is_empty() {
+            messages.push(Vec::new());
+        }
+        messages.last_mut().unwrap().push(line);
+    }
+
+    messages.iter()
+            .filter(|lines| lines.iter().any(|x| x.starts_with("diff --git ")))
+            .map(|lines| parse_mail(lines))
+            .collect()
+}
+
+fn parse_mail(lines: &[&str]) -> Result<MailPatch, Error> {
+    let mut lines = lines.iter().skip_while(|x| x.starts_with("From "));
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in lines.by_ref() {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+
+        // folded header lines start with whitespace
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = headers.last_mut() {
+                last.1.push(' ');
+                last.1.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let header = |name: &str| headers.iter().find(|x| x.0 == name).map(|x| x.1.as_str());
+
+    let from = header("from").ok_or_else(|| Error::from_str("No From header in the patch"))?;
+    let from = decode_header(from);
+    let (author_name, author_email) = match from.rfind('<') {
+        Some(pos) => (from[..pos].trim().trim_matches('"').to_string(),
+                      from[pos + 1..].trim_end_matches('>').to_string()),
+        None => (from.to_string(), from.to_string()),
+    };
+
+    let date = header("date").ok_or_else(|| Error::from_str("No Date header in the patch"))?;
+    let date = DateTime::parse_from_rfc2822(date)
+        .map_err(|e| Error::from_str(&format!("Invalid Date header in the patch: {}", e)))?;
+    let time = Time::new(date.timestamp(), date.offset().local_minus_utc() / 60);
+
+    let subject = decode_header(header("subject").unwrap_or(""));
+    let subject = subject.as_str();
+    // strip "[PATCH n/m]"
+    let subject = match subject.strip_prefix('[').and_then(|x| x.split_once(']')) {
+        Some((_, rest)) => rest.trim(),
+        None => subject,
+    }.to_string();
+
+    let mut body = String::new();
+    let mut diff = String::new();
+    let mut in_diff = false;
+    let mut in_body = true;
+    // remaining old and new lines of the current hunk
+    let mut hunk_lines: (usize, usize) = (0, 0);
+    for line in lines {
+        if line.starts_with("diff --git ") {
+            in_diff = true;
+        }
+
+        if in_diff {
+            if hunk_lines != (0, 0) {
+                match line.chars().next() {
+                    Some(' ') => hunk_lines = (hunk_lines.0.saturating_sub(1), hunk_lines.1.saturating_sub(1)),
+                    Some('-') => hunk_lines.0 = hunk_lines.0.saturating_sub(1),
+                    Some('+') => hunk_lines.1 = hunk_lines.1.saturating_sub(1),
+                    _ => {}
+                }
+            } else if line.starts_with("@@ ") {
+                hunk_lines = parse_hunk_header(line);
+            } else if line.trim_end() == "--" {
+                // the signature of format-patch outside of hunks
+                break;
+            }
+            diff.push_str(line);
+        } else if line.trim_end() == "---" {
+            // the diffstat follows
+            in_body = false;
+        } else if in_body {
+            body.push_str(line);
+        }
+    }
+
+    let body = body.trim();
+    let message = if body.is_empty() {
+        format!("{}\n", subject)
+    } else {
+        format!("{}\n\n{}\n", subject, body)
+    };
+
+    Ok(MailPatch {
+        author_name,
+        author_email,
+        time,
+        subject,
+        message,
+        diff: diff.into_bytes(),
+    })
+}
+
+// decodes RFC 2047 encoded words like "=?UTF-8?q?Ren=C3=A9?=" written by format-patch
+fn decode_header(value: &str) -> String {
+    let mut decoded = String::new();
+    // charset and bytes of adjacent encoded words, as a character may be split between them
+    let mut pending: Option<(String, Vec<u8>)> = None;
+    let mut rest = value;
+    while let Some(start) = rest.find("=?") {
+        let (text, word) = rest.split_at(start);
+        match parse_encoded_word(word) {
+            Some((charset, bytes, len)) => {
+                // whitespace between encoded words is dropped
+                let adjacent = pending.is_some() && text.trim().is_empty();
+                match pending {
+                    Some((ref pending_charset, ref mut pending_bytes))
+                        if adjacent && pending_charset.eq_ignore_ascii_case(&charset) => pending_bytes.extend(bytes),
+                    _ => {
+                        push_encoded_words(&mut decoded, pending.take());
+                        if !adjacent {
+                            decoded.push_str(text);
+                        }
+                        pending = Some((charset, bytes));
+                    }
+                }
+                rest = &word[len..];
+            }
+            None => {
+                push_encoded_words(&mut decoded, pending.take());
+                decoded.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    push_encoded_words(&mut decoded, pending);
+    decoded.push_str(rest);
+
+    decoded
+}
+
+fn push_encoded_words(decoded: &mut String, words: Option<(String, Vec<u8>)>) {
+    match words {
+        Some((charset, bytes)) if charset.eq_ignore_ascii_case("iso-8859-1") => {
+            decoded.extend(bytes.iter().map(|&x| x as char));
+        }
+        Some((_, bytes)) => decoded.push_str(&String::from_utf8_lossy(&bytes)),
+        None => {}
+    }
+}
+
+// returns the charset, the decoded bytes and the length of "=?charset?encoding?text?="
+fn parse_encoded_word(word: &str) -> Option<(String, Vec<u8>, usize)> {
+    let mut parts = word.strip_prefix("=?")?.splitn(3, '?');
+    let charset = parts.next()?;
+    let encoding = parts.next()?;
+    let text = parts.next()?;
+    let end = text.find("?=")?;
+    let text = &text[..end];
+    if charset.is_empty() || charset.contains(char::is_whitespace) || text.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let bytes = match encoding {
+        "B" | "b" => decode_base64(text)?,
+        "Q" | "q" => decode_q(text)?,
+        _ => return None,
+    };
+    let len = charset.len() + encoding.len() + end + 6;
+
+    // strip the RFC 2231 language like "UTF-8*en"
+    let charset = charset.split('*').next().unwrap_or(charset);
+    Some((charset.to_string(), bytes, len))
+}
+
+fn decode_q(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut chars = text.bytes();
+    while let Some(c) = chars.next() {
+        match c {
+            b'_' => bytes.push(b' '),
+            b'=' => {
+                let hex = [chars.next()?, chars.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            c => bytes.push(c),
+        }
+    }
+
+    Some(bytes)
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.bytes().take_while(|&c| c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Some(bytes)
+}
+
+// returns old and new line counts of "@@ -a,b +c,d @@"
+fn parse_hunk_header(line: &str) -> (usize, usize) {
+    let count = |range: Option<&str>| {
+        range.map(|x| match x.split_once(',') {
+                      Some((_, count)) => count.parse().unwrap_or(0),
+                      None => 1,
+                  })
+             .unwrap_or(0)
+    };
+
+    let mut ranges = line.split_whitespace().skip(1);
+    let old_count = count(ranges.next().and_then(|x| x.strip_prefix('-')));
+    let new_count = count(ranges.next().and_then(|x| x.strip_prefix('+')));
+
+    (old_count, new_count)
+}
+
+pub fn head_oid(repository_manager: &RepositoryManager) -> Result<Option<Oid>, MetalGitError> {
+    let repo = repository_manager.open()?;
+
+    head_target(&repo).context("Failed to read HEAD")
+}
+
+fn head_target(repo: &Repository) -> Result<Option<Oid>, Error> {
+    let head = match repo.head() {
+        Ok(head) => head,
+        Err(ref e) if e.code() == git2::ErrorCode::UnbornBranch => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    Ok(head.target())
+}
+
+// like `git am`: creates a commit for each patch keeping the author and the date
+pub fn apply_patches(repository_manager: &RepositoryManager,
+                     patches: &[MailPatch])
+                     -> Result<Vec<Oid>, MetalGitError> {
+    let repo = repository_manager.open()?;
+    // the commits are made from the index
+    ensure_clean(&repo).context("Failed to apply patches")?;
+
+    let mut oids = Vec::new();
+    for (i, patch) in patches.iter().enumerate() {
+        let oid = apply_patch(&repo, patch)
+            .context(&format!("Failed to apply patch {}/{} \"{}\"", i + 1, patches.len(), patch.subject))?;
+        oids.push(oid);
+    }
+
+    Ok(oids)
+}
+
+fn ensure_clean(repo: &Repository) -> Result<(), Error> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false);
+
+    if !repo.statuses(Some(&mut opts))?.is_empty() {
+        return Err(Error::from_str("The work tree has uncommitted changes; commit or stash them first"));
+    }
+
+    Ok(())
+}
+
+fn apply_patch(repo: &Repository, patch: &MailPatch) -> Result<Oid, Error> {
+    let diff = Diff::from_buffer(&patch.diff)?;
+    repo.apply(&diff, ApplyLocation::Both, None)?;
+
+    let mut index = repo.index()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+
+    let author = Signature::new(&patch.author_name, &patch.author_email, &patch.time)?;
+    let committer = repo.signature()?;
+
+    let parent = match head_target(repo)? {
+        Some(oid) => Some(repo.find_commit(oid)?),
+        None => None,
+    };
+    let parents = parent.iter().collect::<Vec<_>>();
+
+    repo.commit(Some("HEAD"), &author, &committer, &patch.message, &tree, &parents)
+}
+
+// moves HEAD back to the commit before applying patches; a failed patch leaves nothing behind,
+// and the safe checkout keeps changes made since
+pub fn abort_apply(repository_manager: &RepositoryManager, orig_head: Oid) -> Result<(), MetalGitError> {
+    let repo = repository_manager.open()?;
+
+    restore_head(&repo, orig_head).oid_context("Failed to restore HEAD to", orig_head)
+}
+
+fn restore_head(repo: &Repository, orig_head: Oid) -> Result<(), Error> {
+    let object = repo.find_object(orig_head, None)?;
+    repo.checkout_tree(&object, Some(CheckoutBuilder::new().safe()))?;
+    repo.reset(&object, git2::ResetType::Soft, None)
+}
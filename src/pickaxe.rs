@@ -0,0 +1,373 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use git2::{Commit, Error, Oid, Repository};
+
+// like `git log -S` / `git log -G`
+#[derive(Clone, PartialEq, Debug)]
+pub enum PickaxeQuery {
+    // commits changing the number of occurrences of the string
+    Occurrences(String),
+    // commits whose added or removed lines match the pattern
+    ChangedLines(String),
+}
+
+pub enum PickaxeEvent {
+    Progress(usize),
+    Matched(Oid),
+    Finished,
+    Failed(String),
+}
+
+#[derive(Clone)]
+enum Matcher {
+    Substring(Vec<char>),
+    Pattern(Pattern),
+}
+
+// a query compiled once for highlighting every line of a diff
+#[derive(Clone)]
+pub struct PickaxeMatcher(Matcher);
+
+impl PickaxeMatcher {
+    pub fn find_in_line(&self, line: &str) -> Vec<(usize, usize)> {
+        self.0.find_all(line)
+    }
+}
+
+impl Matcher {
+    fn new(query: &PickaxeQuery) -> Result<Matcher, Error> {
+        match query {
+            PickaxeQuery::Occurrences(s) => Ok(Matcher::Substring(s.chars().collect())),
+            PickaxeQuery::ChangedLines(s) => Pattern::parse(s).map(Matcher::Pattern),
+        }
+    }
+
+    // returns non-overlapping matches as character offsets
+    fn find_all(&self, text: &str) -> Vec<(usize, usize)> {
+        let chars = text.chars().collect::<Vec<_>>();
+        let mut matches = Vec::new();
+        let mut pos = 0;
+
+        while pos <= chars.len() {
+            let found = match self {
+                Matcher::Substring(needle) => {
+                    if needle.is_empty() {
+                        return matches;
+                    }
+                    chars[pos..].windows(needle.len())
+                                .position(|x| x == needle.as_slice())
+                                .map(|x| (pos + x, pos + x + needle.len()))
+                }
+                Matcher::Pattern(pattern) => pattern.find_at(&chars, pos),
+            };
+
+            match found {
+                Some((start, end)) => {
+                    matches.push((start, end));
+                    // an empty match must not stop the search
+                    pos = if end > start { end } else { end + 1 };
+                }
+                None => break,
+            }
+        }
+
+        matches
+    }
+}
+
+impl PickaxeQuery {
+    pub fn matcher(&self) -> Result<PickaxeMatcher, Error> {
+        Matcher::new(self).map(PickaxeMatcher)
+    }
+
+    pub fn find_in_line(&self, line: &str) -> Vec<(usize, usize)> {
+        match self.matcher() {
+            Ok(matcher) => matcher.find_in_line(line),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        Matcher::new(self).map(|_| ())
+    }
+}
+
+pub fn search<F>(work_dir_path: &str,
+                 query: &PickaxeQuery,
+                 cancelled: &AtomicBool,
+                 mut callback: F)
+                 -> Result<(), Error>
+    where F: FnMut(PickaxeEvent)
+{
+    let repo = Repository::discover(work_dir_path)?;
+    let matcher = Matcher::new(query)?;
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(git2::Sort::TIME)?;
+    revwalk.push_head()?;
+
+    for (i, oid) in revwalk.enumerate() {
+        if cancelled.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let commit = repo.find_commit(oid?)?;
+        if commit_matches(&repo, &commit, &matcher)? {
+            callback(PickaxeEvent::Matched(commit.id()));
+        }
+
+        if i % 100 == 0 {
+            callback(PickaxeEvent::Progress(i));
+        }
+    }
+
+    callback(PickaxeEvent::Finished);
+
+    Ok(())
+}
+
+pub fn search_commit(repo: &Repository, oid: Oid, query: &PickaxeQuery) -> Result<bool, Error> {
+    let matcher = Matcher::new(query)?;
+    let commit = repo.find_commit(oid)?;
+
+    commit_matches(repo, &commit, &matcher)
+}
+
+fn commit_matches(repo: &Repository, commit: &Commit, matcher: &Matcher) -> Result<bool, Error> {
+    let parent_tree = if commit.parent_count() > 0 {
+        Some(commit.parent(0)?.tree()?)
+    } else {
+        None
+    };
+    let tree = commit.tree()?;
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+
+    match matcher {
+        Matcher::Substring(_) => {
+            for delta in diff.deltas() {
+                let count = |oid: Oid| -> Result<usize, Error> {
+                    if oid.is_zero() {
+                        return Ok(0);
+                    }
+                    let blob = repo.find_blob(oid)?;
+                    Ok(matcher.find_all(&String::from_utf8_lossy(blob.content())).len())
+                };
+
+                if count(delta.old_file().id())? != count(delta.new_file().id())? {
+                    return Ok(true);
+                }
+            }
+
+            Ok(false)
+        }
+        Matcher::Pattern(_) => {
+            let mut matched = false;
+            let result = diff.foreach(&mut |_, _| true,
+                                      None,
+                                      None,
+                                      Some(&mut |_, _, line| {
+                                          if line.origin() == '+' || line.origin() == '-' {
+                                              let content = String::from_utf8_lossy(line.content());
+                                              matched = !matcher.find_all(&content).is_empty();
+                                          }
+                                          // stop iterating when found
+                                          !matched
+                                      }));
+
+            match result {
+                Err(ref e) if matched && e.code() == git2::ErrorCode::User => Ok(true),
+                Err(e) => Err(e),
+                Ok(()) => Ok(matched),
+            }
+        }
+    }
+}
+
+// a small subset of the extended regular expression: . [] ^ $ * + ? and \d \w \s escapes
+#[derive(Clone)]
+struct Pattern {
+    pieces: Vec<Piece>,
+    anchored_start: bool,
+    anchored_end: bool,
+}
+
+#[derive(Clone)]
+struct Piece {
+    atom: Atom,
+    min: usize,
+    max: usize,
+}
+
+#[derive(Clone)]
+enum Atom {
+    Any,
+    Char(char),
+    Class(Vec<(char, char)>, bool),
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Atom::Any => true,
+            Atom::Char(x) => *x == c,
+            Atom::Class(ranges, negated) => {
+                ranges.iter().any(|&(from, to)| from <= c && c <= to) != *negated
+            }
+        }
+    }
+}
+
+fn escape_class(c: char) -> Option<Atom> {
+    match c {
+        'd' => Some(Atom::Class(vec![('0', '9')], false)),
+        'w' => Some(Atom::Class(vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')], false)),
+        's' => Some(Atom::Class(vec![(' ', ' '), ('\t', '\t'), ('\r', '\r'), ('\n', '\n')], false)),
+        _ => None,
+    }
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Result<Pattern, Error> {
+        let mut chars = pattern.chars().peekable();
+        let mut pieces: Vec<Piece> = Vec::new();
+        let mut anchored_start = false;
+        let mut anchored_end = false;
+
+        if chars.peek() == Some(&'^') {
+            chars.next();
+            anchored_start = true;
+        }
+
+        while let Some(c) = chars.next() {
+            let atom = match c {
+                '.' => Atom::Any,
+                '$' if chars.peek().is_none() => {
+                    anchored_end = true;
+                    break;
+                }
+                '\\' => {
+                    let escaped = chars.next().ok_or_else(|| Error::from_str("Trailing backslash in the pattern"))?;
+                    escape_class(escaped).unwrap_or(Atom::Char(escaped))
+                }
+                '[' => {
+                    let mut ranges = Vec::new();
+                    let negated = chars.peek() == Some(&'^');
+                    if negated {
+                        chars.next();
+                    }
+
+                    let mut first = true;
+                    loop {
+                        let c = chars.next().ok_or_else(|| Error::from_str("Unterminated [] in the pattern"))?;
+                        if c == ']' && !first {
+                            break;
+                        }
+                        first = false;
+
+                        let from = if c == '\\' {
+                            chars.next().ok_or_else(|| Error::from_str("Trailing backslash in the pattern"))?
+                        } else {
+                            c
+                        };
+
+                        if chars.peek() == Some(&'-') {
+                            chars.next();
+                            match chars.next() {
+                                Some(']') => {
+                                    ranges.push((from, from));
+                                    ranges.push(('-', '-'));
+                                    break;
+                                }
+                                Some(to) => ranges.push((from, to)),
+                                None => return Err(Error::from_str("Unterminated [] in the pattern")),
+                            }
+                        } else {
+                            ranges.push((from, from));
+                        }
+                    }
+
+                    Atom::Class(ranges, negated)
+                }
+                '*' | '+' | '?' => return Err(Error::from_str("Nothing to repeat in the pattern")),
+                // alternation, groups and intervals are not implemented; don't take them as literals
+                '|' | '(' | ')' | '{' | '}' => {
+                    return Err(Error::from_str(&format!("Unsupported pattern syntax '{}'; escape it to match it literally", c)));
+                }
+                c => Atom::Char(c),
+            };
+
+            let (min, max) = match chars.peek() {
+                Some('*') => (0, usize::MAX),
+                Some('+') => (1, usize::MAX),
+                Some('?') => (0, 1),
+                _ => (1, 1),
+            };
+            if (min, max) != (1, 1) {
+                chars.next();
+            }
+
+            pieces.push(Piece { atom, min, max });
+        }
+
+        Ok(Pattern { pieces, anchored_start, anchored_end })
+    }
+
+    fn find_at(&self, chars: &[char], from: usize) -> Option<(usize, usize)> {
+        // a piece failing at a position fails from every start, so that lines like "aaaa...b"
+        // against "a*a*a*c" are not backtracked exponentially
+        let mut failed = vec![false; (self.pieces.len() + 1) * (chars.len() + 1)];
+
+        if self.anchored_start {
+            return if from == 0 {
+                self.match_here(chars, 0, 0, &mut failed).map(|end| (0, end))
+            } else {
+                None
+            };
+        }
+
+        (from..=chars.len()).find_map(|start| self.match_here(chars, 0, start, &mut failed).map(|end| (start, end)))
+    }
+
+    // backtracking matcher, greedy on repetitions
+    fn match_here(&self, chars: &[char], piece_index: usize, pos: usize, failed: &mut [bool]) -> Option<usize> {
+        let state = piece_index * (chars.len() + 1) + pos;
+        if failed[state] {
+            return None;
+        }
+
+        let end = self.match_pieces(chars, piece_index, pos, failed);
+        if end.is_none() {
+            failed[state] = true;
+        }
+
+        end
+    }
+
+    fn match_pieces(&self, chars: &[char], piece_index: usize, pos: usize, failed: &mut [bool]) -> Option<usize> {
+        let piece = match self.pieces.get(piece_index) {
+            Some(piece) => piece,
+            None => {
+                // a line may end with a newline
+                let at_end = pos == chars.len() || (pos + 1 == chars.len() && chars[pos] == '\n');
+                return if !self.anchored_end || at_end { Some(pos) } else { None };
+            }
+        };
+
+        let mut count = 0;
+        while count < piece.max && pos + count < chars.len() && piece.atom.matches(chars[pos + count]) {
+            count += 1;
+        }
+
+        while count >= piece.min {
+            if let Some(end) = self.match_here(chars, piece_index + 1, pos + count, failed) {
+                return Some(end);
+            }
+            if count == 0 {
+                break;
+            }
+            count -= 1;
+        }
+
+        None
+    }
+}
@@ -6,6 +6,7 @@ use chrono::*;
 
 use git2::{Error, Oid};
 use crate::repository_manager::RepositoryManager;
+use crate::stash_util;
 
 #[derive(Clone, PartialEq)]
 pub struct RailwayTrack {
@@ -17,18 +18,44 @@ pub struct RailwayTrack {
     pub to_lines: Vec<LineNumber>,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RefKind {
+    LocalBranch,
+    RemoteBranch,
+    Tag,
+    Stash,
+    Other,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RailwayRef {
+    pub name: String,
+    pub kind: RefKind,
+}
+
 #[derive(PartialEq)]
 pub struct RailwayStation {
     pub tracks: Vec<RailwayTrack>,
     pub oid: Oid,
     pub subject: String,
-    pub ref_names: Vec<String>,
+    pub message: String,
+    pub refs: Vec<RailwayRef>,
     pub author_name: String,
+    pub committer_name: String,
     pub time: String,
+    pub timestamp: i64,
+    // the pseudo station of uncommitted changes, whose oid is zero
+    pub is_work_tree: bool,
 
     active_track_index: usize,
 }
 
+#[derive(Default)]
+pub struct CollectOptions {
+    pub with_stashes: bool,
+    pub with_work_tree: bool,
+}
+
 impl RailwayTrack {
     fn new(line_number: LineNumber,
            track_number: TrackNumber,
@@ -74,7 +101,7 @@ impl RailwayTrack {
 
 impl RailwayStation {
     fn new(commit: &git2::Commit,
-           ref_names: Vec<String>,
+           refs: Vec<RailwayRef>,
            tracks: Vec<RailwayTrack>)
            -> RailwayStation {
         let active_track_index = tracks.iter().position(|x| x.is_active).unwrap();
@@ -91,9 +118,31 @@ impl RailwayStation {
             active_track_index: active_track_index,
             oid: commit.id(),
             subject: first_line.to_string(),
-            ref_names: ref_names,
+            message: message.to_string(),
+            refs: refs,
             author_name: commit.author().name().unwrap_or("").to_string(),
-            time: format!("{}", commit_time.format("%Y-%m-%d %H:%M:%S %Z"))
+            committer_name: commit.committer().name().unwrap_or("").to_string(),
+            time: format!("{}", commit_time.format("%Y-%m-%d %H:%M:%S %Z")),
+            timestamp: time.seconds(),
+            is_work_tree: false,
+        }
+    }
+
+    fn new_work_tree(tracks: Vec<RailwayTrack>) -> RailwayStation {
+        let active_track_index = tracks.iter().position(|x| x.is_active).unwrap();
+
+        RailwayStation {
+            tracks: tracks,
+            active_track_index: active_track_index,
+            oid: Oid::zero(),
+            subject: "Uncommitted changes".to_string(),
+            message: "Uncommitted changes".to_string(),
+            refs: Vec::new(),
+            author_name: "".to_string(),
+            committer_name: "".to_string(),
+            time: "".to_string(),
+            timestamp: Local::now().timestamp(),
+            is_work_tree: true,
         }
     }
 
@@ -219,23 +268,34 @@ impl TrackLineMap {
 }
 
 struct RefTable {
-    oid_table: HashMap<Oid, Vec<String>>,
+    oid_table: HashMap<Oid, Vec<RailwayRef>>,
 }
 
 impl RefTable {
     fn collect(repo: &git2::Repository) -> Result<RefTable, Error> {
-        let mut table = HashMap::<Oid, Vec<String>>::new();
+        let mut table = HashMap::<Oid, Vec<RailwayRef>>::new();
         let refs = repo.references()?;
         for r in refs {
             let r = r?;
             if let Some(oid) = r.target() {
                 if let Some(shorthand) = r.shorthand() {
+                    let kind = if r.is_branch() {
+                        RefKind::LocalBranch
+                    } else if r.is_remote() {
+                        RefKind::RemoteBranch
+                    } else if r.is_tag() {
+                        RefKind::Tag
+                    } else {
+                        RefKind::Other
+                    };
+                    let railway_ref = RailwayRef { name: shorthand.to_owned(), kind: kind };
+
                     match table.entry(oid) {
                         Occupied(mut entry) => {
-                            entry.get_mut().push(shorthand.to_owned());
+                            entry.get_mut().push(railway_ref);
                         }
                         Vacant(entry) => {
-                            entry.insert(vec![shorthand.to_owned()]);
+                            entry.insert(vec![railway_ref]);
                         }
                     }
                 }
@@ -245,7 +305,7 @@ impl RefTable {
         Ok(RefTable { oid_table: table })
     }
 
-    fn get_names_for_oid(&self, oid: &Oid) -> Vec<String> {
+    fn get_refs_for_oid(&self, oid: &Oid) -> Vec<RailwayRef> {
         self.oid_table
             .get(oid)
             .map(|x| x.clone())
@@ -254,20 +314,59 @@ impl RefTable {
 }
 
 pub fn collect_tree(repository_manager: &RepositoryManager) -> Result<Vec<RailwayStation>, Error> {
+    collect_tree_with_options(repository_manager, &CollectOptions::default())
+}
+
+// stashes are drawn as side stations branching from the commits they were made on,
+// uncommitted changes as a station above HEAD
+pub fn collect_tree_with_options(repository_manager: &RepositoryManager,
+                                 options: &CollectOptions)
+                                 -> Result<Vec<RailwayStation>, Error> {
     let repo = repository_manager.open()?;
 
-    let ref_table = RefTable::collect(&repo)?;
+    let stashes = if options.with_stashes {
+        stash_util::collect_stashes(&mut repository_manager.open_mut()?)?
+    } else {
+        Vec::new()
+    };
+
+    let mut ref_table = RefTable::collect(&repo)?;
 
     let mut revwalk = repo.revwalk()?;
 
     revwalk.set_sorting(git2::Sort::TIME)?;
     revwalk.push_head()?;
 
+    // the index and the untracked files commits of stashes are not shown
+    let mut stash_oids = HashSet::new();
+    let mut hidden_oids = HashSet::new();
+    for stash in &stashes {
+        revwalk.push(stash.oid)?;
+        stash_oids.insert(stash.oid);
+        hidden_oids.extend(repo.find_commit(stash.oid)?.parent_ids().skip(1));
+
+        // "stash" of refs/stash is replaced with "stash@{n}"
+        let refs = ref_table.oid_table.entry(stash.oid).or_insert_with(Vec::new);
+        refs.retain(|x| x.name != "stash");
+        refs.push(RailwayRef { name: stash.name(), kind: RefKind::Stash });
+    }
+
+    let head_oid = repo.head()?.target();
+    let work_tree_oid = if options.with_work_tree && has_work_tree_station(&repo)? {
+        Some(Ok(Oid::zero()))
+    } else {
+        None
+    };
+
     let mut track_line_map = TrackLineMap::new();
 
     let mut stations = Vec::<RailwayStation>::new();
-    for oid in revwalk {
+    for oid in work_tree_oid.into_iter().chain(revwalk) {
         let oid = oid?;
+        if hidden_oids.contains(&oid) {
+            continue;
+        }
+
         let mut prev_to_map = HashMap::new();
         if let Some(last_station) = stations.last() {
             track_line_map.vacuum_unused_track_numbers(last_station.tracks
@@ -289,12 +388,19 @@ pub fn collect_tree(repository_manager: &RepositoryManager) -> Result<Vec<Railwa
         }
 
 
-        let commit = repo.find_commit(oid)?;
+        let commit = if oid.is_zero() { None } else { Some(repo.find_commit(oid)?) };
+        let parent_ids = match commit {
+            // a stash station is connected only to its base commit
+            Some(ref commit) if stash_oids.contains(&oid) => commit.parent_ids().take(1).collect(),
+            Some(ref commit) => commit.parent_ids().collect(),
+            None => head_oid.into_iter().collect::<Vec<_>>(),
+        };
+
         let active_line_number = track_line_map.take_line_number_or_aquire(&oid);
 
         let mut is_first_non_merge = true;
         let mut active_to_line_numbers = Vec::<LineNumber>::new();
-        for parent_id in commit.parent_ids() {
+        for parent_id in parent_ids {
             let parent_line_number = if track_line_map.is_oid_assigned(&parent_id) {
                 track_line_map.take_line_number_or_aquire(&parent_id)
             } else if is_first_non_merge {
@@ -336,8 +442,10 @@ pub fn collect_tree(repository_manager: &RepositoryManager) -> Result<Vec<Railwa
                                    })
                                    .collect::<Vec<_>>();
 
-        let ref_names = ref_table.get_names_for_oid(&oid);
-        stations.push(RailwayStation::new(&commit, ref_names, tracks));
+        stations.push(match commit {
+            Some(ref commit) => RailwayStation::new(commit, ref_table.get_refs_for_oid(&oid), tracks),
+            None => RailwayStation::new_work_tree(tracks),
+        });
     }
 
     // 	for station in stations.iter() {
@@ -345,3 +453,15 @@ pub fn collect_tree(repository_manager: &RepositoryManager) -> Result<Vec<Railwa
     // 	}
     Ok(stations)
 }
+
+// uncommitted changes are shown above HEAD unless HEAD is unborn
+pub fn has_work_tree_station(repo: &git2::Repository) -> Result<bool, Error> {
+    Ok(repo.head()?.target().is_some() && !repo.is_bare() && has_uncommitted_changes(repo)?)
+}
+
+fn has_uncommitted_changes(repo: &git2::Repository) -> Result<bool, Error> {
+    let mut opts = git2::StatusOptions::new();
+    opts.include_untracked(true);
+
+    Ok(!repo.statuses(Some(&mut opts))?.is_empty())
+}
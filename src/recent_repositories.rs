@@ -0,0 +1,70 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use git2::Error;
+
+pub const MAX_RECENT_REPOSITORIES: usize = 10;
+
+// most-recently-used work directories, one path per line
+pub struct RecentRepositories {
+    file_path: PathBuf,
+}
+
+impl RecentRepositories {
+    pub fn new(file_path: &Path) -> RecentRepositories {
+        RecentRepositories { file_path: file_path.to_path_buf() }
+    }
+
+    // $XDG_CONFIG_HOME/metal-git/recent_repositories, or under ~/.config
+    pub fn default_file_path() -> Option<PathBuf> {
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .filter(|x| !x.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|x| PathBuf::from(x).join(".config")))?;
+
+        Some(config_dir.join("metal-git").join("recent_repositories"))
+    }
+
+    // newer first; a missing file means an empty list
+    pub fn list(&self) -> Vec<String> {
+        fs::read_to_string(&self.file_path)
+            .map(|text| {
+                text.lines()
+                    .filter(|x| !x.is_empty())
+                    .map(|x| x.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn add(&self, path: &str) -> Result<(), Error> {
+        let mut paths = self.list();
+        paths.retain(|x| x != path);
+        paths.insert(0, path.to_string());
+        paths.truncate(MAX_RECENT_REPOSITORIES);
+
+        self.save(&paths)
+    }
+
+    pub fn remove(&self, path: &str) -> Result<(), Error> {
+        let mut paths = self.list();
+        paths.retain(|x| x != path);
+
+        self.save(&paths)
+    }
+
+    fn save(&self, paths: &[String]) -> Result<(), Error> {
+        let failed = |e: std::io::Error| {
+            Error::from_str(&format!("Failed to write {}: {}", self.file_path.to_string_lossy(), e))
+        };
+
+        if let Some(dir) = self.file_path.parent() {
+            fs::create_dir_all(dir).map_err(failed)?;
+        }
+
+        let mut text = paths.join("\n");
+        text.push('\n');
+        fs::write(&self.file_path, text).map_err(failed)
+    }
+}
@@ -0,0 +1,250 @@
+use glib::Cast;
+use gtk::prelude::ComboBoxExtManual;
+use gtk::traits::{BoxExt, ButtonExt, ComboBoxTextExt, StatusbarExt, ToggleButtonExt, WidgetExt};
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::thread;
+
+use crate::error::MetalGitError;
+use crate::event_bus::{EventBus, RepositoryEvent};
+use crate::remote_util;
+use crate::remote_util::{PullMode, PushOptions, RemoteEvent};
+use crate::remote_window::RemoteWindow;
+use crate::repository_manager::RepositoryManager;
+
+pub struct RemoteBar {
+    container: gtk::Box,
+
+    remote_combo_box: gtk::ComboBoxText,
+    fetch_button: gtk::Button,
+    pull_button: gtk::Button,
+    pull_mode_combo_box: gtk::ComboBoxText,
+    push_button: gtk::Button,
+    force_with_lease_checkbutton: gtk::CheckButton,
+    tags_checkbutton: gtk::CheckButton,
+    remotes_button: gtk::Button,
+
+    remote_window: Rc<RemoteWindow>,
+    statusbar: gtk::Statusbar,
+
+    repository_manager: Rc<RepositoryManager>,
+    running: Cell<bool>,
+
+    event_bus: Rc<EventBus>,
+}
+
+#[derive(Clone, Copy)]
+enum RemoteOperation {
+    Fetch,
+    Pull(PullMode),
+    Push(PushOptions),
+}
+
+// the order of items in pull_mode_combo_box
+const PULL_MODES: [(PullMode, &str); 2] = [(PullMode::Merge, "Merge"), (PullMode::Rebase, "Rebase")];
+
+impl RemoteBar {
+    pub fn new(repository_manager: Rc<RepositoryManager>,
+               statusbar: gtk::Statusbar,
+               event_bus: Rc<EventBus>) -> Rc<RemoteBar> {
+        let container = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+
+        let remote_combo_box = gtk::ComboBoxText::new();
+        container.pack_start(&remote_combo_box, false, true, 0);
+
+        let fetch_button = gtk::Button::with_label("Fetch");
+        container.pack_start(&fetch_button, false, true, 0);
+
+        let pull_button = gtk::Button::with_label("Pull");
+        container.pack_start(&pull_button, false, true, 0);
+        let pull_mode_combo_box = gtk::ComboBoxText::new();
+        for (_, label) in PULL_MODES.iter() {
+            pull_mode_combo_box.append_text(label);
+        }
+        pull_mode_combo_box.set_active(Some(0));
+        container.pack_start(&pull_mode_combo_box, false, true, 0);
+
+        let push_button = gtk::Button::with_label("Push");
+        container.pack_start(&push_button, false, true, 0);
+        let force_with_lease_checkbutton = gtk::CheckButton::with_label("force with lease");
+        container.pack_start(&force_with_lease_checkbutton, false, true, 0);
+        let tags_checkbutton = gtk::CheckButton::with_label("tags");
+        container.pack_start(&tags_checkbutton, false, true, 0);
+
+        let remotes_button = gtk::Button::with_label("Remotes...");
+        container.pack_end(&remotes_button, false, true, 0);
+
+        let remote_window = RemoteWindow::new(Rc::clone(&repository_manager));
+
+        let remote_bar = Rc::new(RemoteBar {
+            container,
+            remote_combo_box,
+            fetch_button,
+            pull_button,
+            pull_mode_combo_box,
+            push_button,
+            force_with_lease_checkbutton,
+            tags_checkbutton,
+            remotes_button,
+            remote_window,
+            statusbar,
+            repository_manager,
+            running: Cell::new(false),
+            event_bus,
+        });
+
+        let w = Rc::downgrade(&remote_bar);
+        remote_bar.fetch_button.connect_clicked(move |_| {
+            let w = w.upgrade().unwrap();
+            dialog_when_error!("Failed to fetch: {}", w.start(RemoteOperation::Fetch));
+        });
+
+        let w = Rc::downgrade(&remote_bar);
+        remote_bar.pull_button.connect_clicked(move |_| {
+            let w = w.upgrade().unwrap();
+            let mode = w.pull_mode_combo_box
+                        .active()
+                        .map(|x| PULL_MODES[x as usize].0)
+                        .unwrap_or(PullMode::Merge);
+            dialog_when_error!("Failed to pull: {}", w.start(RemoteOperation::Pull(mode)));
+        });
+
+        let w = Rc::downgrade(&remote_bar);
+        remote_bar.push_button.connect_clicked(move |_| {
+            let w = w.upgrade().unwrap();
+            let options = PushOptions {
+                force_with_lease: w.force_with_lease_checkbutton.is_active(),
+                tags: w.tags_checkbutton.is_active(),
+            };
+            dialog_when_error!("Failed to push: {}", w.start(RemoteOperation::Push(options)));
+        });
+
+        let w = Rc::downgrade(&remote_bar);
+        remote_bar.remotes_button.connect_clicked(move |_| {
+            w.upgrade().unwrap().remote_window.show();
+        });
+
+        let w = Rc::downgrade(&remote_bar);
+        remote_bar.remote_window.connect_changed(move || {
+            // renaming or removing a remote changes remote-tracking branches
+            w.upgrade().unwrap().event_bus.publish(RepositoryEvent::RefsChanged);
+        });
+
+        remote_bar
+    }
+
+    pub fn widget(&self) -> gtk::Widget {
+        self.container.clone().upcast::<gtk::Widget>()
+    }
+
+    pub fn close(&self) {
+        self.remote_window.close();
+    }
+
+    pub fn refresh(&self) {
+        dialog_when_error!("Failed to load remotes: {}", self.load_remotes());
+    }
+
+    fn load_remotes(&self) -> Result<(), MetalGitError> {
+        let selected = self.remote_combo_box.active_text();
+
+        let repo = self.repository_manager.open()?;
+        let names = remote_util::remote_names(&repo)?;
+
+        self.remote_combo_box.remove_all();
+        for name in &names {
+            self.remote_combo_box.append_text(name);
+        }
+
+        // keeps the selection, or prefers "origin"
+        let selected = selected.map(|x| x.to_string()).unwrap_or_else(|| "origin".to_string());
+        let index = names.iter().position(|x| *x == selected).unwrap_or(0);
+        if !names.is_empty() {
+            self.remote_combo_box.set_active(Some(index as u32));
+        }
+
+        self.set_buttons_sensitive(!names.is_empty() && !self.running.get());
+
+        Ok(())
+    }
+
+    fn set_buttons_sensitive(&self, sensitive: bool) {
+        self.fetch_button.set_sensitive(sensitive);
+        self.pull_button.set_sensitive(sensitive);
+        self.push_button.set_sensitive(sensitive);
+    }
+
+    fn set_status(&self, text: &str) {
+        let context_id = self.statusbar.context_id("remote");
+        self.statusbar.remove_all(context_id);
+        self.statusbar.push(context_id, text);
+    }
+
+    // runs on a background thread not to block the UI while transferring
+    fn start(self: &Rc<Self>, operation: RemoteOperation) -> Result<(), MetalGitError> {
+        let remote_name = match self.remote_combo_box.active_text() {
+            Some(name) => name.to_string(),
+            None => return Err(MetalGitError::new("No remote is selected")),
+        };
+
+        self.running.set(true);
+        self.set_buttons_sensitive(false);
+        self.set_status(&format!("Connecting to {}...", remote_name));
+
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let work_dir_path = self.repository_manager.work_dir_path();
+        thread::spawn(move || {
+            let progress_sender = sender.clone();
+            let progress = move |text| {
+                let _ = progress_sender.send(RemoteEvent::Progress(text));
+            };
+
+            let result = match operation {
+                RemoteOperation::Fetch => remote_util::fetch(&work_dir_path, &remote_name, progress),
+                RemoteOperation::Pull(mode) => {
+                    remote_util::pull(&work_dir_path, &remote_name, mode, progress)
+                }
+                RemoteOperation::Push(options) => {
+                    remote_util::push(&work_dir_path, &remote_name, &options, progress)
+                }
+            };
+
+            let _ = sender.send(match result {
+                Ok(text) => RemoteEvent::Finished(text),
+                Err(err) => RemoteEvent::Failed(err.to_string()),
+            });
+        });
+
+        let w = Rc::downgrade(self);
+        receiver.attach(None, move |event| {
+            let w = match w.upgrade() {
+                Some(w) => w,
+                None => return glib::Continue(false),
+            };
+
+            match event {
+                RemoteEvent::Progress(text) => {
+                    w.set_status(&text);
+                    return glib::Continue(true);
+                }
+                RemoteEvent::Finished(text) => w.set_status(&text),
+                RemoteEvent::Failed(message) => {
+                    w.set_status("");
+                    crate::gtk_utils::message_box_error(&message);
+                }
+            }
+
+            w.running.set(false);
+            w.set_buttons_sensitive(true);
+            w.event_bus.publish(match operation {
+                RemoteOperation::Pull(_) => RepositoryEvent::HeadMoved,
+                RemoteOperation::Fetch | RemoteOperation::Push(_) => RepositoryEvent::RefsChanged,
+            });
+
+            glib::Continue(false)
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,508 @@
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+
+use git2::build::CheckoutBuilder;
+use git2::{
+    AnnotatedCommit, AutotagOption, Cred, CredentialType, Direction, Error, FetchOptions,
+    Oid, Reference, RemoteCallbacks, Repository, StatusOptions,
+};
+
+use crate::error::{MetalGitError, ResultExt};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PullMode {
+    Merge,
+    Rebase,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct PushOptions {
+    // refuses to overwrite the remote branch unless it equals our remote-tracking branch
+    pub force_with_lease: bool,
+    pub tags: bool,
+}
+
+// sent from a background thread, like PickaxeEvent
+pub enum RemoteEvent {
+    Progress(String),
+    Finished(String),
+    Failed(String),
+}
+
+fn create_callbacks<'a, F>(progress: &'a RefCell<F>) -> RemoteCallbacks<'a>
+    where F: FnMut(String)
+{
+    let mut callbacks = RemoteCallbacks::new();
+
+    callbacks.credentials(|_url, username, allowed| {
+        if allowed.contains(CredentialType::SSH_KEY) {
+            Cred::ssh_key_from_agent(username.unwrap_or("git"))
+        } else if allowed.contains(CredentialType::DEFAULT) {
+            Cred::default()
+        } else {
+            Err(Error::from_str("No supported credentials"))
+        }
+    });
+
+    callbacks.transfer_progress(move |stats| {
+        let text = if stats.received_objects() < stats.total_objects() {
+            format!("Receiving objects: {}/{} ({} bytes)",
+                    stats.received_objects(),
+                    stats.total_objects(),
+                    stats.received_bytes())
+        } else {
+            format!("Resolving deltas: {}/{}", stats.indexed_deltas(), stats.total_deltas())
+        };
+        progress.borrow_mut()(text);
+        true
+    });
+
+    callbacks.sideband_progress(move |data| {
+        let text = String::from_utf8_lossy(data);
+        if let Some(line) = text.split(['\r', '\n']).rev().find(|x| !x.trim().is_empty()) {
+            progress.borrow_mut()(format!("remote: {}", line.trim()));
+        }
+        true
+    });
+
+    callbacks.push_transfer_progress(move |current, total, bytes| {
+        progress.borrow_mut()(format!("Writing objects: {}/{} ({} bytes)", current, total, bytes));
+    });
+
+    callbacks
+}
+
+pub fn remote_names(repo: &Repository) -> Result<Vec<String>, MetalGitError> {
+    let remotes = repo.remotes().context("Failed to list the remotes")?;
+
+    Ok(remotes.iter().flatten().map(|x| x.to_string()).collect())
+}
+
+// the operations on remotes run on their own thread with their own repository
+fn discover_repository(work_dir_path: &str) -> Result<Repository, MetalGitError> {
+    Repository::discover(work_dir_path).path_context("Failed to open the repository", Path::new(work_dir_path))
+}
+
+pub fn fetch<F>(work_dir_path: &str, remote_name: &str, progress: F) -> Result<String, MetalGitError>
+    where F: FnMut(String)
+{
+    let repo = discover_repository(work_dir_path)?;
+    let progress = RefCell::new(progress);
+
+    fetch_remote(&repo, remote_name, &progress).context(&format!("Failed to fetch {}", remote_name))?;
+
+    Ok(format!("Fetched {}", remote_name))
+}
+
+fn fetch_remote<F>(repo: &Repository, remote_name: &str, progress: &RefCell<F>) -> Result<(), Error>
+    where F: FnMut(String)
+{
+    let mut remote = repo.find_remote(remote_name)?;
+
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(create_callbacks(progress));
+    fetch_options.download_tags(AutotagOption::Auto);
+
+    // the configured refspecs are used
+    remote.fetch::<&str>(&[], Some(&mut fetch_options), None)
+}
+
+fn head_branch(repo: &Repository) -> Result<(Reference<'_>, String), Error> {
+    let head = repo.head()?;
+    if !head.is_branch() {
+        return Err(Error::from_str("HEAD is not on a branch"));
+    }
+
+    let name = head.shorthand().unwrap_or("").to_string();
+    Ok((head, name))
+}
+
+fn ensure_clean(repo: &Repository) -> Result<(), Error> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false);
+
+    if !repo.statuses(Some(&mut opts))?.is_empty() {
+        return Err(Error::from_str("The work tree has uncommitted changes; commit or stash them first"));
+    }
+
+    Ok(())
+}
+
+// the configured upstream, or the branch of the same name on the remote
+fn upstream_reference<'repo>(repo: &'repo Repository,
+                             remote_name: &str,
+                             branch_name: &str)
+                             -> Result<Reference<'repo>, Error> {
+    let branch = repo.find_branch(branch_name, git2::BranchType::Local)?;
+    if let Ok(upstream) = branch.upstream() {
+        return Ok(upstream.into_reference());
+    }
+
+    repo.find_reference(&format!("refs/remotes/{}/{}", remote_name, branch_name))
+        .map_err(|_| Error::from_str(&format!("No upstream branch for {} on {}", branch_name, remote_name)))
+}
+
+pub fn pull<F>(work_dir_path: &str, remote_name: &str, mode: PullMode, progress: F) -> Result<String, MetalGitError>
+    where F: FnMut(String)
+{
+    let repo = discover_repository(work_dir_path)?;
+    let progress = RefCell::new(progress);
+
+    fetch_remote(&repo, remote_name, &progress).context(&format!("Failed to fetch {}", remote_name))?;
+
+    integrate_upstream(&repo, remote_name, mode).context(&format!("Failed to pull from {}", remote_name))
+}
+
+// merges or rebases onto the fetched upstream branch
+fn integrate_upstream(repo: &Repository, remote_name: &str, mode: PullMode) -> Result<String, Error> {
+    let (head, branch_name) = head_branch(repo)?;
+    let upstream = upstream_reference(repo, remote_name, &branch_name)?;
+    let upstream_name = upstream.shorthand().unwrap_or("").to_string();
+    let upstream_commit = repo.reference_to_annotated_commit(&upstream)?;
+
+    let (analysis, _) = repo.merge_analysis(&[&upstream_commit])?;
+    if analysis.is_up_to_date() {
+        return Ok("Already up to date".to_string());
+    }
+    if analysis.is_fast_forward() {
+        fast_forward(repo, head, upstream_commit.id())?;
+        return Ok(format!("Fast-forwarded {} to {}", branch_name, upstream_name));
+    }
+
+    // a conflict is rolled back with a hard reset, which needs a clean work tree
+    ensure_clean(repo)?;
+
+    match mode {
+        PullMode::Merge => merge(repo, &head, &upstream_commit, &upstream_name)?,
+        PullMode::Rebase => rebase(repo, &upstream_commit)?,
+    }
+
+    Ok(match mode {
+        PullMode::Merge => format!("Merged {} into {}", upstream_name, branch_name),
+        PullMode::Rebase => format!("Rebased {} onto {}", branch_name, upstream_name),
+    })
+}
+
+fn fast_forward(repo: &Repository, mut head: Reference<'_>, target: Oid) -> Result<(), Error> {
+    let object = repo.find_object(target, None)?;
+
+    // the checkout fails without moving the branch if local changes conflict
+    repo.checkout_tree(&object, Some(CheckoutBuilder::new().safe()))?;
+    head.set_target(target, "pull: fast-forward")?;
+
+    Ok(())
+}
+
+fn merge(repo: &Repository,
+         head: &Reference<'_>,
+         upstream_commit: &AnnotatedCommit,
+         upstream_name: &str)
+         -> Result<(), Error> {
+    let head_commit = head.peel_to_commit()?;
+    let head_object = head_commit.as_object().clone();
+
+    repo.merge(&[upstream_commit], None, None)?;
+
+    let mut index = repo.index()?;
+    if index.has_conflicts() {
+        repo.cleanup_state()?;
+        repo.reset(&head_object, git2::ResetType::Hard, None)?;
+        return Err(Error::from_str(&format!("Merging {} has conflicts; the merge is aborted", upstream_name)));
+    }
+
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let signature = repo.signature()?;
+    let other_commit = repo.find_commit(upstream_commit.id())?;
+    let message = format!("Merge remote-tracking branch '{}'", upstream_name);
+
+    repo.commit(Some("HEAD"), &signature, &signature, &message, &tree, &[&head_commit, &other_commit])?;
+    repo.cleanup_state()?;
+
+    Ok(())
+}
+
+fn rebase(repo: &Repository, upstream_commit: &AnnotatedCommit) -> Result<(), Error> {
+    let signature = repo.signature()?;
+    let mut rebase = repo.rebase(None, Some(upstream_commit), None, None)?;
+
+    while let Some(operation) = rebase.next() {
+        operation?;
+
+        if repo.index()?.has_conflicts() {
+            rebase.abort()?;
+            return Err(Error::from_str("Rebasing has conflicts; the rebase is aborted"));
+        }
+
+        // the original author and message are kept
+        match rebase.commit(None, &signature, None) {
+            // the change already exists upstream
+            Err(ref e) if e.code() == git2::ErrorCode::Applied => {}
+            Err(e) => {
+                rebase.abort()?;
+                return Err(e);
+            }
+            Ok(_) => {}
+        }
+    }
+
+    rebase.finish(Some(&signature))
+}
+
+pub fn push<F>(work_dir_path: &str,
+               remote_name: &str,
+               options: &PushOptions,
+               progress: F)
+               -> Result<String, MetalGitError>
+    where F: FnMut(String)
+{
+    let repo = discover_repository(work_dir_path)?;
+    let progress = RefCell::new(progress);
+
+    push_head(&repo, remote_name, options, &progress).context(&format!("Failed to push to {}", remote_name))
+}
+
+fn push_head<F>(repo: &Repository, remote_name: &str, options: &PushOptions, progress: &RefCell<F>) -> Result<String, Error>
+    where F: FnMut(String)
+{
+    let (_, branch_name) = head_branch(repo)?;
+    let ref_name = format!("refs/heads/{}", branch_name);
+
+    let mut remote = repo.find_remote(remote_name)?;
+
+    let tracking_name = format!("refs/remotes/{}/{}", remote_name, branch_name);
+    let expected = repo.refname_to_id(&tracking_name).ok();
+
+    let mut refspecs = vec![format!("{}{}:{}",
+                                    if options.force_with_lease { "+" } else { "" },
+                                    ref_name,
+                                    ref_name)];
+    if options.tags {
+        for tag_name in repo.tag_names(None)?.iter().flatten() {
+            refspecs.push(format!("refs/tags/{}:refs/tags/{}", tag_name, tag_name));
+        }
+    }
+
+    let rejected = RefCell::new(Vec::new());
+    let lease_broken = Cell::new(false);
+    let pushed = {
+        let mut callbacks = create_callbacks(progress);
+        callbacks.push_update_reference(|refname, status| {
+            if let Some(status) = status {
+                rejected.borrow_mut().push(format!("{}: {}", refname, status));
+            }
+            Ok(())
+        });
+        if options.force_with_lease {
+            // the remote's branch is compared on the same connection that updates it
+            callbacks.push_negotiation(|updates| {
+                let actual = updates.iter()
+                                    .find(|x| x.dst_refname() == Some(ref_name.as_str()))
+                                    .map(|x| x.src())
+                                    .filter(|x| !x.is_zero());
+                if actual.is_some() && actual != expected {
+                    lease_broken.set(true);
+                    return Err(Error::from_str("The lease is broken"));
+                }
+                Ok(())
+            });
+        }
+
+        let mut push_options = git2::PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+        remote.push(&refspecs, Some(&mut push_options))
+    };
+
+    if lease_broken.get() {
+        return Err(Error::from_str(&format!("{} on {} was updated since the last fetch; fetch first",
+                                            branch_name,
+                                            remote_name)));
+    }
+    pushed?;
+
+    let rejected = rejected.into_inner();
+    if !rejected.is_empty() {
+        return Err(Error::from_str(&format!("Rejected by {}: {}", remote_name, rejected.join(", "))));
+    }
+
+    Ok(format!("Pushed {} to {}", branch_name, remote_name))
+}
+
+
+pub struct RemoteInfo {
+    pub name: String,
+    pub url: String,
+    pub push_url: Option<String>,
+    pub fetch_refspecs: Vec<String>,
+}
+
+pub fn list_remotes(repo: &Repository) -> Result<Vec<RemoteInfo>, MetalGitError> {
+    let mut remotes = Vec::new();
+    for name in remote_names(repo)? {
+        let remote = repo.find_remote(&name).context(&format!("Failed to read the remote {}", name))?;
+        let fetch_refspecs = remote.fetch_refspecs()
+                                   .context(&format!("Failed to read the refspecs of {}", name))?
+                                   .iter()
+                                   .flatten()
+                                   .map(|x| x.to_string())
+                                   .collect();
+
+        remotes.push(RemoteInfo {
+            url: remote.url().unwrap_or("").to_string(),
+            push_url: remote.pushurl().map(|x| x.to_string()),
+            fetch_refspecs,
+            name,
+        });
+    }
+
+    Ok(remotes)
+}
+
+pub fn add_remote(repo: &Repository, name: &str, url: &str) -> Result<(), MetalGitError> {
+    repo.remote(name, url).map(|_| ()).context(&format!("Failed to add the remote {}", name))
+}
+
+// returns refspecs which could not be renamed
+pub fn rename_remote(repo: &Repository, name: &str, new_name: &str) -> Result<Vec<String>, MetalGitError> {
+    let problems = repo.remote_rename(name, new_name)
+                       .context(&format!("Failed to rename the remote {} to {}", name, new_name))?;
+
+    Ok(problems.iter().flatten().map(|x| x.to_string()).collect())
+}
+
+pub fn remove_remote(repo: &Repository, name: &str) -> Result<(), MetalGitError> {
+    repo.remote_delete(name).context(&format!("Failed to remove the remote {}", name))
+}
+
+// an empty push URL means the URL is also used for pushing
+pub fn set_remote_urls(repo: &Repository, name: &str, url: &str, push_url: &str) -> Result<(), MetalGitError> {
+    repo.remote_set_url(name, url)
+        .and_then(|_| repo.remote_set_pushurl(name, if push_url.is_empty() { None } else { Some(push_url) }))
+        .context(&format!("Failed to set the URLs of the remote {}", name))
+}
+
+pub fn set_fetch_refspecs(repo: &Repository, name: &str, refspecs: &[String]) -> Result<(), MetalGitError> {
+    let mut config = repo.config().context("Failed to open the config")?;
+    // removing fails when no refspec is configured
+    let _ = config.remove_multivar(&format!("remote.{}.fetch", name), ".*");
+
+    for refspec in refspecs {
+        repo.remote_add_fetch(name, refspec)
+            .context(&format!("Failed to add the refspec {} to the remote {}", refspec, name))?;
+    }
+
+    Ok(())
+}
+
+pub struct BranchInfo {
+    pub name: String,
+    // like "origin/master"
+    pub upstream: Option<String>,
+    // commits to push and to pull
+    pub ahead_behind: Option<(usize, usize)>,
+}
+
+pub fn list_branches(repo: &Repository) -> Result<Vec<BranchInfo>, MetalGitError> {
+    collect_branches(repo).context("Failed to list the branches")
+}
+
+fn collect_branches(repo: &Repository) -> Result<Vec<BranchInfo>, Error> {
+    let mut branches = Vec::new();
+    for branch in repo.branches(Some(git2::BranchType::Local))? {
+        let (branch, _) = branch?;
+        let name = branch.name()?.unwrap_or("").to_string();
+        let (upstream, ahead_behind) = match branch.upstream() {
+            Ok(upstream) => (upstream.name()?.map(|x| x.to_string()), ahead_behind(repo, &branch, &upstream)?),
+            Err(_) => (None, None),
+        };
+        branches.push(BranchInfo { name, upstream, ahead_behind });
+    }
+
+    Ok(branches)
+}
+
+fn ahead_behind(repo: &Repository,
+                branch: &git2::Branch,
+                upstream: &git2::Branch) -> Result<Option<(usize, usize)>, Error> {
+    match (branch.get().target(), upstream.get().target()) {
+        (Some(local), Some(remote)) => Ok(Some(repo.graph_ahead_behind(local, remote)?)),
+        _ => Ok(None),
+    }
+}
+
+// None when HEAD is detached or has no upstream
+pub fn head_ahead_behind(repo: &Repository) -> Result<Option<(usize, usize)>, MetalGitError> {
+    let head = match repo.head() {
+        Ok(head) if head.is_branch() => head,
+        _ => return Ok(None),
+    };
+
+    let branch = git2::Branch::wrap(head);
+    match branch.upstream() {
+        Ok(upstream) => ahead_behind(repo, &branch, &upstream).context("Failed to compare HEAD with its upstream"),
+        Err(_) => Ok(None),
+    }
+}
+
+// like "↑1 ↓2"; empty when up to date
+pub fn format_ahead_behind(ahead_behind: (usize, usize)) -> String {
+    let mut items = Vec::new();
+    if ahead_behind.0 > 0 {
+        items.push(format!("\u{2191}{}", ahead_behind.0));
+    }
+    if ahead_behind.1 > 0 {
+        items.push(format!("\u{2193}{}", ahead_behind.1));
+    }
+
+    items.join(" ")
+}
+
+pub fn remote_branch_names(repo: &Repository) -> Result<Vec<String>, MetalGitError> {
+    collect_remote_branch_names(repo).context("Failed to list the remote-tracking branches")
+}
+
+fn collect_remote_branch_names(repo: &Repository) -> Result<Vec<String>, Error> {
+    let mut names = Vec::new();
+    for branch in repo.branches(Some(git2::BranchType::Remote))? {
+        let (branch, _) = branch?;
+        if let Some(name) = branch.name()? {
+            // origin/HEAD is a symbolic reference
+            if !name.ends_with("/HEAD") {
+                names.push(name.to_string());
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+pub fn set_upstream(repo: &Repository, branch_name: &str, upstream: Option<&str>) -> Result<(), MetalGitError> {
+    repo.find_branch(branch_name, git2::BranchType::Local)
+        .and_then(|mut branch| branch.set_upstream(upstream))
+        .context(&format!("Failed to set the upstream of {}", branch_name))
+}
+
+// deletes remote-tracking branches which no longer exist on the remote, and returns their names
+pub fn prune(repo: &Repository, remote_name: &str) -> Result<Vec<String>, MetalGitError> {
+    prune_remote(repo, remote_name).context(&format!("Failed to prune {}", remote_name))
+}
+
+fn prune_remote(repo: &Repository, remote_name: &str) -> Result<Vec<String>, Error> {
+    let mut remote = repo.find_remote(remote_name)?;
+    let progress = RefCell::new(|_| {});
+
+    let pruned = RefCell::new(Vec::new());
+    {
+        let mut callbacks = create_callbacks(&progress);
+        callbacks.update_tips(|refname, _, new| {
+            if new.is_zero() {
+                pruned.borrow_mut().push(refname.to_string());
+            }
+            true
+        });
+
+        let mut connection = remote.connect_auth(Direction::Fetch, Some(create_callbacks(&progress)), None)?;
+        connection.remote().prune(Some(callbacks))?;
+    }
+
+    Ok(pruned.into_inner())
+}
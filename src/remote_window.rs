@@ -0,0 +1,396 @@
+use glib::StaticType;
+use gtk::prelude::{ComboBoxExtManual, GtkListStoreExtManual, TreeViewColumnExt, WidgetExtManual};
+use gtk::traits::{
+    BoxExt, ButtonExt, ComboBoxExt, ComboBoxTextExt, ContainerExt, GtkListStoreExt, GtkWindowExt, LabelExt,
+    TreeModelExt, TreeSelectionExt, TreeViewExt, WidgetExt,
+};
+use gtk::Inhibit;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::error::MetalGitError;
+use crate::error_bar::ErrorBar;
+use crate::gtk_utils;
+use crate::remote_util;
+use crate::repository_manager::RepositoryManager;
+
+pub struct RemoteWindow {
+    window: gtk::Window,
+
+    remote_list_store: gtk::ListStore,
+    remote_tree_view: gtk::TreeView,
+    add_button: gtk::Button,
+    rename_button: gtk::Button,
+    edit_button: gtk::Button,
+    remove_button: gtk::Button,
+    prune_button: gtk::Button,
+
+    branch_list_store: gtk::ListStore,
+    branch_tree_view: gtk::TreeView,
+    upstream_combo_box: gtk::ComboBoxText,
+    set_upstream_button: gtk::Button,
+
+    repository_manager: Rc<RepositoryManager>,
+    error_bar: Rc<ErrorBar>,
+
+    changed: RefCell<Box<dyn Fn()>>,
+}
+
+const COLUMN_REMOTE_NAME: u32 = 0;
+const COLUMN_REMOTE_URL: u32 = 1;
+const COLUMN_REMOTE_PUSH_URL: u32 = 2;
+const COLUMN_REMOTE_REFSPECS: u32 = 3;
+
+const COLUMN_BRANCH_NAME: u32 = 0;
+const COLUMN_BRANCH_UPSTREAM: u32 = 1;
+const COLUMN_BRANCH_AHEAD_BEHIND: u32 = 2;
+
+// the first item of upstream_combo_box
+const NO_UPSTREAM: &str = "(none)";
+
+fn create_tree_view(list_store: &gtk::ListStore, columns: &[(&str, u32)]) -> gtk::TreeView {
+    let tree_view = gtk::TreeView::with_model(list_store);
+    for &(title, column) in columns {
+        let renderer = gtk::CellRendererText::new();
+        let col = gtk::TreeViewColumn::new();
+        col.set_title(title);
+        col.set_resizable(true);
+        col.pack_start(&renderer, false);
+        col.add_attribute(&renderer, "text", column as i32);
+        tree_view.append_column(&col);
+    }
+
+    tree_view
+}
+
+impl RemoteWindow {
+    pub fn new(repository_manager: Rc<RepositoryManager>) -> Rc<RemoteWindow> {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_title("Remotes - Metal Git");
+        window.set_default_size(800, 500);
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        vbox.set_border_width(6);
+
+        let error_bar = ErrorBar::new();
+        vbox.pack_start(&error_bar.widget(), false, true, 0);
+
+        let label = gtk::Label::new(Some("Remotes"));
+        label.set_xalign(0.0);
+        vbox.pack_start(&label, false, true, 0);
+
+        let remote_list_store = gtk::ListStore::new(&[
+            String::static_type(), // COLUMN_REMOTE_NAME
+            String::static_type(), // COLUMN_REMOTE_URL
+            String::static_type(), // COLUMN_REMOTE_PUSH_URL
+            String::static_type(), // COLUMN_REMOTE_REFSPECS
+        ]);
+        let remote_tree_view = create_tree_view(&remote_list_store,
+                                                &[("Name", COLUMN_REMOTE_NAME),
+                                                  ("URL", COLUMN_REMOTE_URL),
+                                                  ("Push URL", COLUMN_REMOTE_PUSH_URL),
+                                                  ("Fetch refspecs", COLUMN_REMOTE_REFSPECS)]);
+        let scrolled = gtk::ScrolledWindow::builder().build();
+        scrolled.add(&remote_tree_view);
+        vbox.pack_start(&scrolled, true, true, 0);
+
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let add_button = gtk::Button::with_label("Add...");
+        hbox.pack_start(&add_button, false, true, 0);
+        let rename_button = gtk::Button::with_label("Rename...");
+        hbox.pack_start(&rename_button, false, true, 0);
+        let edit_button = gtk::Button::with_label("Edit...");
+        hbox.pack_start(&edit_button, false, true, 0);
+        let remove_button = gtk::Button::with_label("Remove");
+        hbox.pack_start(&remove_button, false, true, 0);
+        let prune_button = gtk::Button::with_label("Prune");
+        hbox.pack_end(&prune_button, false, true, 0);
+        vbox.pack_start(&hbox, false, true, 0);
+
+        let label = gtk::Label::new(Some("Local branches"));
+        label.set_xalign(0.0);
+        vbox.pack_start(&label, false, true, 0);
+
+        let branch_list_store = gtk::ListStore::new(&[
+            String::static_type(), // COLUMN_BRANCH_NAME
+            String::static_type(), // COLUMN_BRANCH_UPSTREAM
+            String::static_type(), // COLUMN_BRANCH_AHEAD_BEHIND
+        ]);
+        let branch_tree_view = create_tree_view(&branch_list_store,
+                                                &[("Branch", COLUMN_BRANCH_NAME),
+                                                  ("Upstream", COLUMN_BRANCH_UPSTREAM),
+                                                  ("Ahead/Behind", COLUMN_BRANCH_AHEAD_BEHIND)]);
+        let scrolled = gtk::ScrolledWindow::builder().build();
+        scrolled.add(&branch_tree_view);
+        vbox.pack_start(&scrolled, true, true, 0);
+
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let upstream_combo_box = gtk::ComboBoxText::new();
+        hbox.pack_start(&upstream_combo_box, true, true, 0);
+        let set_upstream_button = gtk::Button::with_label("Set upstream");
+        hbox.pack_start(&set_upstream_button, false, true, 0);
+        vbox.pack_start(&hbox, false, true, 0);
+
+        window.add(&vbox);
+
+        let remote_window = Rc::new(RemoteWindow {
+            window,
+            remote_list_store,
+            remote_tree_view,
+            add_button,
+            rename_button,
+            edit_button,
+            remove_button,
+            prune_button,
+            branch_list_store,
+            branch_tree_view,
+            upstream_combo_box,
+            set_upstream_button,
+            repository_manager,
+            error_bar,
+            changed: RefCell::new(Box::new(|| {})),
+        });
+        gtk_utils::register_error_bar(&remote_window.window, &remote_window.error_bar);
+
+        let w = Rc::downgrade(&remote_window);
+        remote_window.window.connect_delete_event(move |_, _| {
+            w.upgrade().unwrap().hide();
+            Inhibit(true)
+        });
+
+        let w = Rc::downgrade(&remote_window);
+        remote_window.add_button.connect_clicked(move |_| {
+            dialog_when_error!("{}", w.upgrade().unwrap().add_button_clicked());
+        });
+
+        let w = Rc::downgrade(&remote_window);
+        remote_window.rename_button.connect_clicked(move |_| {
+            dialog_when_error!("{}", w.upgrade().unwrap().rename_button_clicked());
+        });
+
+        let w = Rc::downgrade(&remote_window);
+        remote_window.edit_button.connect_clicked(move |_| {
+            dialog_when_error!("{}", w.upgrade().unwrap().edit_button_clicked());
+        });
+
+        let w = Rc::downgrade(&remote_window);
+        remote_window.remove_button.connect_clicked(move |_| {
+            dialog_when_error!("{}", w.upgrade().unwrap().remove_button_clicked());
+        });
+
+        let w = Rc::downgrade(&remote_window);
+        remote_window.prune_button.connect_clicked(move |_| {
+            dialog_when_error!("{}", w.upgrade().unwrap().prune_button_clicked());
+        });
+
+        let w = Rc::downgrade(&remote_window);
+        remote_window.branch_tree_view.selection().connect_changed(move |_| {
+            w.upgrade().unwrap().branch_selected();
+        });
+
+        let w = Rc::downgrade(&remote_window);
+        remote_window.set_upstream_button.connect_clicked(move |_| {
+            dialog_when_error!("{}", w.upgrade().unwrap().set_upstream_button_clicked());
+        });
+
+        remote_window
+    }
+
+    pub fn show(&self) {
+        self.window.show_all();
+        self.window.present();
+        self.refresh();
+    }
+
+    pub fn hide(&self) {
+        self.window.hide();
+    }
+
+    // the window is not shown again
+    pub fn close(&self) {
+        gtk_utils::unregister_error_bar(&self.window);
+        // nothing shows or refreshes the window after this
+        unsafe {
+            self.window.destroy();
+        }
+    }
+
+    pub fn refresh(&self) {
+        dialog_when_error!("Failed to load remotes: {}", self.load());
+    }
+
+    fn load(&self) -> Result<(), MetalGitError> {
+        let repo = self.repository_manager.open()?;
+
+        self.remote_list_store.clear();
+        for remote in remote_util::list_remotes(&repo)? {
+            self.remote_list_store.insert_with_values(
+                None,
+                &[(COLUMN_REMOTE_NAME, &remote.name),
+                  (COLUMN_REMOTE_URL, &remote.url),
+                  (COLUMN_REMOTE_PUSH_URL, &remote.push_url.unwrap_or_default()),
+                  (COLUMN_REMOTE_REFSPECS, &remote.fetch_refspecs.join(" "))]);
+        }
+
+        self.branch_list_store.clear();
+        for branch in remote_util::list_branches(&repo)? {
+            let ahead_behind = branch.ahead_behind.map(remote_util::format_ahead_behind).unwrap_or_default();
+            self.branch_list_store.insert_with_values(
+                None,
+                &[(COLUMN_BRANCH_NAME, &branch.name),
+                  (COLUMN_BRANCH_UPSTREAM, &branch.upstream.unwrap_or_default()),
+                  (COLUMN_BRANCH_AHEAD_BEHIND, &ahead_behind)]);
+        }
+
+        self.upstream_combo_box.remove_all();
+        self.upstream_combo_box.append_text(NO_UPSTREAM);
+        for name in remote_util::remote_branch_names(&repo)? {
+            self.upstream_combo_box.append_text(&name);
+        }
+        self.upstream_combo_box.set_active(Some(0));
+
+        Ok(())
+    }
+
+    fn selected_value(tree_view: &gtk::TreeView, column: u32) -> Option<String> {
+        tree_view.selection().selected().map(|(model, iter)| {
+            model.value(&iter, column as i32).get::<String>().unwrap()
+        })
+    }
+
+    fn selected_remote(&self) -> Result<String, MetalGitError> {
+        Self::selected_value(&self.remote_tree_view, COLUMN_REMOTE_NAME)
+            .ok_or_else(|| MetalGitError::new("No remote is selected"))
+    }
+
+    fn changed(&self) {
+        self.refresh();
+        self.changed.borrow()();
+    }
+
+    fn add_button_clicked(&self) -> Result<(), MetalGitError> {
+        if let Some(values) = gtk_utils::input_box("Add remote", &[("Name", ""), ("URL", "")]) {
+            let repo = self.repository_manager.open()?;
+            remote_util::add_remote(&repo, &values[0], &values[1])?;
+            self.changed();
+        }
+
+        Ok(())
+    }
+
+    fn rename_button_clicked(&self) -> Result<(), MetalGitError> {
+        let name = self.selected_remote()?;
+
+        if let Some(values) = gtk_utils::input_box("Rename remote", &[("Name", &name)]) {
+            let repo = self.repository_manager.open()?;
+            let problems = remote_util::rename_remote(&repo, &name, &values[0])?;
+            self.changed();
+
+            if !problems.is_empty() {
+                gtk_utils::message_box_error(&format!("These refspecs were not renamed: {}",
+                                                      problems.join(", ")));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn edit_button_clicked(&self) -> Result<(), MetalGitError> {
+        let name = self.selected_remote()?;
+        let repo = self.repository_manager.open()?;
+        let remote = match remote_util::list_remotes(&repo)?.into_iter().find(|x| x.name == name) {
+            Some(remote) => remote,
+            None => return Ok(()),
+        };
+
+        let push_url = remote.push_url.unwrap_or_default();
+        let refspecs = remote.fetch_refspecs.join(" ");
+        let fields = [("URL", remote.url.as_str()),
+                      ("Push URL", push_url.as_str()),
+                      ("Fetch refspecs", refspecs.as_str())];
+
+        if let Some(values) = gtk_utils::input_box(&format!("Edit remote {}", name), &fields) {
+            remote_util::set_remote_urls(&repo, &name, &values[0], &values[1])?;
+
+            // refspecs are separated by spaces
+            let refspecs = values[2].split_whitespace().map(|x| x.to_string()).collect::<Vec<_>>();
+            remote_util::set_fetch_refspecs(&repo, &name, &refspecs)?;
+            self.changed();
+        }
+
+        Ok(())
+    }
+
+    fn remove_button_clicked(&self) -> Result<(), MetalGitError> {
+        let name = self.selected_remote()?;
+
+        if gtk_utils::message_box_question(&format!("Remove the remote {}?", name)) {
+            let repo = self.repository_manager.open()?;
+            remote_util::remove_remote(&repo, &name)?;
+            self.changed();
+        }
+
+        Ok(())
+    }
+
+    fn prune_button_clicked(&self) -> Result<(), MetalGitError> {
+        let name = self.selected_remote()?;
+        let repo = self.repository_manager.open()?;
+
+        let pruned = remote_util::prune(&repo, &name)?;
+        self.changed();
+
+        let message = if pruned.is_empty() {
+            "No stale remote-tracking branch".to_string()
+        } else {
+            format!("Pruned: {}", pruned.join(", "))
+        };
+        gtk_utils::message_box_info(&message);
+
+        Ok(())
+    }
+
+    fn branch_selected(&self) {
+        let upstream = Self::selected_value(&self.branch_tree_view, COLUMN_BRANCH_UPSTREAM);
+
+        // selects the current upstream in the combo box
+        if let Some(model) = self.upstream_combo_box.model() {
+            let mut index = 0;
+            if let Some(iter) = model.iter_first() {
+                loop {
+                    let text = model.value(&iter, 0).get::<String>().unwrap_or_default();
+                    if Some(&text) == upstream.as_ref() {
+                        self.upstream_combo_box.set_active(Some(index));
+                        return;
+                    }
+
+                    index += 1;
+                    if !model.iter_next(&iter) {
+                        break;
+                    }
+                }
+            }
+        }
+        self.upstream_combo_box.set_active(Some(0));
+    }
+
+    fn set_upstream_button_clicked(&self) -> Result<(), MetalGitError> {
+        let branch = Self::selected_value(&self.branch_tree_view, COLUMN_BRANCH_NAME)
+            .ok_or_else(|| MetalGitError::new("No branch is selected"))?;
+        let upstream = self.upstream_combo_box.active_text().map(|x| x.to_string());
+        let upstream = upstream.as_deref().filter(|x| *x != NO_UPSTREAM);
+
+        let repo = self.repository_manager.open()?;
+        remote_util::set_upstream(&repo, &branch, upstream)?;
+        self.changed();
+
+        Ok(())
+    }
+
+    pub fn connect_changed<F>(&self, callback: F)
+        where F: Fn() + 'static
+    {
+        *self.changed.borrow_mut() = Box::new(callback);
+    }
+}
@@ -4,19 +4,74 @@ use std::cell::RefCell;
 
 pub struct RepositoryManager {
     work_dir_path: RefCell<String>,
+    // reused while the repository exists, instead of discovering it on each open
+    repository: RefCell<Option<Rc<Repository>>>,
 }
 
 impl RepositoryManager {
     pub fn new() -> Rc<RepositoryManager> {
-        Rc::new(RepositoryManager { work_dir_path: RefCell::new("".to_string()) })
+        Rc::new(RepositoryManager {
+            work_dir_path: RefCell::new("".to_string()),
+            repository: RefCell::new(None),
+        })
     }
 
     pub fn set_work_dir_path(&self, work_dir_path: &str) {
+        if *self.work_dir_path.borrow() != work_dir_path {
+            self.invalidate();
+        }
+
         *self.work_dir_path.borrow_mut() = work_dir_path.to_string();
     }
 
-    pub fn open(&self) -> Result<Repository, Error> {
-        // TODO: check the path is set
-        git2::Repository::discover(self.work_dir_path.borrow().as_str())
+    pub fn work_dir_path(&self) -> String {
+        self.work_dir_path.borrow().clone()
+    }
+
+    // the next open discovers the repository again
+    pub fn invalidate(&self) {
+        *self.repository.borrow_mut() = None;
+    }
+
+    pub fn open(&self) -> Result<Rc<Repository>, Error> {
+        if let Some(repo) = self.repository.borrow().as_ref() {
+            if Self::revalidate(repo) {
+                return Ok(Rc::clone(repo));
+            }
+        }
+
+        let repo = Rc::new(self.discover()?);
+        *self.repository.borrow_mut() = Some(Rc::clone(&repo));
+
+        Ok(repo)
+    }
+
+    // a handle of its own, for operations which need &mut Repository
+    pub fn open_mut(&self) -> Result<Repository, Error> {
+        let repo = self.open()?;
+
+        Repository::open(repo.path())
+    }
+
+    fn discover(&self) -> Result<Repository, Error> {
+        let work_dir_path = self.work_dir_path.borrow();
+        if work_dir_path.is_empty() {
+            return Err(Error::from_str("No repository is opened"));
+        }
+
+        git2::Repository::discover(work_dir_path.as_str())
+    }
+
+    // refs and objects are read from the disk on each lookup, but the index is loaded once
+    fn revalidate(repo: &Repository) -> bool {
+        if !repo.path().exists() {
+            return false;
+        }
+
+        // reloads the index only when the file was changed
+        match repo.index() {
+            Ok(mut index) => index.read(false).is_ok(),
+            Err(_) => false,
+        }
     }
 }
@@ -0,0 +1,148 @@
+use gtk::gio;
+use gtk::gio::prelude::{FileExt, FileMonitorExt};
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use git2::Error;
+
+use crate::repository_manager::RepositoryManager;
+use crate::watch_util;
+use crate::watch_util::Changes;
+
+type ChangedCallback = Box<dyn Fn(&Changes)>;
+
+// refreshes are delayed until events stop for this duration
+const DEBOUNCE_INTERVAL: Duration = Duration::from_millis(300);
+
+// watches the work tree and the .git directory, and reports changes after debouncing
+pub struct RepositoryWatcher {
+    repository_manager: Rc<RepositoryManager>,
+
+    monitors: RefCell<Vec<gio::FileMonitor>>,
+    pending: RefCell<Changes>,
+    timeout: RefCell<Option<glib::SourceId>>,
+
+    changed: RefCell<ChangedCallback>,
+}
+
+impl RepositoryWatcher {
+    pub fn new(repository_manager: Rc<RepositoryManager>) -> Rc<RepositoryWatcher> {
+        Rc::new(RepositoryWatcher {
+            repository_manager,
+            monitors: RefCell::new(Vec::new()),
+            pending: RefCell::new(Changes::default()),
+            timeout: RefCell::new(None),
+            changed: RefCell::new(Box::new(|_| {})),
+        })
+    }
+
+    pub fn start(self: &Rc<Self>) -> Result<(), Error> {
+        self.stop();
+
+        let repo = self.repository_manager.open()?;
+        for dir in watch_util::watched_directories(&repo) {
+            self.watch_directory(&dir)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        for monitor in self.monitors.borrow_mut().drain(..) {
+            monitor.cancel();
+        }
+
+        if let Some(source_id) = self.timeout.borrow_mut().take() {
+            source_id.remove();
+        }
+        *self.pending.borrow_mut() = Changes::default();
+    }
+
+    fn watch_directory(self: &Rc<Self>, dir: &Path) -> Result<(), Error> {
+        let monitor = gio::File::for_path(dir)
+            .monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+            .map_err(|e| Error::from_str(&format!("Failed to watch {}: {}", dir.to_string_lossy(), e)))?;
+
+        let w = Rc::downgrade(self);
+        monitor.connect_changed(move |_, file, other_file, event| {
+            if let Some(w) = w.upgrade() {
+                w.file_changed(file, other_file, event);
+            }
+        });
+
+        self.monitors.borrow_mut().push(monitor);
+
+        Ok(())
+    }
+
+    fn file_changed(self: &Rc<Self>,
+                    file: &gio::File,
+                    other_file: Option<&gio::File>,
+                    event: gio::FileMonitorEvent) {
+        match event {
+            gio::FileMonitorEvent::Changed
+            | gio::FileMonitorEvent::Created
+            | gio::FileMonitorEvent::Deleted
+            | gio::FileMonitorEvent::Renamed
+            | gio::FileMonitorEvent::MovedIn
+            | gio::FileMonitorEvent::MovedOut => {}
+            _ => return,
+        }
+
+        let repo = match self.repository_manager.open() {
+            Ok(repo) => repo,
+            Err(_) => return,
+        };
+
+        // "index.lock" is renamed to "index"
+        let mut changes = Changes::default();
+        for path in [Some(file), other_file].iter().flatten().filter_map(|x| x.path()) {
+            if let Some(kind) = watch_util::classify_change(&repo, &path) {
+                changes.add(kind);
+            }
+
+            // new directories are watched too
+            if event != gio::FileMonitorEvent::Deleted && path.is_dir() {
+                let mut dirs = Vec::new();
+                watch_util::collect_directories(&repo, &path, &mut dirs);
+                for dir in dirs {
+                    // it may be removed already
+                    let _ = self.watch_directory(&dir);
+                }
+            }
+        }
+
+        if !changes.is_empty() {
+            self.schedule(changes);
+        }
+    }
+
+    fn schedule(self: &Rc<Self>, changes: Changes) {
+        self.pending.borrow_mut().merge(&changes);
+
+        if let Some(source_id) = self.timeout.borrow_mut().take() {
+            source_id.remove();
+        }
+
+        let w = Rc::downgrade(self);
+        let source_id = glib::timeout_add_local(DEBOUNCE_INTERVAL, move || {
+            if let Some(w) = w.upgrade() {
+                // the source is removed by returning false
+                w.timeout.borrow_mut().take();
+                let changes = std::mem::take(&mut *w.pending.borrow_mut());
+                w.changed.borrow()(&changes);
+            }
+            glib::Continue(false)
+        });
+        *self.timeout.borrow_mut() = Some(source_id);
+    }
+
+    pub fn connect_changed<F>(&self, callback: F)
+        where F: Fn(&Changes) + 'static
+    {
+        *self.changed.borrow_mut() = Box::new(callback);
+    }
+}
@@ -0,0 +1,136 @@
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+use crate::history_window::HistoryWindow;
+use crate::commit_window::CommitWindow;
+use crate::event_bus::{EventBus, RepositoryEvent};
+use crate::stash_window::StashWindow;
+use crate::repository_manager::RepositoryManager;
+use crate::repository_watcher::RepositoryWatcher;
+use crate::window_manager::WindowManager;
+
+// windows for one repository
+pub struct Session {
+    window_manager: Weak<WindowManager>,
+    repository_manager: Rc<RepositoryManager>,
+    watcher: Rc<RepositoryWatcher>,
+    event_bus: Rc<EventBus>,
+    windows: RefCell<Option<Windows>>,
+}
+
+struct Windows {
+    history_window: Rc<HistoryWindow>,
+    commit_window: Rc<CommitWindow>,
+    stash_window: Rc<StashWindow>,
+}
+
+impl Session {
+    pub fn new(window_manager: Weak<WindowManager>, work_dir_path: &str) -> Rc<Session> {
+        let repository_manager = RepositoryManager::new();
+        repository_manager.set_work_dir_path(work_dir_path);
+
+        let session = Rc::new(Session {
+            window_manager,
+            repository_manager: Rc::clone(&repository_manager),
+            watcher: RepositoryWatcher::new(Rc::clone(&repository_manager)),
+            event_bus: EventBus::new(),
+            windows: RefCell::new(None),
+        });
+
+        let event_bus = &session.event_bus;
+        let windows = Windows {
+            history_window: HistoryWindow::new(Rc::downgrade(&session),
+                                               repository_manager.clone(),
+                                               event_bus.clone()),
+            commit_window: CommitWindow::new(repository_manager.clone(), event_bus.clone()),
+            stash_window: StashWindow::new(repository_manager.clone(), event_bus.clone()),
+        };
+
+        *session.windows.borrow_mut() = Some(windows);
+
+        // changes by other programs like the git command
+        let b = Rc::downgrade(&session.event_bus);
+        session.watcher.connect_changed(move |changes| {
+            let event_bus = b.upgrade().unwrap();
+            if changes.refs {
+                event_bus.publish(RepositoryEvent::RefsChanged);
+            }
+            if changes.index {
+                event_bus.publish(RepositoryEvent::IndexChanged);
+            } else if changes.work_tree {
+                // the status is loaded once for both
+                event_bus.publish(RepositoryEvent::WorkTreeChanged);
+            }
+        });
+        dialog_when_error!("Failed to watch the repository: {}", session.watcher.start());
+
+        session
+    }
+
+    fn with_windows<F>(&self, func: F)
+        where F: Fn(&Windows)
+    {
+        let windows_ref = self.windows.borrow();
+        let windows = windows_ref.as_ref().unwrap();
+        func(windows);
+    }
+
+    pub fn window_manager(&self) -> Rc<WindowManager> {
+        self.window_manager.upgrade().unwrap()
+    }
+
+    pub fn work_dir_path(&self) -> String {
+        self.repository_manager.work_dir_path()
+    }
+
+    pub fn history_window(&self) -> gtk::Window {
+        self.windows.borrow().as_ref().unwrap().history_window.window()
+    }
+
+    pub fn show(&self) {
+        self.with_windows(|windows| {
+            windows.history_window.show();
+        });
+    }
+
+    pub fn present(&self) {
+        self.with_windows(|windows| {
+            windows.history_window.present();
+        });
+    }
+
+    pub fn update_windows_menu(&self, session_paths: &[String]) {
+        self.with_windows(|windows| {
+            windows.history_window.update_windows_menu(session_paths);
+        });
+    }
+
+    // called when the history window is closed
+    pub fn connect_closed<F>(&self, callback: F)
+        where F: Fn() + 'static
+    {
+        let windows_ref = self.windows.borrow();
+        windows_ref.as_ref().unwrap().history_window.connect_closed(callback);
+    }
+
+    // the history window is destroyed by itself
+    pub fn close(&self) {
+        self.watcher.stop();
+        self.with_windows(|windows| {
+            windows.history_window.close();
+            windows.commit_window.close();
+            windows.stash_window.close();
+        });
+    }
+
+    pub fn show_commit_window(&self) {
+        self.with_windows(|windows| {
+            windows.commit_window.show();
+        });
+    }
+
+    pub fn show_stash_window(&self) {
+        self.with_windows(|windows| {
+            windows.stash_window.show();
+        });
+    }
+}
@@ -0,0 +1,148 @@
+use glib::StaticType;
+use gtk::prelude::{GtkListStoreExtManual, TreeViewColumnExt};
+use gtk::traits::{
+    BoxExt, ButtonExt, ContainerExt, GtkListStoreExt, GtkWindowExt, LabelExt, TreeModelExt,
+    TreeViewExt, WidgetExt,
+};
+use gtk::Inhibit;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// shown when no repository is opened: an error, recent repositories and "Open repository..."
+pub struct StartWindow {
+    window: gtk::Window,
+
+    error_label: gtk::Label,
+    recent_list_store: gtk::ListStore,
+    recent_tree_view: gtk::TreeView,
+    open_button: gtk::Button,
+
+    open_requested: RefCell<Box<dyn Fn(Option<String>)>>,
+    closed: RefCell<Box<dyn Fn()>>,
+}
+
+const COLUMN_PATH: u32 = 0;
+
+impl StartWindow {
+    pub fn new() -> Rc<StartWindow> {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_title("Metal Git");
+        window.set_default_size(600, 400);
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        vbox.set_border_width(12);
+
+        let error_label = gtk::Label::new(None);
+        error_label.set_xalign(0.0);
+        error_label.set_line_wrap(true);
+        error_label.set_selectable(true);
+        vbox.pack_start(&error_label, false, true, 6);
+
+        let label = gtk::Label::new(Some("Recent repositories"));
+        label.set_xalign(0.0);
+        vbox.pack_start(&label, false, true, 0);
+
+        let recent_list_store = gtk::ListStore::new(&[
+            String::static_type(), // COLUMN_PATH
+        ]);
+        let recent_tree_view = gtk::TreeView::with_model(&recent_list_store);
+        recent_tree_view.set_headers_visible(false);
+        let renderer = gtk::CellRendererText::new();
+        let col = gtk::TreeViewColumn::new();
+        col.pack_start(&renderer, true);
+        col.add_attribute(&renderer, "text", COLUMN_PATH as i32);
+        recent_tree_view.append_column(&col);
+
+        let scrolled = gtk::ScrolledWindow::builder().build();
+        scrolled.add(&recent_tree_view);
+        vbox.pack_start(&scrolled, true, true, 0);
+
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let open_button = gtk::Button::with_label("Open repository...");
+        hbox.pack_end(&open_button, false, true, 0);
+        vbox.pack_start(&hbox, false, true, 0);
+
+        window.add(&vbox);
+
+        let start_window = Rc::new(StartWindow {
+            window,
+            error_label,
+            recent_list_store,
+            recent_tree_view,
+            open_button,
+            open_requested: RefCell::new(Box::new(|_| {})),
+            closed: RefCell::new(Box::new(|| {})),
+        });
+
+        let w = Rc::downgrade(&start_window);
+        start_window.window.connect_delete_event(move |_, _| {
+            let w = w.upgrade().unwrap();
+            w.window.hide();
+            w.closed.borrow()();
+            Inhibit(true)
+        });
+
+        // None asks to choose a directory
+        let w = Rc::downgrade(&start_window);
+        start_window.open_button.connect_clicked(move |_| {
+            w.upgrade().unwrap().open_requested.borrow()(None);
+        });
+
+        let w = Rc::downgrade(&start_window);
+        start_window.recent_tree_view.connect_row_activated(move |tree_view, path, _| {
+            let model = tree_view.model().unwrap();
+            if let Some(iter) = model.iter(path) {
+                let path = model.value(&iter, COLUMN_PATH as i32).get::<String>().unwrap();
+                w.upgrade().unwrap().open_requested.borrow()(Some(path));
+            }
+        });
+
+        start_window
+    }
+
+    pub fn window(&self) -> gtk::Window {
+        self.window.clone()
+    }
+
+    // error is None when nothing was tried to open
+    pub fn show(&self, error: Option<&str>, recent_paths: &[String]) {
+        self.window.show_all();
+
+        match error {
+            Some(error) => {
+                let markup = format!("<span foreground=\"#a00000\"><b>Failed to open the repository</b></span>\n{}",
+                                     glib::markup_escape_text(error));
+                self.error_label.set_markup(&markup);
+                self.error_label.show();
+            }
+            None => {
+                self.error_label.set_text("");
+                self.error_label.hide();
+            }
+        }
+
+        self.recent_list_store.clear();
+        for path in recent_paths {
+            self.recent_list_store.insert_with_values(None, &[(COLUMN_PATH, path)]);
+        }
+
+        self.window.present();
+    }
+
+    pub fn hide(&self) {
+        self.window.hide();
+    }
+
+    pub fn connect_open_requested<F>(&self, callback: F)
+        where F: Fn(Option<String>) + 'static
+    {
+        *self.open_requested.borrow_mut() = Box::new(callback);
+    }
+
+    pub fn connect_closed<F>(&self, callback: F)
+        where F: Fn() + 'static
+    {
+        *self.closed.borrow_mut() = Box::new(callback);
+    }
+}
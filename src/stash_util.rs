@@ -0,0 +1,77 @@
+use git2::{Error, Oid, Repository, StashFlags};
+
+use crate::error::{MetalGitError, ResultExt};
+use crate::repository_manager::RepositoryManager;
+
+pub struct StashEntry {
+    pub index: usize,
+    pub message: String,
+    pub oid: Oid,
+}
+
+impl StashEntry {
+    // e.g. stash@{0}
+    pub fn name(&self) -> String {
+        format!("stash@{{{}}}", self.index)
+    }
+}
+
+pub fn list_stashes(repository_manager: &RepositoryManager) -> Result<Vec<StashEntry>, MetalGitError> {
+    let mut repo = repository_manager.open_mut()?;
+
+    collect_stashes(&mut repo).context("Failed to list the stashes")
+}
+
+pub fn collect_stashes(repo: &mut Repository) -> Result<Vec<StashEntry>, Error> {
+    let mut entries = Vec::new();
+    repo.stash_foreach(|index, message, oid| {
+        entries.push(StashEntry {
+            index,
+            message: message.to_string(),
+            oid: *oid,
+        });
+        true
+    })?;
+
+    Ok(entries)
+}
+
+pub fn save_stash(repository_manager: &RepositoryManager,
+                  message: &str,
+                  include_untracked: bool,
+                  keep_index: bool)
+                  -> Result<Oid, MetalGitError> {
+    let mut repo = repository_manager.open_mut()?;
+    let signature = repo.signature().context("Failed to read user.name and user.email")?;
+
+    let mut flags = StashFlags::DEFAULT;
+    if include_untracked {
+        flags |= StashFlags::INCLUDE_UNTRACKED;
+    }
+    if keep_index {
+        flags |= StashFlags::KEEP_INDEX;
+    }
+
+    // an empty message makes the default "WIP on <branch>: ..."
+    let message = if message.trim().is_empty() { None } else { Some(message) };
+
+    repo.stash_save2(&signature, message, Some(flags)).context("Failed to stash the changes")
+}
+
+pub fn apply_stash(repository_manager: &RepositoryManager, index: usize) -> Result<(), MetalGitError> {
+    let mut repo = repository_manager.open_mut()?;
+
+    repo.stash_apply(index, None).context(&format!("Failed to apply stash@{{{}}}", index))
+}
+
+pub fn pop_stash(repository_manager: &RepositoryManager, index: usize) -> Result<(), MetalGitError> {
+    let mut repo = repository_manager.open_mut()?;
+
+    repo.stash_pop(index, None).context(&format!("Failed to pop stash@{{{}}}", index))
+}
+
+pub fn drop_stash(repository_manager: &RepositoryManager, index: usize) -> Result<(), MetalGitError> {
+    let mut repo = repository_manager.open_mut()?;
+
+    repo.stash_drop(index).context(&format!("Failed to drop stash@{{{}}}", index))
+}
@@ -0,0 +1,238 @@
+use glib::StaticType;
+use gtk::prelude::{GtkListStoreExtManual, TreeViewColumnExt, WidgetExtManual};
+use gtk::traits::{
+    BoxExt, ButtonExt, ContainerExt, GtkListStoreExt, GtkWindowExt, PanedExt, TreeModelExt,
+    TreeSelectionExt, TreeViewExt, WidgetExt,
+};
+use gtk::Inhibit;
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+
+use crate::commit_diff_panel::CommitDiffPanel;
+use crate::error::MetalGitError;
+use crate::error_bar::ErrorBar;
+use crate::gtk_utils;
+use crate::event_bus::{EventBus, RepositoryEvent, SubscriptionId};
+use crate::repository_manager::RepositoryManager;
+use crate::stash_util;
+use crate::stash_util::StashEntry;
+
+pub struct StashWindow {
+    window: gtk::Window,
+
+    apply_button: gtk::Button,
+    pop_button: gtk::Button,
+    drop_button: gtk::Button,
+    refresh_button: gtk::Button,
+
+    stash_list_store: gtk::ListStore,
+    stash_tree_view: gtk::TreeView,
+
+    commit_diff_panel: Rc<CommitDiffPanel>,
+
+    repository_manager: Rc<RepositoryManager>,
+    stashes: RefCell<Vec<StashEntry>>,
+
+    event_bus: Rc<EventBus>,
+    subscription: Cell<Option<SubscriptionId>>,
+    error_bar: Rc<ErrorBar>,
+}
+
+const COLUMN_NAME: u32 = 0;
+const COLUMN_MESSAGE: u32 = 1;
+const COLUMN_INDEX: u32 = 2;
+
+impl StashWindow {
+    pub fn new(repository_manager: Rc<RepositoryManager>, event_bus: Rc<EventBus>) -> Rc<StashWindow> {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_title("Stashes - Metal Git");
+        window.set_default_size(1000, 600);
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 0);
+
+        let error_bar = ErrorBar::new();
+        vbox.pack_start(&error_bar.widget(), false, true, 0);
+
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let apply_button = gtk::Button::with_label("Apply");
+        hbox.pack_start(&apply_button, false, true, 0);
+        let pop_button = gtk::Button::with_label("Pop");
+        hbox.pack_start(&pop_button, false, true, 0);
+        let drop_button = gtk::Button::with_label("Drop");
+        hbox.pack_start(&drop_button, false, true, 0);
+        let refresh_button = gtk::Button::with_label("Refresh");
+        hbox.pack_end(&refresh_button, false, true, 0);
+        vbox.pack_start(&hbox, false, true, 6);
+
+        let stash_list_store = gtk::ListStore::new(&[
+            String::static_type(), // COLUMN_NAME
+            String::static_type(), // COLUMN_MESSAGE
+            u32::static_type(),    // COLUMN_INDEX
+        ]);
+
+        let stash_tree_view = gtk::TreeView::with_model(&stash_list_store);
+        for &(title, column) in &[("Stash", COLUMN_NAME), ("Message", COLUMN_MESSAGE)] {
+            let renderer = gtk::CellRendererText::new();
+            let col = gtk::TreeViewColumn::new();
+            col.set_title(title);
+            col.pack_start(&renderer, false);
+            col.add_attribute(&renderer, "text", column as i32);
+            stash_tree_view.append_column(&col);
+        }
+
+        let paned = gtk::Paned::new(gtk::Orientation::Horizontal);
+        let scrolled = gtk::ScrolledWindow::builder().build();
+        scrolled.add(&stash_tree_view);
+        paned.pack1(&scrolled, true, false);
+
+        // a stash is a merge commit, whose first parent is the base commit
+        let commit_diff_panel = CommitDiffPanel::new(Rc::clone(&repository_manager));
+        paned.pack2(&commit_diff_panel.container(), true, false);
+        paned.set_position(300);
+        vbox.pack_start(&paned, true, true, 0);
+
+        window.add(&vbox);
+
+        let stash_window = Rc::new(StashWindow {
+            window,
+            apply_button,
+            pop_button,
+            drop_button,
+            refresh_button,
+            stash_list_store,
+            stash_tree_view,
+            commit_diff_panel,
+            repository_manager,
+            stashes: RefCell::new(Vec::new()),
+            event_bus,
+            subscription: Cell::new(None),
+            error_bar,
+        });
+        gtk_utils::register_error_bar(&stash_window.window, &stash_window.error_bar);
+
+        let w = Rc::downgrade(&stash_window);
+        stash_window.window.connect_delete_event(move |_, _| {
+            w.upgrade().unwrap().hide();
+            Inhibit(true)
+        });
+
+        let w = Rc::downgrade(&stash_window);
+        let subscription = stash_window.event_bus.subscribe(move |event| {
+            if let Some(w) = w.upgrade() {
+                if event == RepositoryEvent::RefsChanged && w.window.is_visible() {
+                    w.refresh();
+                }
+            }
+        });
+        stash_window.subscription.set(Some(subscription));
+
+        let w = Rc::downgrade(&stash_window);
+        stash_window.stash_tree_view.selection().connect_changed(move |_| {
+            let w = w.upgrade().unwrap();
+            dialog_when_error!("Failed to show the stash: {}", w.stash_selected());
+        });
+
+        let w = Rc::downgrade(&stash_window);
+        stash_window.apply_button.connect_clicked(move |_| {
+            let w = w.upgrade().unwrap();
+            dialog_when_error!("{}", w.run_on_selected(stash_util::apply_stash));
+        });
+
+        let w = Rc::downgrade(&stash_window);
+        stash_window.pop_button.connect_clicked(move |_| {
+            let w = w.upgrade().unwrap();
+            dialog_when_error!("{}", w.run_on_selected(stash_util::pop_stash));
+        });
+
+        let w = Rc::downgrade(&stash_window);
+        stash_window.drop_button.connect_clicked(move |_| {
+            let w = w.upgrade().unwrap();
+            dialog_when_error!("{}", w.run_on_selected(stash_util::drop_stash));
+        });
+
+        let w = Rc::downgrade(&stash_window);
+        stash_window.refresh_button.connect_clicked(move |_| {
+            w.upgrade().unwrap().refresh();
+        });
+
+        stash_window
+    }
+
+    pub fn show(&self) {
+        self.window.show_all();
+        self.window.present();
+        self.refresh();
+    }
+
+    pub fn hide(&self) {
+        self.window.hide();
+    }
+
+    // the window is not shown again
+    pub fn close(&self) {
+        if let Some(subscription) = self.subscription.take() {
+            self.event_bus.unsubscribe(subscription);
+        }
+        self.commit_diff_panel.close();
+        gtk_utils::unregister_error_bar(&self.window);
+        // nothing shows or refreshes the window after this
+        unsafe {
+            self.window.destroy();
+        }
+    }
+
+    pub fn refresh(&self) {
+        dialog_when_error!("{}", self.load_stashes());
+    }
+
+    fn load_stashes(&self) -> Result<(), MetalGitError> {
+        let stashes = stash_util::list_stashes(&self.repository_manager)?;
+
+        self.stash_list_store.clear();
+        for stash in &stashes {
+            let index = stash.index as u32;
+            self.stash_list_store.insert_with_values(None,
+                                                     &[(COLUMN_NAME, &stash.name()),
+                                                       (COLUMN_MESSAGE, &stash.message),
+                                                       (COLUMN_INDEX, &index)]);
+        }
+
+        self.stashes.replace(stashes);
+
+        Ok(())
+    }
+
+    fn selected_index(&self) -> Option<usize> {
+        self.stash_tree_view.selection().selected().map(|(model, iter)| {
+            model.value(&iter, COLUMN_INDEX as i32).get::<u32>().unwrap() as usize
+        })
+    }
+
+    fn stash_selected(&self) -> Result<(), MetalGitError> {
+        let oid = self.selected_index()
+                      .and_then(|index| self.stashes.borrow().get(index).map(|x| x.oid));
+
+        match oid {
+            Some(oid) => self.commit_diff_panel.update_commit(oid),
+            None => Ok(()),
+        }
+    }
+
+    fn run_on_selected<F>(&self, func: F) -> Result<(), MetalGitError>
+        where F: Fn(&RepositoryManager, usize) -> Result<(), MetalGitError>
+    {
+        let index = match self.selected_index() {
+            Some(index) => index,
+            None => return Err(MetalGitError::new("No stash is selected")),
+        };
+
+        let result = func(&self.repository_manager, index);
+
+        // refreshed even when failed
+        self.event_bus.publish_all(&[RepositoryEvent::RefsChanged, RepositoryEvent::WorkTreeChanged]);
+
+        result
+    }
+}
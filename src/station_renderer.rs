@@ -85,7 +85,12 @@ pub fn render(station: &railway::RailwayStation,
                         dot_radius as f64,
                         0.0,
                         2.0 * consts::PI);
-            context.fill()?;
+            // uncommitted changes are drawn as a hollow dot
+            if station.is_work_tree {
+                context.stroke()?;
+            } else {
+                context.fill()?;
+            }
         }
 
     }
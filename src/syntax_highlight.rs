@@ -0,0 +1,321 @@
+// a keyword, string and comment scanner with a few bundled word lists, not a real grammar:
+// it does not know raw strings, multi-line or triple-quoted strings, nested block comments
+// or Rust char literals and lifetimes, and languages it has no list for are not highlighted
+
+use std::path::Path;
+
+use git2::{AttrCheckFlags, AttrValue, Repository};
+
+// larger diffs are shown without highlighting
+pub const MAX_SYNTAX_LINES: usize = 3000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TokenKind {
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+// char offsets in a line
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SyntaxSpan {
+    pub start: usize,
+    pub end: usize,
+    pub kind: TokenKind,
+}
+
+// keywords, comments and string quotes of a language
+pub struct Language {
+    pub name: &'static str,
+    aliases: &'static [&'static str],
+    extensions: &'static [&'static str],
+    keywords: &'static [&'static str],
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+}
+
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "char", "class", "const", "continue", "default", "delete", "do",
+    "double", "else", "enum", "explicit", "extern", "false", "float", "for", "friend", "goto",
+    "if", "inline", "int", "long", "namespace", "new", "nullptr", "operator", "private",
+    "protected", "public", "register", "return", "short", "signed", "sizeof", "static",
+    "struct", "switch", "template", "this", "throw", "true", "try", "catch", "typedef",
+    "typename", "union", "unsigned", "using", "virtual", "void", "volatile", "while",
+];
+
+static LANGUAGES: &[Language] = &[
+    Language {
+        name: "rust",
+        aliases: &["rs"],
+        extensions: &["rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+    },
+    Language {
+        name: "cpp",
+        aliases: &["c", "c++"],
+        extensions: &["c", "h", "cc", "cpp", "cxx", "hpp", "hh", "hxx"],
+        keywords: C_KEYWORDS,
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Language {
+        name: "java",
+        aliases: &[],
+        extensions: &["java"],
+        keywords: &[
+            "abstract", "boolean", "break", "byte", "case", "catch", "char", "class", "const",
+            "continue", "default", "do", "double", "else", "enum", "extends", "false", "final",
+            "finally", "float", "for", "if", "implements", "import", "instanceof", "int",
+            "interface", "long", "new", "null", "package", "private", "protected", "public",
+            "return", "short", "static", "super", "switch", "this", "throw", "throws", "true",
+            "try", "var", "void", "while",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Language {
+        name: "javascript",
+        aliases: &["js", "typescript", "ts"],
+        extensions: &["js", "mjs", "cjs", "jsx", "ts", "tsx"],
+        keywords: &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue", "default",
+            "delete", "do", "else", "export", "extends", "false", "finally", "for", "from",
+            "function", "if", "import", "in", "instanceof", "interface", "let", "new", "null",
+            "of", "return", "static", "super", "switch", "this", "throw", "true", "try", "type",
+            "typeof", "undefined", "var", "void", "while", "yield",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+    },
+    Language {
+        name: "golang",
+        aliases: &["go"],
+        extensions: &["go"],
+        keywords: &[
+            "break", "case", "chan", "const", "continue", "default", "defer", "else",
+            "fallthrough", "false", "for", "func", "go", "goto", "if", "import", "interface",
+            "map", "nil", "package", "range", "return", "select", "struct", "switch", "true",
+            "type", "var",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+    },
+    Language {
+        name: "python",
+        aliases: &["py"],
+        extensions: &["py", "pyw"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+            "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return",
+            "True", "try", "while", "with", "yield",
+        ],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Language {
+        name: "ruby",
+        aliases: &["rb"],
+        extensions: &["rb", "rake"],
+        keywords: &[
+            "begin", "break", "case", "class", "def", "do", "else", "elsif", "end", "ensure",
+            "false", "for", "if", "in", "module", "next", "nil", "not", "or", "and", "redo",
+            "rescue", "retry", "return", "self", "super", "then", "true", "unless", "until",
+            "when", "while", "yield",
+        ],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Language {
+        name: "bash",
+        aliases: &["sh", "shell"],
+        extensions: &["sh", "bash", "zsh"],
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+            "in", "local", "return", "then", "until", "while",
+        ],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Language {
+        name: "css",
+        aliases: &["scss"],
+        extensions: &["css", "scss"],
+        keywords: &["important", "media", "import"],
+        line_comment: None,
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Language {
+        name: "toml",
+        aliases: &[],
+        extensions: &["toml"],
+        keywords: &["true", "false", "null"],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Language {
+        name: "html",
+        aliases: &["xml"],
+        extensions: &["html", "htm", "xml", "ui", "svg"],
+        keywords: &[],
+        line_comment: None,
+        block_comment: Some(("<!--", "-->")),
+        quotes: &['"'],
+    },
+];
+
+pub fn language_by_name(name: &str) -> Option<&'static Language> {
+    let name = name.to_lowercase();
+    LANGUAGES.iter().find(|x| x.name == name || x.aliases.contains(&name.as_str()))
+}
+
+pub fn language_for_path(path: &Path) -> Option<&'static Language> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    LANGUAGES.iter().find(|x| x.extensions.contains(&extension.as_str()))
+}
+
+// "linguist-language" or a diff driver like "diff=python" in .gitattributes, then the extension
+pub fn language_for_file(repo: &Repository, path_in_repository: &Path) -> Option<&'static Language> {
+    for name in &["linguist-language", "diff"] {
+        let value = repo.get_attr(path_in_repository, name, AttrCheckFlags::FILE_THEN_INDEX)
+                        .ok()
+                        .flatten();
+        if let AttrValue::String(value) = AttrValue::from_string(value) {
+            if let Some(language) = language_by_name(value) {
+                return Some(language);
+            }
+        }
+    }
+
+    language_for_path(path_in_repository)
+}
+
+// keeps whether a block comment continues to the next line
+pub struct Highlighter {
+    language: &'static Language,
+    in_block_comment: bool,
+}
+
+impl Highlighter {
+    pub fn new(language: &'static Language) -> Highlighter {
+        Highlighter { language, in_block_comment: false }
+    }
+
+    pub fn highlight_line(&mut self, line: &str) -> Vec<SyntaxSpan> {
+        let chars = line.trim_end_matches('\n').chars().collect::<Vec<_>>();
+        let mut spans = Vec::new();
+        let mut push = |start: usize, end: usize, kind: TokenKind| {
+            if start < end {
+                spans.push(SyntaxSpan { start, end, kind });
+            }
+        };
+
+        let mut i = 0;
+        if self.in_block_comment {
+            let (_, block_end) = self.language.block_comment.unwrap();
+            match find(&chars, 0, block_end) {
+                Some(end) => {
+                    push(0, end, TokenKind::Comment);
+                    self.in_block_comment = false;
+                    i = end;
+                }
+                None => {
+                    push(0, chars.len(), TokenKind::Comment);
+                    return spans;
+                }
+            }
+        }
+
+        while i < chars.len() {
+            let c = chars[i];
+
+            if self.language.line_comment.is_some_and(|x| starts_with_at(&chars, i, x)) {
+                push(i, chars.len(), TokenKind::Comment);
+                break;
+            }
+
+            if let Some((block_start, block_end)) = self.language.block_comment {
+                if starts_with_at(&chars, i, block_start) {
+                    match find(&chars, i + block_start.chars().count(), block_end) {
+                        Some(end) => {
+                            push(i, end, TokenKind::Comment);
+                            i = end;
+                        }
+                        None => {
+                            push(i, chars.len(), TokenKind::Comment);
+                            self.in_block_comment = true;
+                            break;
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            if self.language.quotes.contains(&c) {
+                let mut end = i + 1;
+                while end < chars.len() && chars[end] != c {
+                    end += if chars[end] == '\\' { 2 } else { 1 };
+                }
+                let end = (end + 1).min(chars.len());
+                push(i, end, TokenKind::String);
+                i = end;
+                continue;
+            }
+
+            if c.is_alphanumeric() || c == '_' {
+                let mut end = i + 1;
+                while end < chars.len() && (chars[end].is_alphanumeric() || chars[end] == '_') {
+                    end += 1;
+                }
+                if c.is_ascii_digit() {
+                    // like 1.5e3 or 0xff
+                    while end < chars.len() && (chars[end].is_alphanumeric() || chars[end] == '.') {
+                        end += 1;
+                    }
+                    push(i, end, TokenKind::Number);
+                } else {
+                    let word = chars[i..end].iter().collect::<String>();
+                    if self.language.keywords.contains(&word.as_str()) {
+                        push(i, end, TokenKind::Keyword);
+                    }
+                }
+                i = end;
+                continue;
+            }
+
+            i += 1;
+        }
+
+        spans
+    }
+}
+
+fn starts_with_at(chars: &[char], index: usize, pattern: &str) -> bool {
+    pattern.chars().enumerate().all(|(i, c)| chars.get(index + i) == Some(&c))
+}
+
+// the offset after the pattern
+fn find(chars: &[char], from: usize, pattern: &str) -> Option<usize> {
+    (from..chars.len())
+        .find(|&i| starts_with_at(chars, i, pattern))
+        .map(|i| i + pattern.chars().count())
+}
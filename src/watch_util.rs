@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+
+use git2::Repository;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ChangeKind {
+    Refs,
+    Index,
+    WorkTree,
+}
+
+// changes gathered while debouncing
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Changes {
+    pub refs: bool,
+    pub index: bool,
+    pub work_tree: bool,
+}
+
+impl Changes {
+    pub fn add(&mut self, kind: ChangeKind) {
+        match kind {
+            ChangeKind::Refs => self.refs = true,
+            ChangeKind::Index => self.index = true,
+            ChangeKind::WorkTree => self.work_tree = true,
+        }
+    }
+
+    pub fn merge(&mut self, other: &Changes) {
+        self.refs |= other.refs;
+        self.index |= other.index;
+        self.work_tree |= other.work_tree;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !(self.refs || self.index || self.work_tree)
+    }
+}
+
+// files directly under .git which point to commits
+const GIT_DIR_REF_FILES: [&str; 5] = ["HEAD", "packed-refs", "ORIG_HEAD", "MERGE_HEAD", "FETCH_HEAD"];
+
+// returns None for changes which do not affect the windows, like objects, lock files and ignored files
+pub fn classify_change(repo: &Repository, path: &Path) -> Option<ChangeKind> {
+    if path.extension().is_some_and(|x| x == "lock") {
+        return None;
+    }
+
+    if let Ok(relative) = path.strip_prefix(repo.path()) {
+        let first = relative.components().next()?.as_os_str().to_str()?;
+        return if first == "refs" || GIT_DIR_REF_FILES.contains(&first) {
+            Some(ChangeKind::Refs)
+        } else if first == "index" {
+            Some(ChangeKind::Index)
+        } else {
+            None
+        };
+    }
+
+    let relative = path.strip_prefix(repo.workdir()?).ok()?;
+    if relative.as_os_str().is_empty() || repo.is_path_ignored(relative).unwrap_or(false) {
+        return None;
+    }
+
+    Some(ChangeKind::WorkTree)
+}
+
+// directories to watch without recursion: the .git directory, refs and the work tree except ignored ones
+pub fn watched_directories(repo: &Repository) -> Vec<PathBuf> {
+    let mut dirs = vec![repo.path().to_path_buf()];
+    collect_directories(repo, &repo.path().join("refs"), &mut dirs);
+
+    if let Some(work_dir) = repo.workdir() {
+        collect_directories(repo, work_dir, &mut dirs);
+    }
+
+    dirs
+}
+
+// collects the directory and its subdirectories
+pub fn collect_directories(repo: &Repository, dir: &Path, dirs: &mut Vec<PathBuf>) {
+    // .git in the work tree is watched separately
+    if dir == repo.path() || is_ignored_directory(repo, dir) {
+        return;
+    }
+
+    let entries = match dir.read_dir() {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    dirs.push(dir.to_path_buf());
+
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|x| x.is_dir()) {
+            collect_directories(repo, &entry.path(), dirs);
+        }
+    }
+}
+
+fn is_ignored_directory(repo: &Repository, dir: &Path) -> bool {
+    match repo.workdir().and_then(|x| dir.strip_prefix(x).ok()) {
+        Some(relative) if !relative.as_os_str().is_empty() && !dir.starts_with(repo.path()) => {
+            repo.is_path_ignored(relative).unwrap_or(false)
+        }
+        _ => false,
+    }
+}
@@ -1,60 +1,148 @@
 use std::rc::Rc;
 use std::cell::RefCell;
-use crate::history_window::HistoryWindow;
-use crate::commit_window::CommitWindow;
-use crate::repository_manager::RepositoryManager;
+use git2::Error;
+use gtk::traits::WidgetExt;
+use crate::gtk_utils;
+use crate::session::Session;
+use crate::start_window::StartWindow;
+use crate::recent_repositories::RecentRepositories;
 
 pub struct WindowManager {
-    windows: RefCell<Option<Windows>>,
-}
-
-struct Windows {
-    history_window: Rc<HistoryWindow>,
-    commit_window: Rc<CommitWindow>,
+    sessions: RefCell<Vec<Rc<Session>>>,
+    start_window: Rc<StartWindow>,
+    recent_repositories: Option<RecentRepositories>,
 }
 
 impl WindowManager {
-    pub fn new(repository_manager: Rc<RepositoryManager>) -> Rc<WindowManager> {
-        let window_manager = Rc::new(WindowManager { windows: RefCell::new(None) });
+    pub fn new() -> Rc<WindowManager> {
+        let recent_repositories = RecentRepositories::default_file_path().map(|x| RecentRepositories::new(&x));
+        let window_manager = Rc::new(WindowManager {
+            sessions: RefCell::new(Vec::new()),
+            start_window: StartWindow::new(),
+            recent_repositories,
+        });
 
-        let windows = Windows {
-            history_window: HistoryWindow::new(Rc::downgrade(&window_manager),
-                                               repository_manager.clone()),
-            commit_window: CommitWindow::new(repository_manager.clone()),
-        };
+        let w = Rc::downgrade(&window_manager);
+        window_manager.start_window.connect_closed(move || {
+            w.upgrade().unwrap().quit_if_no_window();
+        });
 
-        *window_manager.windows.borrow_mut() = Some(windows);
+        let w = Rc::downgrade(&window_manager);
+        window_manager.start_window.connect_open_requested(move |path| {
+            let w = w.upgrade().unwrap();
+            match path {
+                Some(path) => w.open_repository_or_show_error(&path),
+                None => w.choose_repository(),
+            }
+        });
 
         window_manager
     }
 
-    fn with_windows<F>(&self, func: F)
-        where F: Fn(&Windows)
-    {
-        let windows_ref = self.windows.borrow();
-        let windows = windows_ref.as_ref().unwrap();
-        func(windows);
+    // paths are given in the arguments
+    pub fn start(self: &Rc<Self>, paths: &[String]) {
+        for path in paths {
+            self.open_repository_or_show_error(path);
+        }
+
+        // the current directory is tried silently
+        if paths.is_empty() && self.open_repository(".").is_err() {
+            self.show_start_window(None);
+        }
     }
 
-    pub fn start(&self) {
-        self.with_windows(|windows| {
-            windows.history_window.connect_closed(|| {
-                gtk::main_quit();
-            });
+    fn recent_paths(&self) -> Vec<String> {
+        self.recent_repositories.as_ref().map(|x| x.list()).unwrap_or_default()
+    }
 
-            windows.history_window.show();
-        });
+    fn show_start_window(&self, error: Option<&str>) {
+        self.start_window.show(error, &self.recent_paths());
+    }
+
+    fn open_repository_or_show_error(self: &Rc<Self>, path: &str) {
+        if let Err(err) = self.open_repository(path) {
+            if self.sessions.borrow().is_empty() {
+                self.show_start_window(Some(&format!("{}\n{}", path, err.message())));
+            } else {
+                gtk_utils::message_box_error(&format!("Failed to open the repository: {}", err.message()));
+            }
+        }
     }
 
-    pub fn show_commit_window(&self) {
-        self.with_windows(|windows| {
-            // TODO: messaging should be done in another class
-            let w = Rc::downgrade(&windows.history_window);
-            windows.commit_window.connect_commited(move || {
-                w.upgrade().unwrap().refresh();
-            });
+    // opens a new session, or presents the session already opened
+    pub fn open_repository(self: &Rc<Self>, path: &str) -> Result<(), Error> {
+        let repo = git2::Repository::discover(path)?;
+        let work_dir = repo.workdir()
+                           .and_then(|x| x.to_str())
+                           .map(|x| x.trim_end_matches('/').to_string())
+                           .ok_or_else(|| Error::from_str("Bare repositories are not supported"))?;
+
+        if let Some(recent_repositories) = self.recent_repositories.as_ref() {
+            dialog_when_error!("Failed to save recent repositories: {}", recent_repositories.add(&work_dir));
+        }
 
-            windows.commit_window.show();
+        self.start_window.hide();
+
+        let existing = self.sessions.borrow().iter().find(|x| x.work_dir_path() == work_dir).cloned();
+        if let Some(session) = existing {
+            session.present();
+            return Ok(());
+        }
+
+        let session = Session::new(Rc::downgrade(self), &work_dir);
+        let w = Rc::downgrade(self);
+        let s = Rc::downgrade(&session);
+        session.connect_closed(move || {
+            w.upgrade().unwrap().close_session(&s.upgrade().unwrap());
         });
+
+        self.sessions.borrow_mut().push(Rc::clone(&session));
+        self.update_windows_menus();
+        session.show();
+
+        Ok(())
+    }
+
+    fn close_session(&self, session: &Rc<Session>) {
+        session.close();
+        self.sessions.borrow_mut().retain(|x| !Rc::ptr_eq(x, session));
+        self.update_windows_menus();
+
+        self.quit_if_no_window();
+    }
+
+    fn quit_if_no_window(&self) {
+        if self.sessions.borrow().is_empty() && !self.start_window.window().is_visible() {
+            gtk::main_quit();
+        }
+    }
+
+    // asks a directory with the file chooser
+    pub fn choose_repository(self: &Rc<Self>) {
+        let parent = match self.sessions.borrow().last() {
+            Some(session) => session.history_window(),
+            None => self.start_window.window(),
+        };
+
+        if let Some(path) = gtk_utils::choose_directory("Open repository", Some(&parent)) {
+            if let Some(path) = path.to_str() {
+                self.open_repository_or_show_error(path);
+            }
+        }
+    }
+
+    fn update_windows_menus(&self) {
+        let sessions = self.sessions.borrow();
+        let paths = sessions.iter().map(|x| x.work_dir_path()).collect::<Vec<_>>();
+        for session in sessions.iter() {
+            session.update_windows_menu(&paths);
+        }
+    }
+
+    pub fn present_session(&self, work_dir_path: &str) {
+        let session = self.sessions.borrow().iter().find(|x| x.work_dir_path() == work_dir_path).cloned();
+        if let Some(session) = session {
+            session.present();
+        }
     }
 }
@@ -0,0 +1,73 @@
+extern crate git2;
+extern crate tempdir;
+extern crate metal_git;
+
+mod util;
+use crate::util::test_repo::TestRepo;
+use metal_git::blame_util;
+
+// f.txt at B: "hello" from A, "foo()" from B
+fn blame_at_b(test_repo: &TestRepo) -> blame_util::BlameResult {
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+    let b_oid = repo.revparse_single("HEAD~2").unwrap().id();
+    blame_util::blame_file(&test_repo.repository_manager(), "f.txt", b_oid).unwrap()
+}
+
+#[test]
+pub fn blame_file() {
+    let test_repo = TestRepo::file_history();
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+    let summary = |oid: git2::Oid| repo.find_commit(oid).unwrap().summary().unwrap().to_string();
+
+    let result = blame_at_b(&test_repo);
+    assert_eq!("f.txt", result.path);
+    assert_eq!("B", summary(result.revision));
+
+    let chunks = result.chunks
+                       .iter()
+                       .map(|x| (summary(x.commit_oid), x.start_line, x.lines.clone()))
+                       .collect::<Vec<_>>();
+    assert_eq!(vec![
+        ("A".to_string(), 1, vec!["hello".to_string()]),
+        ("B".to_string(), 2, vec!["foo()".to_string()]),
+    ], chunks);
+    assert_eq!(Some("f.txt"), result.chunks[0].orig_path.as_deref());
+
+    // the line removed by D is gone
+    let head_oid = repo.head().unwrap().target().unwrap();
+    let result = blame_util::blame_file(&test_repo.repository_manager(), "f.txt", head_oid).unwrap();
+    assert_eq!(1, result.chunks.len());
+    assert_eq!("A", summary(result.chunks[0].commit_oid));
+
+    // g.txt does not exist at B
+    let b_oid = repo.revparse_single("HEAD~2").unwrap().id();
+    assert!(blame_util::blame_file(&test_repo.repository_manager(), "g.txt", b_oid).is_err());
+}
+
+#[test]
+pub fn parent_revision() {
+    let test_repo = TestRepo::file_history();
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+    let result = blame_at_b(&test_repo);
+    let a_oid = repo.revparse_single("HEAD~3").unwrap().id();
+
+    // the root commit has no parent to blame
+    assert_eq!(None, blame_util::parent_revision(&test_repo.repository_manager(), &result.chunks[0]).unwrap());
+    assert_eq!(Some((a_oid, "f.txt".to_string())),
+               blame_util::parent_revision(&test_repo.repository_manager(), &result.chunks[1]).unwrap());
+}
+
+#[test]
+pub fn age_ratio() {
+    let test_repo = TestRepo::file_history();
+    let result = blame_at_b(&test_repo);
+
+    assert_eq!(0.0, result.age_ratio(&result.chunks[0]));
+    assert_eq!(1.0, result.age_ratio(&result.chunks[1]));
+
+    // a single commit counts as the newest
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+    let head_oid = repo.head().unwrap().target().unwrap();
+    let result = blame_util::blame_file(&test_repo.repository_manager(), "f.txt", head_oid).unwrap();
+    assert_eq!(1.0, result.age_ratio(&result.chunks[0]));
+}
@@ -0,0 +1,212 @@
+extern crate git2;
+extern crate tempdir;
+extern crate metal_git;
+
+mod util;
+use crate::util::test_repo::TestRepo;
+use metal_git::commit_diff_util;
+use metal_git::commit_diff_util::{DiffSettings, WhitespaceMode};
+
+#[test]
+pub fn list_commit_diff_files_root_commit() {
+    let test_repo = TestRepo::single();
+    let repository_manager = test_repo.repository_manager();
+
+    let repo = repository_manager.open().unwrap();
+    let oid = repo.head().unwrap().target().unwrap();
+
+    let result = commit_diff_util::list_commit_diff_files(&repository_manager, oid, &DiffSettings::default()).unwrap();
+
+    assert_eq!(oid, result.current_oid);
+    assert_eq!(None, result.parent_oid);
+    assert!(!result.combined);
+    assert_eq!(0, result.files.len());
+}
+
+#[test]
+pub fn list_commit_diff_files_non_root_commit() {
+    let test_repo = TestRepo::flat_two();
+    let repository_manager = test_repo.repository_manager();
+
+    let repo = repository_manager.open().unwrap();
+    let b = repo.head().unwrap().peel_to_commit().unwrap();
+    let a = b.parent_id(0).unwrap();
+
+    let result = commit_diff_util::list_commit_diff_files(&repository_manager, b.id(), &DiffSettings::default()).unwrap();
+    assert_eq!(Some(a), result.parent_oid);
+
+    let result = commit_diff_util::list_commit_diff_files(&repository_manager, a, &DiffSettings::default()).unwrap();
+    assert_eq!(None, result.parent_oid);
+}
+
+#[test]
+pub fn list_tree_diff_files() {
+    let test_repo = TestRepo::file_history();
+    let repository_manager = test_repo.repository_manager();
+
+    let repo = repository_manager.open().unwrap();
+    let a = repo.revparse_single("HEAD~3").unwrap().id();
+    let c = repo.revparse_single("HEAD~1").unwrap().id();
+
+    let paths = |result: &commit_diff_util::ListCommitDiffResult| {
+        result.files.iter()
+            .map(|x| (x.status, x.old_file_path.clone(), x.new_file_path.clone()))
+            .collect::<Vec<_>>()
+    };
+    let path = |x: &str| Some(x.to_string());
+
+    let result = commit_diff_util::list_tree_diff_files(&repository_manager, Some(a), c, &DiffSettings::default()).unwrap();
+    assert_eq!(c, result.current_oid);
+    assert_eq!(Some(a), result.parent_oid);
+    assert!(!result.combined);
+    assert!(!result.work_tree);
+    assert_eq!(vec![('M', path("f.txt"), path("f.txt")), ('A', path("g.txt"), path("g.txt"))], paths(&result));
+    assert_eq!((2, 0), (result.insertions, result.deletions));
+
+    // the swapped direction reverses the changes
+    let result = commit_diff_util::list_tree_diff_files(&repository_manager, Some(c), a, &DiffSettings::default()).unwrap();
+    assert_eq!(a, result.current_oid);
+    assert_eq!(Some(c), result.parent_oid);
+    assert_eq!(vec![('M', path("f.txt"), path("f.txt")), ('D', path("g.txt"), path("g.txt"))], paths(&result));
+    assert_eq!((0, 2), (result.insertions, result.deletions));
+}
+
+#[test]
+pub fn list_work_tree_diff_files() {
+    let test_repo = TestRepo::file_history();
+    let repository_manager = test_repo.repository_manager();
+
+    // staged, unstaged and untracked changes are listed together
+    std::fs::write(test_repo.path().join("f.txt"), "staged\n").unwrap();
+    let repo = repository_manager.open().unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(std::path::Path::new("f.txt")).unwrap();
+    index.write().unwrap();
+    std::fs::write(test_repo.path().join("g.txt"), "unstaged\n").unwrap();
+    std::fs::write(test_repo.path().join("h.txt"), "untracked\n").unwrap();
+
+    let result = commit_diff_util::list_work_tree_diff_files(&repository_manager, &DiffSettings::default()).unwrap();
+
+    assert!(result.work_tree);
+    assert!(result.current_oid.is_zero());
+    assert_eq!(repo.head().unwrap().target(), result.parent_oid);
+
+    let files = result.files.iter().map(|x| x.format_file_move()).collect::<Vec<_>>();
+    assert_eq!(vec!["f.txt", "g.txt", "h.txt"], files);
+}
+
+#[test]
+pub fn list_work_tree_diff_files_renames() {
+    let test_repo = TestRepo::file_history();
+    let repository_manager = test_repo.repository_manager();
+
+    std::fs::rename(test_repo.path().join("g.txt"), test_repo.path().join("h.txt")).unwrap();
+
+    let result = commit_diff_util::list_work_tree_diff_files(&repository_manager, &DiffSettings::default()).unwrap();
+    let files = result.files.iter().map(|x| x.format_file_move()).collect::<Vec<_>>();
+    assert_eq!(vec!["g.txt -> h.txt"], files);
+    assert_eq!(vec!["g.txt", "h.txt"], result.files[0].paths());
+
+    let settings = DiffSettings { renames: false, ..DiffSettings::default() };
+    let result = commit_diff_util::list_work_tree_diff_files(&repository_manager, &settings).unwrap();
+    let files = result.files.iter().map(|x| x.format_file_move()).collect::<Vec<_>>();
+    assert_eq!(vec!["g.txt", "h.txt"], files);
+}
+
+#[test]
+pub fn diff_work_tree_whitespace() {
+    let test_repo = TestRepo::file_history();
+    let repo = test_repo.repository_manager().open().unwrap();
+
+    std::fs::write(test_repo.path().join("f.txt"), "hello  \n").unwrap();
+    let hunk_count = |settings: &DiffSettings| {
+        let diff = commit_diff_util::diff_work_tree(&repo, &["f.txt"], settings).unwrap();
+        git2::Patch::from_diff(&diff, 0).unwrap().map(|x| x.num_hunks()).unwrap_or(0)
+    };
+
+    assert_eq!(1, hunk_count(&DiffSettings::default()));
+    assert_eq!(0, hunk_count(&DiffSettings { whitespace: WhitespaceMode::IgnoreEol, ..DiffSettings::default() }));
+}
+
+#[test]
+pub fn list_commit_diff_files_stats() {
+    let test_repo = TestRepo::file_history();
+    let repository_manager = test_repo.repository_manager();
+
+    let repo = repository_manager.open().unwrap();
+    let d = repo.head().unwrap().peel_to_commit().unwrap();
+    let c = d.parent_id(0).unwrap();
+
+    let result = commit_diff_util::list_commit_diff_files(&repository_manager, d.id(), &DiffSettings::default()).unwrap();
+    let file = &result.files[0];
+    assert_eq!(('M', 0, 1), (file.status, file.insertions, file.deletions));
+    assert_eq!("1 file changed, 0 insertions(+), 1 deletion(-)", result.format_summary());
+
+    let result = commit_diff_util::list_commit_diff_files(&repository_manager, c, &DiffSettings::default()).unwrap();
+    let file = &result.files[0];
+    assert_eq!(('A', 1, 0), (file.status, file.insertions, file.deletions));
+    assert_eq!((1, 0), (result.insertions, result.deletions));
+}
+
+#[test]
+pub fn stat_bar() {
+    assert_eq!((3, 2), commit_diff_util::stat_bar(3, 2, 10, 20));
+    assert_eq!((20, 0), commit_diff_util::stat_bar(100, 0, 100, 20));
+    // small changes keep a column when scaled
+    assert_eq!((1, 10), commit_diff_util::stat_bar(1, 50, 100, 20));
+}
+
+#[test]
+pub fn combined_diff() {
+    let test_repo = TestRepo::merge_with_contents();
+    let repository_manager = test_repo.repository_manager();
+
+    let repo = repository_manager.open().unwrap();
+    let oid = repo.head().unwrap().target().unwrap();
+
+    let files = commit_diff_util::combined_diff(&repository_manager, oid, None, &DiffSettings::default()).unwrap();
+
+    // only_b.txt is the same as in the second parent
+    let paths = files.iter().map(|x| x.path.as_str()).collect::<Vec<_>>();
+    assert_eq!(vec!["both.txt", "image.bin", "tail.txt"], paths);
+
+    // unchanged files are not taken as changed when copies are detected
+    let settings = DiffSettings { copies: true, ..DiffSettings::default() };
+    let copies_files = commit_diff_util::combined_diff(&repository_manager, oid, None, &settings).unwrap();
+    assert_eq!(paths, copies_files.iter().map(|x| x.path.as_str()).collect::<Vec<_>>());
+
+    let lines = |file: &commit_diff_util::CombinedDiffFile| {
+        file.hunks[0].lines.iter()
+                           .map(|x| (x.origins.iter().collect::<String>(), x.content.clone()))
+                           .collect::<Vec<_>>()
+    };
+    let line = |origins: &str, content: &str| (origins.to_string(), content.to_string());
+
+    // changed against both parents like a resolved conflict
+    assert_eq!(1, files[0].hunks.len());
+    assert_eq!("@@@ -1,7 -1,7 +1,7 @@@", files[0].hunks[0].header);
+    assert_eq!(vec![
+        line("  ", "a"),
+        line("  ", "b"),
+        line("  ", "c"),
+        line("- ", "C-side"),
+        line(" -", "B-side"),
+        line("++", "merged"),
+        line("  ", "e"),
+        line("  ", "f"),
+        line("  ", "g"),
+    ], lines(&files[0]));
+
+    assert!(files[1].is_binary);
+    assert!(files[1].hunks.is_empty());
+
+    // deleted at the end of the file
+    assert!(!files[2].is_binary);
+    assert_eq!("@@@ -1,3 -1,3 +1,2 @@@", files[2].hunks[0].header);
+    assert_eq!(vec![
+        line("  ", "x"),
+        line("  ", "y"),
+        line("- ", "z"),
+        line(" -", "z"),
+    ], lines(&files[2]));
+}
@@ -0,0 +1,239 @@
+extern crate git2;
+extern crate tempdir;
+extern crate metal_git;
+
+mod util;
+use std::fs;
+use std::path::Path;
+
+use crate::util::test_repo::TestRepo;
+use metal_git::diff_content::{self, DiffTextLine, FileContent, InlineRow, SplitRow, WordDiff, WordSegment};
+use metal_git::syntax_highlight::TokenKind;
+
+#[test]
+pub fn decode_text() {
+    assert_eq!("héllo\n", diff_content::decode_text("héllo\n".as_bytes()));
+    assert_eq!("bom", diff_content::decode_text(b"\xef\xbb\xbfbom"));
+    assert_eq!("hi", diff_content::decode_text(b"\xff\xfeh\0i\0"));
+    assert_eq!("hi", diff_content::decode_text(b"\xfe\xff\0h\0i"));
+    // Latin-1
+    assert_eq!("caf\u{e9}", diff_content::decode_text(b"caf\xe9"));
+}
+
+#[test]
+pub fn is_binary_content() {
+    assert!(!diff_content::is_binary_content(b"text\n"));
+    assert!(diff_content::is_binary_content(b"\x89PNG\0\0"));
+    assert!(!diff_content::is_binary_content(b"\xff\xfeh\0i\0"));
+
+    let mut late_nul = vec![b'a'; 9000];
+    late_nul.push(0);
+    assert!(!diff_content::is_binary_content(&late_nul));
+}
+
+#[test]
+pub fn collect_diff_text() {
+    let test_repo = TestRepo::file_history();
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+
+    fs::write(test_repo.path().join("f.txt"), b"hello\ncaf\xe9\n").unwrap();
+    fs::write(test_repo.path().join("g.txt"), b"wor\0ld\n").unwrap();
+    let diff = repo.diff_index_to_workdir(None, None).unwrap();
+
+    let text = diff_content::collect_diff_text(&repo, &diff, None).unwrap();
+    assert_eq!(0, text.hidden_lines);
+    assert!(text.lines.iter().any(|x| x.origin == '+' && x.text == "+caf\u{e9}\n"));
+    assert!(text.lines.iter().any(|x| x.text == "Binary file g.txt, 7 bytes\n"));
+
+    let added = text.lines.iter().find(|x| x.origin == '+').unwrap();
+    assert_eq!((None, Some(2)), (added.old_lineno, added.new_lineno));
+    let context = text.lines.iter().find(|x| x.origin == ' ').unwrap();
+    assert_eq!((Some(1), Some(1)), (context.old_lineno, context.new_lineno));
+
+    let limited = diff_content::collect_diff_text(&repo, &diff, Some(3)).unwrap();
+    assert_eq!(3, limited.lines.len());
+    assert_eq!(text.lines.len() - 3, limited.hidden_lines);
+}
+
+#[test]
+pub fn read_work_tree_file() {
+    let test_repo = TestRepo::file_history();
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+
+    fs::write(test_repo.path().join("new.txt"), "0123456789").unwrap();
+    fs::write(test_repo.path().join("image.png"), b"\x89PNG\0").unwrap();
+    fs::write(test_repo.path().join(".gitattributes"), "*.dat binary\n").unwrap();
+    fs::write(test_repo.path().join("text.dat"), "looks like text").unwrap();
+
+    match diff_content::read_work_tree_file(&repo, Path::new("new.txt"), Some(4)).unwrap() {
+        FileContent::Text { text, hidden_bytes } => {
+            assert_eq!("0123", text);
+            assert_eq!(6, hidden_bytes);
+        }
+        FileContent::Binary { .. } => panic!("new.txt is not binary"),
+    }
+
+    fs::write(test_repo.path().join("utf8.txt"), "aé").unwrap();
+    match diff_content::read_work_tree_file(&repo, Path::new("utf8.txt"), Some(2)).unwrap() {
+        FileContent::Text { text, hidden_bytes } => {
+            assert_eq!("a", text);
+            assert_eq!(2, hidden_bytes);
+        }
+        FileContent::Binary { .. } => panic!("utf8.txt is not binary"),
+    }
+
+    match diff_content::read_work_tree_file(&repo, Path::new("image.png"), None).unwrap() {
+        FileContent::Binary { size } => assert_eq!(5, size),
+        FileContent::Text { .. } => panic!("image.png is binary"),
+    }
+
+    assert!(matches!(diff_content::read_work_tree_file(&repo, Path::new("text.dat"), None).unwrap(),
+                     FileContent::Binary { size: 15 }));
+    assert!(diff_content::read_work_tree_file(&repo, Path::new("missing.txt"), None).is_err());
+}
+
+#[test]
+pub fn split_rows() {
+    let line = |origin: char, text: &str| DiffTextLine {
+        origin,
+        text: text.to_string(),
+        old_lineno: None,
+        new_lineno: None,
+    };
+    let lines = vec![
+        line('H', "@@ -1,4 +1,3 @@\n"),
+        line(' ', " a\n"),
+        line('-', "-b\n"),
+        line('-', "-c\n"),
+        line('+', "+B\n"),
+        line(' ', " d"),
+        line('=', "\n\\ No newline at end of file\n"),
+        line('+', "+e\n"),
+    ];
+
+    let changed = |old: Option<usize>, new: Option<usize>| SplitRow::Lines { old, new, changed: true };
+    let context = |i: usize| SplitRow::Lines { old: Some(i), new: Some(i), changed: false };
+
+    assert_eq!(vec![
+        SplitRow::Header(0),
+        context(1),
+        changed(Some(2), Some(4)),
+        changed(Some(3), None),
+        context(5),
+        changed(None, Some(7)),
+    ], diff_content::split_rows(&lines));
+}
+
+#[test]
+pub fn collapsed_lines() {
+    let line = |origin: char| DiffTextLine {
+        origin,
+        text: String::new(),
+        old_lineno: None,
+        new_lineno: None,
+    };
+    let lines = vec![line('F'), line('H'), line('+'), line('H'), line('-'), line('F'), line('H'), line(' ')];
+
+    let hidden = |collapsed: &[usize]| diff_content::collapsed_lines(&lines, &collapsed.iter().copied().collect());
+    assert_eq!(vec![false; 8], hidden(&[]));
+    assert_eq!(vec![false, false, true, false, false, false, false, false], hidden(&[1]));
+    assert_eq!(vec![false, true, true, true, true, false, false, false], hidden(&[0]));
+    assert_eq!(vec![false, false, false, false, false, false, true, true], hidden(&[5]));
+}
+
+#[test]
+pub fn word_diff() {
+    assert_eq!(vec![
+        WordSegment::Same("let ".to_string()),
+        WordSegment::Deleted("foo".to_string()),
+        WordSegment::Added("bar".to_string()),
+        WordSegment::Same(" = 1;".to_string()),
+    ], diff_content::word_segments("let foo = 1;", "let bar = 1;"));
+
+    // char ranges, not byte ranges
+    assert_eq!(WordDiff { old_ranges: vec![(2, 6)], new_ranges: vec![(2, 9)] },
+               diff_content::word_diff("é café", "é tea, ok"));
+}
+
+#[test]
+pub fn changed_word_ranges_and_inline_rows() {
+    let line = |origin: char, text: &str| DiffTextLine {
+        origin,
+        text: text.to_string(),
+        old_lineno: None,
+        new_lineno: None,
+    };
+    let lines = vec![
+        line(' ', " a\n"),
+        line('-', "-x = 1\n"),
+        line('-', "-y\n"),
+        line('+', "+x = 2\n"),
+    ];
+
+    let ranges = diff_content::changed_word_ranges(&lines);
+    assert_eq!(vec![vec![], vec![(4, 5)], vec![], vec![(4, 5)]], ranges);
+
+    assert_eq!(vec![
+        InlineRow::Line(0),
+        InlineRow::Words {
+            old: 1,
+            new: 3,
+            segments: vec![
+                WordSegment::Same("x = ".to_string()),
+                WordSegment::Deleted("1".to_string()),
+                WordSegment::Added("2".to_string()),
+            ],
+        },
+        InlineRow::Line(2),
+    ], diff_content::inline_rows(&lines));
+}
+
+#[test]
+pub fn syntax_spans() {
+    let test_repo = TestRepo::file_history();
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+
+    fs::write(test_repo.path().join("a.rs"), "/* old\n").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("a.rs")).unwrap();
+    index.write().unwrap();
+    fs::write(test_repo.path().join("a.rs"), "fn\n").unwrap();
+    let diff = repo.diff_index_to_workdir(None, None).unwrap();
+
+    let text = diff_content::collect_diff_text(&repo, &diff, None).unwrap();
+    assert_eq!(1, text.files.len());
+    assert_eq!("a.rs", text.files[0].path);
+    assert_eq!(Some("rust"), text.files[0].language.map(|x| x.name));
+
+    let spans = diff_content::syntax_spans(&text);
+    let deleted = text.lines.iter().position(|x| x.origin == '-').unwrap();
+    let added = text.lines.iter().position(|x| x.origin == '+').unwrap();
+    // the comment on the old side does not leak into the new side
+    assert_eq!(TokenKind::Comment, spans[deleted][0].kind);
+    assert_eq!(TokenKind::Keyword, spans[added][0].kind);
+}
+
+#[test]
+pub fn syntax_spans_per_hunk() {
+    let test_repo = TestRepo::file_history();
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+
+    let old = (1..=20).map(|x| format!("let a{} = 1;\n", x)).collect::<String>();
+    fs::write(test_repo.path().join("b.rs"), &old).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("b.rs")).unwrap();
+    index.write().unwrap();
+
+    // an unclosed comment in the first hunk, and a change far below it
+    let new = old.replace("let a2 = 1;", "/* let a2 = 1;").replace("let a19 = 1;", "fn a19() {}");
+    fs::write(test_repo.path().join("b.rs"), new).unwrap();
+    let diff = repo.diff_index_to_workdir(None, None).unwrap();
+
+    let text = diff_content::collect_diff_text(&repo, &diff, None).unwrap();
+    assert_eq!(2, text.lines.iter().filter(|x| x.origin == 'H').count());
+
+    let spans = diff_content::syntax_spans(&text);
+    let added = text.lines.iter().rposition(|x| x.origin == '+').unwrap();
+    assert_eq!("+fn a19() {}\n", text.lines[added].text);
+    assert_eq!(TokenKind::Keyword, spans[added][0].kind);
+}
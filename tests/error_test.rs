@@ -0,0 +1,59 @@
+extern crate git2;
+extern crate tempdir;
+extern crate metal_git;
+
+mod util;
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use git2::Oid;
+
+use crate::util::test_repo::TestRepo;
+use metal_git::error::{MetalGitError, ResultExt};
+use metal_git::blame_util;
+use metal_git::stash_util;
+
+#[test]
+pub fn display_with_context() {
+    let result = fs::read_to_string("/nonexistent/metal-git")
+        .path_context("Failed to read", Path::new("/nonexistent/metal-git"));
+    let err = result.unwrap_err();
+
+    assert!(err.to_string().starts_with("Failed to read /nonexistent/metal-git: "));
+    assert!(err.source().is_some());
+
+    let details = err.details();
+    assert!(details.starts_with("Failed to read\npath: /nonexistent/metal-git\n\n"));
+    assert!(details.contains("kind: NotFound"));
+}
+
+#[test]
+pub fn git_error_details() {
+    let oid = Oid::from_str("0123456789012345678901234567890123456789").unwrap();
+    let result: Result<(), git2::Error> = Err(git2::Error::new(git2::ErrorCode::NotFound,
+                                                               git2::ErrorClass::Odb,
+                                                               "object not found"));
+    let err = result.oid_context("Failed to show the commit", oid).unwrap_err();
+
+    assert_eq!(format!("Failed to show the commit {}: object not found", oid), err.to_string());
+    assert!(err.details().contains(&format!("commit: {}", oid)));
+    assert!(err.details().ends_with("object not found\nclass: Odb, code: NotFound"));
+
+    let result: Result<(), MetalGitError> = Err(MetalGitError::new("No branch"));
+    let err = result.context("Failed to push").unwrap_err();
+    assert_eq!("Failed to push: No branch", err.to_string());
+}
+
+#[test]
+pub fn util_error_context() {
+    let test_repo = TestRepo::file_history();
+    let repository_manager = test_repo.repository_manager();
+
+    let err = stash_util::apply_stash(&repository_manager, 0).unwrap_err();
+    assert!(err.to_string().starts_with("Failed to apply stash@{0}: "));
+
+    let err = blame_util::blame_file(&repository_manager, "missing.txt", Oid::zero()).err().unwrap();
+    assert!(err.details().contains(&format!("commit: {}", Oid::zero())));
+}
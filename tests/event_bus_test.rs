@@ -0,0 +1,44 @@
+extern crate metal_git;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use metal_git::event_bus::{EventBus, RepositoryEvent};
+
+#[test]
+pub fn publish_to_subscribers() {
+    let bus = EventBus::new();
+    let received = Rc::new(RefCell::new(Vec::new()));
+
+    let r = Rc::clone(&received);
+    let first = bus.subscribe(move |event| r.borrow_mut().push((1, event)));
+    let r = Rc::clone(&received);
+    bus.subscribe(move |event| r.borrow_mut().push((2, event)));
+
+    bus.publish(RepositoryEvent::HeadMoved);
+    assert_eq!(vec![(1, RepositoryEvent::HeadMoved), (2, RepositoryEvent::HeadMoved)], *received.borrow());
+
+    received.borrow_mut().clear();
+    bus.unsubscribe(first);
+    bus.publish_all(&[RepositoryEvent::RefsChanged, RepositoryEvent::IndexChanged]);
+    assert_eq!(vec![(2, RepositoryEvent::RefsChanged), (2, RepositoryEvent::IndexChanged)], *received.borrow());
+}
+
+#[test]
+pub fn publish_while_notified() {
+    let bus = EventBus::new();
+    let received = Rc::new(RefCell::new(Vec::new()));
+
+    // a head move changes the index too
+    let b = Rc::downgrade(&bus);
+    bus.subscribe(move |event| {
+        if event == RepositoryEvent::HeadMoved {
+            b.upgrade().unwrap().publish(RepositoryEvent::IndexChanged);
+        }
+    });
+    let r = Rc::clone(&received);
+    bus.subscribe(move |event| r.borrow_mut().push(event));
+
+    bus.publish(RepositoryEvent::HeadMoved);
+    assert_eq!(vec![RepositoryEvent::IndexChanged, RepositoryEvent::HeadMoved], *received.borrow());
+}
@@ -0,0 +1,61 @@
+extern crate git2;
+extern crate tempdir;
+extern crate metal_git;
+
+mod util;
+use crate::util::test_repo::TestRepo;
+use metal_git::history_filter;
+use metal_git::history_filter::{FilterField, HistoryFilter};
+use metal_git::railway;
+
+#[test]
+pub fn matches_fields() {
+    let test_repo = TestRepo::flat_two();
+
+    let stations = railway::collect_tree(&test_repo.repository_manager()).unwrap();
+    let (b, a) = (&stations[0], &stations[1]);
+
+    let filter = HistoryFilter::new(FilterField::Message, "a");
+    assert!(!filter.matches(b));
+    assert!(filter.matches(a));
+
+    let filter = HistoryFilter::new(FilterField::RefName, "MASTER");
+    assert!(filter.matches(b));
+    assert!(!filter.matches(a));
+
+    let sha_prefix = a.oid.to_string()[..7].to_string();
+    let filter = HistoryFilter::new(FilterField::Sha, &sha_prefix);
+    assert!(!filter.matches(b));
+    assert!(filter.matches(a));
+
+    let filter = HistoryFilter::new(FilterField::Author, "test commit");
+    assert!(filter.matches(b));
+    assert!(filter.matches(a));
+}
+
+#[test]
+pub fn matches_date_range() {
+    let test_repo = TestRepo::single();
+
+    let stations = railway::collect_tree(&test_repo.repository_manager()).unwrap();
+    let station = &stations[0];
+
+    let mut filter = HistoryFilter::new(FilterField::Message, "");
+    filter.since = Some(station.timestamp + 1);
+    assert!(!filter.matches(station));
+
+    filter.since = Some(station.timestamp);
+    filter.until = Some(station.timestamp + 1);
+    assert!(filter.matches(station));
+}
+
+#[test]
+pub fn parse_date() {
+    let since = history_filter::parse_date("2024-01-31").unwrap();
+    assert_eq!(Some(since + 24 * 60 * 60), history_filter::parse_date_end(" 2024-01-31 "));
+
+    // the search bar marks these as invalid
+    assert_eq!(None, history_filter::parse_date("2024/01/31"));
+    assert_eq!(None, history_filter::parse_date("yesterday"));
+    assert_eq!(None, history_filter::parse_date(""));
+}
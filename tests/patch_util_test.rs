@@ -0,0 +1,174 @@
+extern crate git2;
+extern crate tempdir;
+extern crate metal_git;
+
+mod util;
+use tempdir::TempDir;
+
+use crate::util::test_repo::TestRepo;
+use metal_git::patch_util;
+
+fn open_with_committer(test_repo: &TestRepo) -> git2::Repository {
+    test_repo.set_committer_config();
+    git2::Repository::open(test_repo.path()).unwrap()
+}
+
+fn commit_oids(repo: &git2::Repository) -> Vec<git2::Oid> {
+    let mut revwalk = repo.revwalk().unwrap();
+    revwalk.push_head().unwrap();
+    revwalk.map(|x| x.unwrap()).collect()
+}
+
+#[test]
+pub fn export_and_apply() {
+    let test_repo = TestRepo::file_history();
+    let repo = open_with_committer(&test_repo);
+    let repository_manager = test_repo.repository_manager();
+
+    // D, C, B, A
+    let oids = commit_oids(&repo);
+    let output_dir = TempDir::new("patches").unwrap();
+    let paths = patch_util::export_patches(&repository_manager,
+                                           &[oids[2], oids[1], oids[0]],
+                                           output_dir.path()).unwrap();
+
+    let file_names = paths.iter()
+                          .map(|x| x.file_name().unwrap().to_str().unwrap().to_string())
+                          .collect::<Vec<_>>();
+    assert_eq!(vec!["0001-B.patch", "0002-C.patch", "0003-D.patch"], file_names);
+
+    let patches = patch_util::read_mbox_files(&paths).unwrap();
+    assert_eq!(vec!["B", "C", "D"], patches.iter().map(|x| x.subject.as_str()).collect::<Vec<_>>());
+
+    patch_util::abort_apply(&repository_manager, oids[3]).unwrap();
+    let applied = patch_util::apply_patches(&repository_manager, &patches).unwrap();
+    assert_eq!(3, applied.len());
+
+    let original = repo.find_commit(oids[0]).unwrap();
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(original.tree_id(), head.tree_id());
+    assert_eq!(original.author().name(), head.author().name());
+    assert_eq!(original.author().when(), head.author().when());
+    assert_eq!(Some("test committer"), head.committer().name());
+}
+
+#[test]
+pub fn apply_conflict_and_abort() {
+    let test_repo = TestRepo::file_history();
+    let repo = open_with_committer(&test_repo);
+    let repository_manager = test_repo.repository_manager();
+
+    let oids = commit_oids(&repo);
+    let output_dir = TempDir::new("patches").unwrap();
+    let paths = patch_util::export_patches(&repository_manager, &[oids[1]], output_dir.path()).unwrap();
+    let patches = patch_util::read_mbox_files(&paths).unwrap();
+
+    // g.txt already exists
+    let orig_head = patch_util::head_oid(&repository_manager).unwrap().unwrap();
+    let result = patch_util::apply_patches(&repository_manager, &patches);
+    assert!(result.is_err());
+    assert!(result.err().unwrap().to_string().starts_with("Failed to apply patch 1/1 \"C\""));
+
+    patch_util::abort_apply(&repository_manager, orig_head).unwrap();
+    assert_eq!(oids, commit_oids(&repo));
+}
+
+#[test]
+pub fn abort_partially_applied() {
+    let test_repo = TestRepo::file_history();
+    let repo = open_with_committer(&test_repo);
+    let repository_manager = test_repo.repository_manager();
+
+    // D, C, B, A
+    let oids = commit_oids(&repo);
+    let output_dir = TempDir::new("patches").unwrap();
+    let paths = patch_util::export_patches(&repository_manager, &[oids[2], oids[1]], output_dir.path()).unwrap();
+    let mut patches = patch_util::read_mbox_files(&paths).unwrap();
+    // C twice; the second one fails as g.txt exists
+    patches.extend(patch_util::read_mbox_files(&paths[1..]).unwrap());
+
+    patch_util::abort_apply(&repository_manager, oids[3]).unwrap();
+    std::fs::write(test_repo.path().join("h.txt"), "untracked\n").unwrap();
+    assert!(patch_util::apply_patches(&repository_manager, &patches).is_err());
+    assert_eq!(3, commit_oids(&repo).len());
+
+    patch_util::abort_apply(&repository_manager, oids[3]).unwrap();
+    assert_eq!(vec![oids[3]], commit_oids(&repo));
+    assert!(repo.statuses(None).unwrap().iter().all(|x| x.path() == Some("h.txt")));
+    assert!(!test_repo.path().join("g.txt").exists());
+    assert_eq!("untracked\n", std::fs::read_to_string(test_repo.path().join("h.txt")).unwrap());
+}
+
+#[test]
+pub fn apply_to_dirty_tree() {
+    let test_repo = TestRepo::file_history();
+    let repo = open_with_committer(&test_repo);
+    let repository_manager = test_repo.repository_manager();
+
+    let oids = commit_oids(&repo);
+    let output_dir = TempDir::new("patches").unwrap();
+    let paths = patch_util::export_patches(&repository_manager, &[oids[2]], output_dir.path()).unwrap();
+    let patches = patch_util::read_mbox_files(&paths).unwrap();
+
+    // staged changes would be folded into the applied commit
+    std::fs::write(test_repo.path().join("h.txt"), "staged\n").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(std::path::Path::new("h.txt")).unwrap();
+    index.write().unwrap();
+
+    let result = patch_util::apply_patches(&repository_manager, &patches);
+    assert_eq!("Failed to apply patches: The work tree has uncommitted changes; commit or stash them first",
+               result.err().unwrap().to_string());
+    assert_eq!(oids, commit_oids(&repo));
+}
+
+#[test]
+pub fn export_and_apply_non_ascii_author() {
+    let test_repo = TestRepo::file_history();
+    let repo = open_with_committer(&test_repo);
+    let repository_manager = test_repo.repository_manager();
+
+    std::fs::write(test_repo.path().join("f.txt"), "bonjour\n").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(std::path::Path::new("f.txt")).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let author = git2::Signature::now("René François", "rene@example.com").unwrap();
+    let parent = repo.head().unwrap().peel_to_commit().unwrap();
+    let oid = repo.commit(Some("HEAD"), &author, &author, "Änderung für René", &tree, &[&parent]).unwrap();
+
+    let output_dir = TempDir::new("patches").unwrap();
+    let paths = patch_util::export_patches(&repository_manager, &[oid], output_dir.path()).unwrap();
+    let patches = patch_util::read_mbox_files(&paths).unwrap();
+
+    patch_util::abort_apply(&repository_manager, parent.id()).unwrap();
+    patch_util::apply_patches(&repository_manager, &patches).unwrap();
+
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(Some("René François"), head.author().name());
+    assert_eq!(Some("rene@example.com"), head.author().email());
+    assert_eq!(Some("Änderung für René"), head.summary());
+}
+
+#[test]
+pub fn parse_mbox_encoded_words() {
+    let content = "From 0123456789abcdef0123456789abcdef01234567 Mon Sep 17 00:00:00 2001\n\
+                   From: =?UTF-8?q?Ren=C3=A9?=\n =?UTF-8?q?_Fran=C3=A7ois?= <rene@example.com>\n\
+                   Date: Mon, 1 Jan 2024 12:00:00 +0100\n\
+                   Subject: [PATCH] =?UTF-8?B?w4RuZGVydW5nIGbD?=\n =?UTF-8?B?vHIgUmVuw6k=?= =?x?y?= =?ISO-8859-1?Q?caf=E9?=\n\
+                   \n\
+                   ---\n\
+                   diff --git a/f.txt b/f.txt\n\
+                   --- a/f.txt\n\
+                   +++ b/f.txt\n\
+                   @@ -1 +1 @@\n\
+                   -hello\n\
+                   +bonjour\n";
+
+    let patches = patch_util::parse_mbox(content).unwrap();
+    assert_eq!(1, patches.len());
+    assert_eq!("René François", patches[0].author_name);
+    assert_eq!("rene@example.com", patches[0].author_email);
+    // an invalid encoded word is kept as it is
+    assert_eq!("Änderung für René =?x?y?= café", patches[0].subject);
+}
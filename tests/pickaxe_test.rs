@@ -0,0 +1,91 @@
+extern crate git2;
+extern crate tempdir;
+extern crate metal_git;
+
+mod util;
+use std::sync::atomic::AtomicBool;
+
+use crate::util::test_repo::TestRepo;
+use metal_git::pickaxe::{self, PickaxeEvent, PickaxeQuery};
+
+fn matched_subjects(test_repo: &TestRepo, query: &PickaxeQuery) -> Vec<String> {
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+    let cancelled = AtomicBool::new(false);
+
+    let mut subjects = Vec::new();
+    let mut finished = false;
+    pickaxe::search(test_repo.path().to_str().unwrap(), query, &cancelled, |event| {
+        match event {
+            PickaxeEvent::Matched(oid) => {
+                let commit = repo.find_commit(oid).unwrap();
+                subjects.push(commit.summary().unwrap().to_string());
+            }
+            PickaxeEvent::Finished => finished = true,
+            _ => {}
+        }
+    }).unwrap();
+
+    assert!(finished);
+    subjects
+}
+
+#[test]
+pub fn search_occurrences() {
+    let test_repo = TestRepo::file_history();
+
+    let query = PickaxeQuery::Occurrences("foo".to_string());
+    assert_eq!(vec!["D", "B"], matched_subjects(&test_repo, &query));
+
+    let query = PickaxeQuery::Occurrences("world".to_string());
+    assert_eq!(vec!["C"], matched_subjects(&test_repo, &query));
+}
+
+#[test]
+pub fn search_changed_lines() {
+    let test_repo = TestRepo::file_history();
+
+    let query = PickaxeQuery::ChangedLines("fo+\\(".to_string());
+    assert_eq!(vec!["D", "B"], matched_subjects(&test_repo, &query));
+
+    let query = PickaxeQuery::ChangedLines("^h[a-z]+o$".to_string());
+    assert_eq!(vec!["A"], matched_subjects(&test_repo, &query));
+}
+
+#[test]
+pub fn find_in_line() {
+    let query = PickaxeQuery::ChangedLines("b+".to_string());
+    assert_eq!(vec![(1, 3), (4, 5)], query.find_in_line("abbab"));
+
+    let query = PickaxeQuery::Occurrences("ab".to_string());
+    assert_eq!(vec![(0, 2), (3, 5)], query.find_in_line("abbab"));
+
+    assert!(PickaxeQuery::ChangedLines("[a-".to_string()).validate().is_err());
+}
+
+#[test]
+pub fn unsupported_pattern_syntax() {
+    for pattern in &["foo|bar", "(foo)", "fo{2}", "a}"] {
+        let result = PickaxeQuery::ChangedLines(pattern.to_string()).validate();
+        assert!(result.err().unwrap().to_string().starts_with("Unsupported pattern syntax"), "{}", pattern);
+    }
+
+    // escaped, they are literals
+    let query = PickaxeQuery::ChangedLines("foo\\|bar\\(".to_string());
+    assert!(query.validate().is_ok());
+    assert_eq!(vec![(1, 9)], query.find_in_line("_foo|bar(_"));
+
+    // -S takes the text as it is
+    assert!(PickaxeQuery::Occurrences("foo|bar".to_string()).validate().is_ok());
+}
+
+#[test]
+pub fn matcher_without_exponential_backtracking() {
+    let matcher = PickaxeQuery::ChangedLines("a*a*a*a*a*a*a*a*a*a*c".to_string()).matcher().unwrap();
+    let line = "a".repeat(200);
+    assert!(matcher.find_in_line(&line).is_empty());
+    assert_eq!(vec![(0, 201)], matcher.find_in_line(&format!("{}c", line)));
+
+    let matcher = PickaxeQuery::ChangedLines("^x.*y$".to_string()).matcher().unwrap();
+    assert_eq!(vec![(0, 4)], matcher.find_in_line("x-+y\n"));
+    assert!(matcher.find_in_line("x-+y-").is_empty());
+}
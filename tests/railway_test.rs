@@ -5,6 +5,8 @@ extern crate metal_git;
 mod util;
 use crate::util::test_repo::TestRepo;
 use metal_git::railway;
+use std::thread::sleep;
+use std::time::Duration;
 
 #[test]
 pub fn collect_tree_flat_two() {
@@ -88,3 +90,92 @@ pub fn collect_tree_branch_merge_branch_merge() {
     assert_eq!("[0 => 0  => 0] | [1 => 1* => 0]", b.dump_tracks());
     assert_eq!("[0 => 0* => ]", a.dump_tracks());
 }
+
+#[test]
+pub fn collect_tree_with_stashes() {
+    let test_repo = TestRepo::file_history();
+    test_repo.set_committer_config();
+    let repository_manager = test_repo.repository_manager();
+
+    sleep(Duration::from_secs(1));
+    std::fs::write(test_repo.path().join("f.txt"), "stashed\n").unwrap();
+    std::fs::write(test_repo.path().join("h.txt"), "untracked\n").unwrap();
+    metal_git::stash_util::save_stash(&repository_manager, "S", true, false).unwrap();
+
+    let stations = railway::collect_tree(&repository_manager).unwrap();
+    assert_eq!(4, stations.len());
+
+    // the index and the untracked files commits of the stash are not shown
+    let options = railway::CollectOptions { with_stashes: true, ..Default::default() };
+    let stations = railway::collect_tree_with_options(&repository_manager, &options).unwrap();
+
+    println!("Stations:");
+    for station in stations.iter() {
+        println!("{}", station.dump_tracks());
+    }
+
+    assert_eq!(5, stations.len());
+    let (s, d) = (&stations[0], &stations[1]);
+    assert_eq!("On master: S", s.subject);
+    assert_eq!(vec![railway::RailwayRef { name: "stash@{0}".to_string(), kind: railway::RefKind::Stash }],
+               s.refs);
+    assert_eq!("D", d.subject);
+
+    assert_eq!("[ => 0* => 0]", s.dump_tracks());
+    assert_eq!("[0 => 0* => 0]", d.dump_tracks());
+}
+
+#[test]
+pub fn collect_tree_with_work_tree() {
+    let test_repo = TestRepo::file_history();
+    let repository_manager = test_repo.repository_manager();
+    let options = railway::CollectOptions { with_work_tree: true, ..Default::default() };
+
+    // a clean work tree has no pseudo station
+    let stations = railway::collect_tree_with_options(&repository_manager, &options).unwrap();
+    assert_eq!(4, stations.len());
+
+    std::fs::write(test_repo.path().join("f.txt"), "changed\n").unwrap();
+
+    let stations = railway::collect_tree_with_options(&repository_manager, &options).unwrap();
+    assert_eq!(5, stations.len());
+    let (w, d) = (&stations[0], &stations[1]);
+    assert!(w.is_work_tree);
+    assert!(w.oid.is_zero());
+    assert_eq!("D", d.subject);
+
+    assert_eq!("[ => 0* => 0]", w.dump_tracks());
+    assert_eq!("[0 => 0* => 0]", d.dump_tracks());
+}
+
+#[test]
+pub fn has_work_tree_station() {
+    let test_repo = TestRepo::file_history();
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+    assert!(!railway::has_work_tree_station(&repo).unwrap());
+
+    std::fs::write(test_repo.path().join("f.txt"), "changed\n").unwrap();
+    assert!(railway::has_work_tree_station(&repo).unwrap());
+
+    std::fs::write(test_repo.path().join("f.txt"), "hello\n").unwrap();
+    assert!(!railway::has_work_tree_station(&repo).unwrap());
+}
+
+#[test]
+pub fn collect_tree_ref_kinds() {
+    let test_repo = TestRepo::flat_two();
+
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    repo.reference("refs/remotes/origin/master", head.id(), false, "test").unwrap();
+    repo.tag_lightweight("v1.0", head.as_object(), false).unwrap();
+
+    let stations = railway::collect_tree(&test_repo.repository_manager()).unwrap();
+
+    let mut refs = stations[0].refs.iter().map(|x| (x.name.as_str(), x.kind)).collect::<Vec<_>>();
+    refs.sort_by_key(|x| x.0);
+    assert_eq!(vec![("master", railway::RefKind::LocalBranch),
+                    ("origin/master", railway::RefKind::RemoteBranch),
+                    ("v1.0", railway::RefKind::Tag)],
+               refs);
+}
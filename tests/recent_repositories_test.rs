@@ -0,0 +1,30 @@
+extern crate tempdir;
+extern crate metal_git;
+
+use tempdir::TempDir;
+
+use metal_git::recent_repositories::{RecentRepositories, MAX_RECENT_REPOSITORIES};
+
+#[test]
+pub fn add_and_list() {
+    let temp_dir = TempDir::new("recent").unwrap();
+    let recent = RecentRepositories::new(&temp_dir.path().join("config").join("recent_repositories"));
+
+    // the file does not exist yet
+    assert!(recent.list().is_empty());
+
+    recent.add("/a").unwrap();
+    recent.add("/b").unwrap();
+    recent.add("/a").unwrap();
+    assert_eq!(vec!["/a", "/b"], recent.list());
+
+    recent.remove("/a").unwrap();
+    assert_eq!(vec!["/b"], recent.list());
+
+    for i in 0..(MAX_RECENT_REPOSITORIES + 5) {
+        recent.add(&format!("/r{}", i)).unwrap();
+    }
+    let paths = recent.list();
+    assert_eq!(MAX_RECENT_REPOSITORIES, paths.len());
+    assert_eq!(format!("/r{}", MAX_RECENT_REPOSITORIES + 4), paths[0]);
+}
@@ -0,0 +1,275 @@
+extern crate git2;
+extern crate tempdir;
+extern crate metal_git;
+
+mod util;
+use std::fs;
+use std::path::Path;
+
+use git2::{Oid, Repository, Signature};
+use tempdir::TempDir;
+
+use crate::util::test_repo::TestRepo;
+use metal_git::error::MetalGitError;
+use metal_git::remote_util::{self, PullMode, PushOptions};
+
+struct Remotes {
+    local: TestRepo,
+    bare_dir: TempDir,
+    other_dir: TempDir,
+}
+
+impl Remotes {
+    // local --push--> bare <--clone-- other
+    fn new(url_prefix: &str) -> Remotes {
+        let local = TestRepo::file_history();
+        local.set_committer_config();
+
+        let bare_dir = TempDir::new("testrepo-bare").unwrap();
+        Repository::init_bare(bare_dir.path()).unwrap();
+
+        let url = format!("{}{}", url_prefix, bare_dir.path().to_str().unwrap());
+        Repository::open(local.path()).unwrap().remote("origin", &url).unwrap();
+        push(local.path(), &PushOptions::default()).unwrap();
+
+        let other_dir = TempDir::new("testrepo-other").unwrap();
+        let other = Repository::clone(bare_dir.path().to_str().unwrap(), other_dir.path()).unwrap();
+        let mut config = other.config().unwrap();
+        config.set_str("user.name", "other").unwrap();
+        config.set_str("user.email", "other@example.com").unwrap();
+
+        Remotes { local, bare_dir, other_dir }
+    }
+
+    fn local_repo(&self) -> Repository {
+        Repository::open(self.local.path()).unwrap()
+    }
+
+    fn bare_master(&self) -> Oid {
+        Repository::open(self.bare_dir.path()).unwrap().refname_to_id("refs/heads/master").unwrap()
+    }
+}
+
+fn commit_file(work_dir: &Path, path: &str, content: &str, message: &str) -> Oid {
+    let repo = Repository::open(work_dir).unwrap();
+    fs::write(work_dir.join(path), content).unwrap();
+
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new(path)).unwrap();
+    index.write().unwrap();
+
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let parent = repo.head().unwrap().peel_to_commit().unwrap();
+    let signature = Signature::now("test author", "author@example.com").unwrap();
+    repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &[&parent]).unwrap()
+}
+
+fn push(work_dir: &Path, options: &PushOptions) -> Result<String, MetalGitError> {
+    remote_util::push(work_dir.to_str().unwrap(), "origin", options, |_| {})
+}
+
+fn pull(work_dir: &Path, mode: PullMode) -> Result<String, MetalGitError> {
+    remote_util::pull(work_dir.to_str().unwrap(), "origin", mode, |_| {})
+}
+
+#[test]
+pub fn push_and_fetch() {
+    let remotes = Remotes::new("file://");
+    let local_repo = remotes.local_repo();
+
+    let head = local_repo.head().unwrap().target().unwrap();
+    assert_eq!(head, remotes.bare_master());
+    // the remote-tracking branch is updated by the push
+    assert_eq!(head, local_repo.refname_to_id("refs/remotes/origin/master").unwrap());
+
+    let other_head = commit_file(remotes.other_dir.path(), "o.txt", "other\n", "O");
+    push(remotes.other_dir.path(), &PushOptions::default()).unwrap();
+
+    let mut messages = Vec::new();
+    remote_util::fetch(remotes.local.path().to_str().unwrap(), "origin", |x| messages.push(x)).unwrap();
+
+    assert!(!messages.is_empty());
+    assert_eq!(other_head, local_repo.refname_to_id("refs/remotes/origin/master").unwrap());
+    assert_eq!(head, local_repo.head().unwrap().target().unwrap());
+}
+
+#[test]
+pub fn pull_fast_forward() {
+    let remotes = Remotes::new("");
+
+    let other_head = commit_file(remotes.other_dir.path(), "o.txt", "other\n", "O");
+    push(remotes.other_dir.path(), &PushOptions::default()).unwrap();
+
+    pull(remotes.local.path(), PullMode::Merge).unwrap();
+
+    assert_eq!(other_head, remotes.local_repo().head().unwrap().target().unwrap());
+    assert!(remotes.local.path().join("o.txt").exists());
+
+    assert_eq!("Already up to date", pull(remotes.local.path(), PullMode::Merge).unwrap());
+}
+
+#[test]
+pub fn pull_merge() {
+    let remotes = Remotes::new("");
+
+    let other_head = commit_file(remotes.other_dir.path(), "o.txt", "other\n", "O");
+    push(remotes.other_dir.path(), &PushOptions::default()).unwrap();
+    let local_head = commit_file(remotes.local.path(), "l.txt", "local\n", "L");
+
+    pull(remotes.local.path(), PullMode::Merge).unwrap();
+
+    let local_repo = remotes.local_repo();
+    let head = local_repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(vec![local_head, other_head], head.parent_ids().collect::<Vec<_>>());
+    assert_eq!(Some("Merge remote-tracking branch 'origin/master'"), head.message());
+    assert!(remotes.local.path().join("o.txt").exists());
+    assert!(remotes.local.path().join("l.txt").exists());
+}
+
+#[test]
+pub fn pull_rebase() {
+    let remotes = Remotes::new("");
+
+    let other_head = commit_file(remotes.other_dir.path(), "o.txt", "other\n", "O");
+    push(remotes.other_dir.path(), &PushOptions::default()).unwrap();
+    commit_file(remotes.local.path(), "l.txt", "local\n", "L");
+
+    pull(remotes.local.path(), PullMode::Rebase).unwrap();
+
+    let local_repo = remotes.local_repo();
+    let head = local_repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(vec![other_head], head.parent_ids().collect::<Vec<_>>());
+    assert_eq!(Some("L"), head.message());
+    assert_eq!(Some("test author"), head.author().name());
+    assert!(remotes.local.path().join("o.txt").exists());
+}
+
+#[test]
+pub fn pull_conflict_is_aborted() {
+    let remotes = Remotes::new("");
+
+    commit_file(remotes.other_dir.path(), "f.txt", "other\n", "O");
+    push(remotes.other_dir.path(), &PushOptions::default()).unwrap();
+    let local_head = commit_file(remotes.local.path(), "f.txt", "local\n", "L");
+
+    for mode in &[PullMode::Merge, PullMode::Rebase] {
+        assert!(pull(remotes.local.path(), *mode).is_err());
+
+        let local_repo = remotes.local_repo();
+        assert_eq!(local_head, local_repo.head().unwrap().target().unwrap());
+        assert_eq!(git2::RepositoryState::Clean, local_repo.state());
+        assert_eq!("local\n", fs::read_to_string(remotes.local.path().join("f.txt")).unwrap());
+    }
+}
+
+#[test]
+pub fn push_force_with_lease() {
+    let remotes = Remotes::new("");
+
+    let other_head = commit_file(remotes.other_dir.path(), "o.txt", "other\n", "O");
+    push(remotes.other_dir.path(), &PushOptions::default()).unwrap();
+    let local_head = commit_file(remotes.local.path(), "l.txt", "local\n", "L");
+
+    // non-fast-forward
+    assert!(push(remotes.local.path(), &PushOptions::default()).is_err());
+
+    // our remote-tracking branch is stale
+    let options = PushOptions { force_with_lease: true, ..Default::default() };
+    let err = push(remotes.local.path(), &options).unwrap_err();
+    assert!(err.to_string().contains("master on origin was updated since the last fetch"));
+    assert_eq!(other_head, remotes.bare_master());
+
+    remote_util::fetch(remotes.local.path().to_str().unwrap(), "origin", |_| {}).unwrap();
+    push(remotes.local.path(), &options).unwrap();
+    assert_eq!(local_head, remotes.bare_master());
+}
+
+#[test]
+pub fn push_tags() {
+    let remotes = Remotes::new("");
+
+    let local_repo = remotes.local_repo();
+    let head = local_repo.head().unwrap().peel_to_commit().unwrap();
+    local_repo.tag_lightweight("v1.0", head.as_object(), false).unwrap();
+
+    let options = PushOptions { tags: true, ..Default::default() };
+    push(remotes.local.path(), &options).unwrap();
+
+    let bare = Repository::open(remotes.bare_dir.path()).unwrap();
+    assert_eq!(head.id(), bare.refname_to_id("refs/tags/v1.0").unwrap());
+}
+
+#[test]
+pub fn manage_remotes() {
+    let remotes = Remotes::new("");
+    let local_repo = remotes.local_repo();
+
+    remote_util::add_remote(&local_repo, "upstream", "/tmp/upstream.git").unwrap();
+    assert_eq!(vec!["origin", "upstream"], remote_util::remote_names(&local_repo).unwrap());
+
+    remote_util::rename_remote(&local_repo, "upstream", "mirror").unwrap();
+    remote_util::set_remote_urls(&local_repo, "mirror", "/tmp/mirror.git", "/tmp/push.git").unwrap();
+    remote_util::set_fetch_refspecs(&local_repo,
+                                    "mirror",
+                                    &["+refs/heads/master:refs/remotes/mirror/master".to_string()]).unwrap();
+
+    let infos = remote_util::list_remotes(&local_repo).unwrap();
+    let mirror = infos.iter().find(|x| x.name == "mirror").unwrap();
+    assert_eq!("/tmp/mirror.git", mirror.url);
+    assert_eq!(Some("/tmp/push.git".to_string()), mirror.push_url);
+    assert_eq!(vec!["+refs/heads/master:refs/remotes/mirror/master"], mirror.fetch_refspecs);
+
+    remote_util::remove_remote(&local_repo, "mirror").unwrap();
+    assert_eq!(vec!["origin"], remote_util::remote_names(&local_repo).unwrap());
+}
+
+#[test]
+pub fn set_upstream_and_prune() {
+    let remotes = Remotes::new("");
+    let local_repo = remotes.local_repo();
+
+    let branches = remote_util::list_branches(&local_repo).unwrap();
+    assert_eq!(1, branches.len());
+    assert_eq!("master", branches[0].name);
+    assert_eq!(None, branches[0].upstream);
+
+    remote_util::set_upstream(&local_repo, "master", Some("origin/master")).unwrap();
+    let branches = remote_util::list_branches(&local_repo).unwrap();
+    assert_eq!(Some("origin/master".to_string()), branches[0].upstream);
+    assert_eq!(Some((0, 0)), branches[0].ahead_behind);
+
+    // a branch deleted on the remote is pruned
+    let bare = Repository::open(remotes.bare_dir.path()).unwrap();
+    let head = bare.head().unwrap().peel_to_commit().unwrap();
+    bare.branch("topic", &head, false).unwrap();
+    remote_util::fetch(remotes.local.path().to_str().unwrap(), "origin", |_| {}).unwrap();
+    assert_eq!(vec!["origin/master", "origin/topic"], remote_util::remote_branch_names(&local_repo).unwrap());
+
+    bare.find_branch("topic", git2::BranchType::Local).unwrap().delete().unwrap();
+    let pruned = remote_util::prune(&local_repo, "origin").unwrap();
+    assert_eq!(vec!["refs/remotes/origin/topic"], pruned);
+    assert_eq!(vec!["origin/master"], remote_util::remote_branch_names(&local_repo).unwrap());
+}
+
+#[test]
+pub fn ahead_behind() {
+    let remotes = Remotes::new("");
+    let local_repo = remotes.local_repo();
+
+    // no upstream yet
+    assert_eq!(None, remote_util::head_ahead_behind(&local_repo).unwrap());
+    remote_util::set_upstream(&local_repo, "master", Some("origin/master")).unwrap();
+
+    commit_file(remotes.other_dir.path(), "o.txt", "other\n", "O");
+    push(remotes.other_dir.path(), &PushOptions::default()).unwrap();
+    commit_file(remotes.local.path(), "l1.txt", "local\n", "L1");
+    commit_file(remotes.local.path(), "l2.txt", "local\n", "L2");
+    remote_util::fetch(remotes.local.path().to_str().unwrap(), "origin", |_| {}).unwrap();
+
+    assert_eq!(Some((2, 1)), remote_util::head_ahead_behind(&local_repo).unwrap());
+    assert_eq!(Some((2, 1)), remote_util::list_branches(&local_repo).unwrap()[0].ahead_behind);
+
+    assert_eq!("\u{2191}2 \u{2193}1", remote_util::format_ahead_behind((2, 1)));
+    assert_eq!("\u{2193}1", remote_util::format_ahead_behind((0, 1)));
+    assert_eq!("", remote_util::format_ahead_behind((0, 0)));
+}
@@ -5,6 +5,11 @@ extern crate metal_git;
 
 mod util;
 
+use std::fs;
+use std::path::Path;
+
+use git2::{BranchType, Repository};
+
 use metal_git::repository_manager;
 use crate::util::test_repo::TestRepo;
 
@@ -18,3 +23,57 @@ pub fn open() {
 	let _ = r.open();
 	// expect not to panic
 }
+
+#[test]
+pub fn open_without_path() {
+	let r = repository_manager::RepositoryManager::new();
+
+	assert!(r.open().is_err());
+}
+
+#[test]
+pub fn open_reuses_repository() {
+	let test_repo = TestRepo::flat_two();
+	let r = repository_manager::RepositoryManager::new();
+	r.set_work_dir_path(test_repo.path().to_str().unwrap());
+
+	let repo1 = r.open().unwrap();
+	let repo2 = r.open().unwrap();
+	assert!(std::rc::Rc::ptr_eq(&repo1, &repo2));
+
+	// changing the path discards the cache
+	let other_repo = TestRepo::flat_two();
+	r.set_work_dir_path(other_repo.path().to_str().unwrap());
+	let repo3 = r.open().unwrap();
+	assert!(!std::rc::Rc::ptr_eq(&repo1, &repo3));
+	assert_eq!(other_repo.path().canonicalize().unwrap(),
+	           repo3.workdir().unwrap().canonicalize().unwrap());
+}
+
+#[test]
+pub fn open_sees_external_changes() {
+	let test_repo = TestRepo::file_history();
+	let r = repository_manager::RepositoryManager::new();
+	r.set_work_dir_path(test_repo.path().to_str().unwrap());
+
+	let repo = r.open().unwrap();
+	let head = repo.head().unwrap().peel_to_commit().unwrap();
+	assert!(repo.index().unwrap().get_path(Path::new("external.txt"), 0).is_none());
+
+	// another handle like the git command
+	let external = Repository::open(test_repo.path()).unwrap();
+	external.branch("external", &external.find_commit(head.id()).unwrap(), false).unwrap();
+	fs::write(test_repo.path().join("external.txt"), "external\n").unwrap();
+	let mut index = external.index().unwrap();
+	index.add_path(Path::new("external.txt")).unwrap();
+	index.write().unwrap();
+
+	let repo = r.open().unwrap();
+	assert!(repo.find_branch("external", BranchType::Local).is_ok());
+	assert!(repo.index().unwrap().get_path(Path::new("external.txt"), 0).is_some());
+
+	// reopened after the repository is removed
+	drop(repo);
+	fs::remove_dir_all(test_repo.path().join(".git")).unwrap();
+	assert!(r.open().is_err());
+}
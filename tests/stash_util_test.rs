@@ -0,0 +1,50 @@
+extern crate git2;
+extern crate tempdir;
+extern crate metal_git;
+
+mod util;
+use std::fs;
+
+use crate::util::test_repo::TestRepo;
+use metal_git::stash_util;
+
+fn stash_messages(test_repo: &TestRepo) -> Vec<String> {
+    stash_util::list_stashes(&test_repo.repository_manager())
+        .unwrap()
+        .into_iter()
+        .map(|x| x.message)
+        .collect()
+}
+
+#[test]
+pub fn save_apply_pop_drop() {
+    let test_repo = TestRepo::file_history();
+    test_repo.set_committer_config();
+    let repository_manager = test_repo.repository_manager();
+    let f_path = test_repo.path().join("f.txt");
+    let h_path = test_repo.path().join("h.txt");
+
+    fs::write(&f_path, "hello\nstashed\n").unwrap();
+    fs::write(&h_path, "untracked\n").unwrap();
+
+    stash_util::save_stash(&repository_manager, "first", false, false).unwrap();
+    assert_eq!("hello\n", fs::read_to_string(&f_path).unwrap());
+    // untracked files are kept without the option
+    assert!(h_path.exists());
+
+    stash_util::save_stash(&repository_manager, "second", true, false).unwrap();
+    assert!(!h_path.exists());
+
+    assert_eq!(vec!["On master: second", "On master: first"], stash_messages(&test_repo));
+
+    stash_util::apply_stash(&repository_manager, 1).unwrap();
+    assert_eq!("hello\nstashed\n", fs::read_to_string(&f_path).unwrap());
+    assert_eq!(2, stash_messages(&test_repo).len());
+
+    stash_util::drop_stash(&repository_manager, 1).unwrap();
+    assert_eq!(vec!["On master: second"], stash_messages(&test_repo));
+
+    stash_util::pop_stash(&repository_manager, 0).unwrap();
+    assert!(h_path.exists());
+    assert!(stash_messages(&test_repo).is_empty());
+}
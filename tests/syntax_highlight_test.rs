@@ -0,0 +1,53 @@
+extern crate git2;
+extern crate tempdir;
+extern crate metal_git;
+
+mod util;
+use std::fs;
+use std::path::Path;
+
+use crate::util::test_repo::TestRepo;
+use metal_git::syntax_highlight::{self, Highlighter, SyntaxSpan, TokenKind};
+
+fn span(start: usize, end: usize, kind: TokenKind) -> SyntaxSpan {
+    SyntaxSpan { start, end, kind }
+}
+
+#[test]
+pub fn highlight_line() {
+    let rust = syntax_highlight::language_by_name("rust").unwrap();
+    let mut highlighter = Highlighter::new(rust);
+
+    assert_eq!(vec![
+        span(0, 3, TokenKind::Keyword),
+        span(8, 14, TokenKind::String),
+        span(16, 18, TokenKind::Number),
+        span(20, 26, TokenKind::Comment),
+    ], highlighter.highlight_line("let s = \"a\\\"b\"; 42; // end\n"));
+
+    // a block comment continues on the next lines
+    assert_eq!(vec![span(2, 6, TokenKind::Comment)], highlighter.highlight_line("x /* a\n"));
+    assert_eq!(vec![span(0, 3, TokenKind::Comment)], highlighter.highlight_line("b c\n"));
+    assert_eq!(vec![span(0, 2, TokenKind::Comment), span(3, 5, TokenKind::Keyword)],
+               highlighter.highlight_line("*/ fn"));
+}
+
+#[test]
+pub fn language_for_file() {
+    let test_repo = TestRepo::single();
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+    fs::write(test_repo.path().join(".gitattributes"), "*.in diff=python\n*.tmpl linguist-language=Ruby\n").unwrap();
+
+    let name = |path: &str| syntax_highlight::language_for_file(&repo, Path::new(path)).map(|x| x.name);
+
+    assert_eq!(Some("rust"), name("src/main.rs"));
+    assert_eq!(Some("cpp"), name("a.H"));
+    assert_eq!(Some("python"), name("setup.in"));
+    assert_eq!(Some("ruby"), name("page.tmpl"));
+    assert_eq!(None, name("README"));
+
+    // no word lists for languages that only look alike
+    assert_eq!(None, name("build.gradle.kts"));
+    assert_eq!(None, name("config.yaml"));
+    assert!(syntax_highlight::language_by_name("c#").is_none());
+}
@@ -71,6 +71,61 @@ fn test_commit<'repo, 'a>(repo: &'repo Repository,
 	commit
 }
 
+// like test_commit, with a flat tree of the given files
+fn test_commit_tree<'repo>(repo: &'repo Repository,
+	branch_name: &str,
+	message: &str,
+	files: &[(&str, &[u8])],
+	parents: &[&Commit]) -> Commit<'repo> {
+	let signature = Signature::now("test commit", "test@example.com").unwrap();
+	
+	let mut treebuilder = repo.treebuilder(None).unwrap();
+	for &(path, content) in files {
+		let blob_oid = repo.blob(content).unwrap();
+		treebuilder.insert(path, blob_oid, 0o100644).unwrap();
+	}
+	let tree = repo.find_tree(treebuilder.write().unwrap()).unwrap();
+	
+	let commit_oid = repo.commit(None, &signature, &signature, message, &tree, parents)
+	                     .expect("Failed to commit");
+	// the branch of HEAD cannot be updated by repo.branch()
+	repo.reference(&format!("refs/heads/{}", branch_name), commit_oid, true, message).unwrap();
+	
+	repo.find_commit(commit_oid).unwrap()
+}
+
+// commits files on HEAD of the work tree, keeping the index and the work tree updated
+fn test_commit_files<'repo>(repo: &'repo Repository,
+	message: &str,
+	files: &[(&str, Option<&str>)]) -> Commit<'repo> {
+	let signature = Signature::now("test commit", "test@example.com").unwrap();
+	let workdir = repo.workdir().unwrap().to_path_buf();
+	
+	let mut index = repo.index().unwrap();
+	for &(path, content) in files {
+		match content {
+			Some(content) => {
+				std::fs::write(workdir.join(path), content).unwrap();
+				index.add_path(Path::new(path)).unwrap();
+			}
+			None => {
+				std::fs::remove_file(workdir.join(path)).unwrap();
+				index.remove_path(Path::new(path)).unwrap();
+			}
+		}
+	}
+	index.write().unwrap();
+	
+	let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+	let parent = repo.head().ok().map(|x| x.peel_to_commit().unwrap());
+	let parents = parent.iter().collect::<Vec<_>>();
+	
+	let commit_oid = repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)
+	                     .expect("Failed to commit");
+	
+	repo.find_commit(commit_oid).unwrap()
+}
+
 #[allow(dead_code)]
 impl TestRepo {
 	fn new(prefix: &str) -> TestRepo {
@@ -98,6 +153,13 @@ impl TestRepo {
 		Repository::open(self.path()).expect("Failed to open a test repository.")
 	}
 	
+	// repo.signature() requires user.name and user.email
+	pub fn set_committer_config(&self) {
+		let mut config = self.repository().config().unwrap();
+		config.set_str("user.name", "test committer").unwrap();
+		config.set_str("user.email", "committer@example.com").unwrap();
+	}
+	
 	#[allow(dead_code)]
 	pub fn set_debug(&self) {
 		self.debug_mode.set(true);
@@ -150,6 +212,67 @@ impl TestRepo {
 	}
 	
 
+    // * D: remove foo() from f.txt
+    // * C: add g.txt
+    // * B: add foo() to f.txt
+    // * A: add f.txt
+	pub fn file_history() -> TestRepo {
+		let test_repo = Self::new("file_history");
+		let repo = test_repo.repository();
+		
+		test_commit_files(&repo, "A", &[("f.txt", Some("hello\n"))]);
+		sleep(Duration::from_secs(1));
+		test_commit_files(&repo, "B", &[("f.txt", Some("hello\nfoo()\n"))]);
+		sleep(Duration::from_secs(1));
+		test_commit_files(&repo, "C", &[("g.txt", Some("world\n"))]);
+		sleep(Duration::from_secs(1));
+		test_commit_files(&repo, "D", &[("f.txt", Some("hello\n"))]);
+		
+		test_repo
+	}
+	
+    // *   M: merge, changing both.txt, tail.txt and image.bin against both parents
+    // |\
+    // | * B: change only_b.txt and both.txt
+    // * | C: change both.txt
+    // |/
+    // * A
+	pub fn merge_with_contents() -> TestRepo {
+		let test_repo = Self::new("merge_with_contents");
+		let repo = test_repo.repository();
+		
+		let a = test_commit_tree(&repo, "master", "A", &[
+			("both.txt", b"a\nb\nc\nd\ne\nf\ng\nh\n"),
+			("image.bin", b"\0\x01\x02"),
+			("logo.bin", b"\0\x10"),
+			("only_b.txt", b"1\n2\n3\n"),
+			("tail.txt", b"x\ny\nz\n"),
+		], &[]);
+		let b = test_commit_tree(&repo, "branch1", "B", &[
+			("both.txt", b"a\nb\nc\nB-side\ne\nf\ng\nh\n"),
+			("image.bin", b"\0\x01\x02"),
+			("logo.bin", b"\0\x10"),
+			("only_b.txt", b"1\nTWO\n3\n"),
+			("tail.txt", b"x\ny\nz\n"),
+		], &[&a]);
+		let c = test_commit_tree(&repo, "master", "C", &[
+			("both.txt", b"a\nb\nc\nC-side\ne\nf\ng\nh\n"),
+			("image.bin", b"\0\x01\x02"),
+			("logo.bin", b"\0\x10"),
+			("only_b.txt", b"1\n2\n3\n"),
+			("tail.txt", b"x\ny\nz\n"),
+		], &[&a]);
+		let _ = test_commit_tree(&repo, "master", "M", &[
+			("both.txt", b"a\nb\nc\nmerged\ne\nf\ng\nh\n"),
+			("image.bin", b"\0\x03"),
+			("logo.bin", b"\0\x10"),
+			("only_b.txt", b"1\nTWO\n3\n"),
+			("tail.txt", b"x\ny\n"),
+		], &[&c, &b]);
+		
+		test_repo
+	}
+	
 	pub fn branch_merge_branch_merge() -> TestRepo {
 		let test_repo = Self::new("branch_merge_branch_merge");
 		
@@ -0,0 +1,63 @@
+extern crate git2;
+extern crate tempdir;
+extern crate metal_git;
+
+mod util;
+use std::fs;
+
+use crate::util::test_repo::TestRepo;
+use metal_git::watch_util::{self, ChangeKind, Changes};
+
+#[test]
+pub fn classify_change() {
+    let test_repo = TestRepo::file_history();
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+    fs::write(test_repo.path().join(".gitignore"), "*.log\ntarget/\n").unwrap();
+
+    let git_dir = repo.path().to_path_buf();
+    let work_dir = repo.workdir().unwrap().to_path_buf();
+
+    assert_eq!(Some(ChangeKind::Refs), watch_util::classify_change(&repo, &git_dir.join("HEAD")));
+    assert_eq!(Some(ChangeKind::Refs),
+               watch_util::classify_change(&repo, &git_dir.join("refs").join("heads").join("master")));
+    assert_eq!(Some(ChangeKind::Refs), watch_util::classify_change(&repo, &git_dir.join("packed-refs")));
+    assert_eq!(Some(ChangeKind::Index), watch_util::classify_change(&repo, &git_dir.join("index")));
+    assert_eq!(None, watch_util::classify_change(&repo, &git_dir.join("index.lock")));
+    assert_eq!(None, watch_util::classify_change(&repo, &git_dir.join("objects").join("ab")));
+    assert_eq!(None, watch_util::classify_change(&repo, &git_dir.join("config")));
+
+    assert_eq!(Some(ChangeKind::WorkTree), watch_util::classify_change(&repo, &work_dir.join("f.txt")));
+    assert_eq!(None, watch_util::classify_change(&repo, &work_dir.join("debug.log")));
+    assert_eq!(None, watch_util::classify_change(&repo, &work_dir.join("target").join("out")));
+}
+
+#[test]
+pub fn watched_directories() {
+    let test_repo = TestRepo::file_history();
+    let repo = git2::Repository::open(test_repo.path()).unwrap();
+    fs::write(test_repo.path().join(".gitignore"), "target/\n").unwrap();
+    fs::create_dir_all(test_repo.path().join("src").join("sub")).unwrap();
+    fs::create_dir_all(test_repo.path().join("target").join("debug")).unwrap();
+
+    let dirs = watch_util::watched_directories(&repo);
+    let work_dir = repo.workdir().unwrap();
+
+    assert!(dirs.iter().any(|x| x == repo.path()));
+    assert!(dirs.contains(&repo.path().join("refs").join("heads")));
+    assert!(dirs.iter().any(|x| x == work_dir));
+    assert!(dirs.contains(&work_dir.join("src").join("sub")));
+    assert!(!dirs.contains(&work_dir.join("target")));
+    assert!(!dirs.contains(&work_dir.join("target").join("debug")));
+    assert!(!dirs.iter().any(|x| x.starts_with(repo.path().join("objects"))));
+}
+
+#[test]
+pub fn changes() {
+    let mut changes = Changes::default();
+    assert!(changes.is_empty());
+
+    changes.add(ChangeKind::Index);
+    changes.add(ChangeKind::Index);
+    assert_eq!(Changes { refs: false, index: true, work_tree: false }, changes);
+    assert!(!changes.is_empty());
+}